@@ -0,0 +1,51 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Runtime.InteropServices;
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFIAmendResult
+    {
+        [MarshalAs(UnmanagedType.U1)]
+        public readonly bool native;
+        public readonly FFIResult cancel;
+        [MarshalAs(UnmanagedType.U1)]
+        public readonly bool replacementSent;
+        public readonly FFIResult replacement;
+        [MarshalAs(UnmanagedType.U1)]
+        public readonly bool restoreSent;
+        public readonly FFIResult restore;
+    }
+
+    // Outcome of every step of an amend. Without a native amend the order is canceled, replaced and,
+    // if the replacement is rejected, put back as it was.
+    public struct AmendResult
+    {
+        // The exchange amended the order itself, replacement is the amended order
+        public readonly bool native;
+        // Null if the original order was canceled
+        public readonly Exception cancelError;
+        // False when the cancel failed or the order filled before it was canceled
+        public readonly bool replacementSent;
+        public readonly Order? replacement;
+        public readonly Exception replacementError;
+        // True when the replacement was rejected and the rest of the original order was placed again
+        public readonly bool restoreSent;
+        public readonly Order? restored;
+        public readonly Exception restoreError;
+
+        public AmendResult(bool native, Exception cancelError, bool replacementSent, Order? replacement, Exception replacementError, bool restoreSent, Order? restored, Exception restoreError)
+        {
+            this.native = native;
+            this.cancelError = cancelError;
+            this.replacementSent = replacementSent;
+            this.replacement = replacement;
+            this.replacementError = replacementError;
+            this.restoreSent = restoreSent;
+            this.restored = restored;
+            this.restoreError = restoreError;
+        }
+
+        public bool Succeeded => replacement != null;
+    }
+}
\ No newline at end of file
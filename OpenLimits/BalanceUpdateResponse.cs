@@ -0,0 +1,19 @@
+namespace OpenLimits
+{
+    using System.Collections.Generic;
+
+    // Balances whose total or free amount changed since the previous poll
+    public class BalanceUpdateResponse
+    {
+        readonly public IEnumerable<Balance> balances;
+        readonly public DeliveryStats stats;
+        readonly public EventTimestamps timestamps;
+
+        public BalanceUpdateResponse(IEnumerable<Balance> balances, DeliveryStats stats, EventTimestamps timestamps)
+        {
+            this.balances = balances;
+            this.stats = stats;
+            this.timestamps = timestamps;
+        }
+    }
+}
\ No newline at end of file
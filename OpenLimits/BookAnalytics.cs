@@ -0,0 +1,85 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct BookAnalyticsConfig
+    {
+        // Levels per side used for imbalance and pressure
+        public readonly uint topLevels;
+        // 0 turns periodic pushes off
+        public readonly ulong pushIntervalMs;
+        // Also push analytics after every order book update
+        [MarshalAs(UnmanagedType.U1)]
+        public readonly bool pushOnUpdate;
+
+        public BookAnalyticsConfig(uint topLevels, ulong pushIntervalMs, bool pushOnUpdate)
+        {
+            this.topLevels = topLevels;
+            this.pushIntervalMs = pushIntervalMs;
+            this.pushOnUpdate = pushOnUpdate;
+        }
+
+        public static BookAnalyticsConfig Default = new BookAnalyticsConfig(5, 0, false);
+    }
+
+    // Computed in rust on a local copy of a subscription's order book.
+    // Every price field is 0 while the side it needs is empty.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct BookAnalytics
+    {
+        public readonly ulong updateId;
+        public readonly double bestBid;
+        public readonly double bestBidQty;
+        public readonly double bestAsk;
+        public readonly double bestAskQty;
+        public readonly double mid;
+        public readonly double spread;
+        public readonly double spreadBps;
+        // (bid qty - ask qty) / (bid qty + ask qty) over the top levels, from -1 (all asks) to 1 (all bids)
+        public readonly double imbalance;
+        // Mid weighted by the opposite side's top of book quantity
+        public readonly double microprice;
+        // Like imbalance, with every level weighted by 1 / (1 + its distance from mid in bps)
+        public readonly double pressure;
+
+        public override string ToString()
+        {
+            return "BookAnalytics{" +
+                "updateId=" + updateId +
+                ", bestBid=" + bestBid +
+                ", bestBidQty=" + bestBidQty +
+                ", bestAsk=" + bestAsk +
+                ", bestAskQty=" + bestAskQty +
+                ", mid=" + mid +
+                ", spread=" + spread +
+                ", spreadBps=" + spreadBps +
+                ", imbalance=" + imbalance +
+                ", microprice=" + microprice +
+                ", pressure=" + pressure +
+                '}';
+        }
+    }
+
+    // Liquidity resting within distanceBps of mid. 1% is 100 bps.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct DepthAtDistance
+    {
+        public readonly double distanceBps;
+        public readonly double bidQty;
+        public readonly double askQty;
+        public readonly double bidNotional;
+        public readonly double askNotional;
+
+        public override string ToString()
+        {
+            return "DepthAtDistance{" +
+                "distanceBps=" + distanceBps +
+                ", bidQty=" + bidQty +
+                ", askQty=" + askQty +
+                ", bidNotional=" + bidNotional +
+                ", askNotional=" + askNotional +
+                '}';
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,109 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Collections.Generic;
+    using System.Runtime.InteropServices;
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct BookReplayState
+    {
+        // Receive time of the last update applied, unix ms
+        public readonly ulong time;
+        public readonly ulong updateId;
+        public readonly ulong applied;
+        // Diffs skipped because they were older than the book
+        public readonly ulong skipped;
+        // False until a full snapshot was applied, the book may be missing levels before that
+        [MarshalAs(UnmanagedType.U1)]
+        public readonly bool hasSnapshot;
+
+        public override string ToString()
+        {
+            return "BookReplayState { time=" + time + ", updateId=" + updateId + ", applied=" + applied + ", skipped=" + skipped + ", hasSnapshot=" + hasSnapshot + "}";
+        }
+    }
+
+    // Rebuilds the order book of a market from a journal written with ExchangeClient.StartJournal,
+    // applying snapshots and diffs with the same rules as live data
+    public class BookReplay : IDisposable
+    {
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "open_book_replay", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult OpenBookReplay(string dir, string market, out IntPtr replay);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "book_replay_step", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult Step(IntPtr replay, [MarshalAs(UnmanagedType.U1)] out bool stepped);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "book_replay_seek", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult Seek(IntPtr replay, ulong time);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "book_replay_book", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetBook(IntPtr replay,
+            IntPtr bidsBuff, ulong bidsLen, out ulong actualBidsLen,
+            IntPtr asksBuff, ulong asksLen, out ulong actualAsksLen,
+            out BookReplayState state
+        );
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "free_book_replay", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern void FreeBookReplay(IntPtr replay);
+
+        readonly public string market;
+        private IntPtr _replay_handle;
+
+        public BookReplay(string dir, string market) {
+            this.market = market;
+            ExchangeClient.handleResult(OpenBookReplay(dir, market, out _replay_handle));
+        }
+
+        // Applies the next order book update, false once the journal is exhausted
+        public bool Step() {
+            ExchangeClient.handleResult(Step(_replay_handle, out var stepped));
+            return stepped;
+        }
+
+        // Moves to the book as it was at time (unix ms). Seeking backwards replays from the start.
+        public void SeekTo(ulong time) {
+            ExchangeClient.handleResult(Seek(_replay_handle, time));
+        }
+
+        public OrderbookResponse Orderbook() {
+            return Orderbook(512, out var state);
+        }
+
+        // The best depth levels of the replayed book and the state of the replay
+        unsafe public OrderbookResponse Orderbook(int depth, out BookReplayState state) {
+            var bids = new FFIAskBid[depth];
+            var asks = new FFIAskBid[depth];
+            var bidsList = new List<AskBid>();
+            var asksList = new List<AskBid>();
+
+            fixed (FFIAskBid* bidBuff = bids.AsSpan()) {
+                fixed (FFIAskBid* askBuff = asks.AsSpan()) {
+                    ExchangeClient.handleResult(
+                        GetBook(
+                            _replay_handle,
+                            (IntPtr)bidBuff, (ulong) depth, out var actualBidsLen,
+                            (IntPtr)askBuff, (ulong) depth, out var actualAsksLen,
+                            out state
+                        )
+                    );
+                    for (int i = 0 ; i < (int)actualBidsLen ; i ++) {
+                        bidsList.Add(bids[i].ToAskBid());
+                        bids[i].Dispose();
+                    }
+                    for (int i = 0 ; i < (int)actualAsksLen ; i ++) {
+                        asksList.Add(asks[i].ToAskBid());
+                        asks[i].Dispose();
+                    }
+                }
+            }
+            return new OrderbookResponse(market, asksList, bidsList, state.updateId, state.updateId);
+        }
+
+        public void Dispose() {
+            if (_replay_handle != IntPtr.Zero) {
+                FreeBookReplay(_replay_handle);
+                _replay_handle = IntPtr.Zero;
+            }
+        }
+    }
+}
\ No newline at end of file
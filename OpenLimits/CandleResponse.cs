@@ -0,0 +1,25 @@
+namespace OpenLimits
+{
+    public class CandleResponse
+    {
+        readonly public string market;
+        readonly public Interval interval;
+        readonly public Candle candle;
+
+        // False while trades can still update the candle
+        readonly public bool isClosed;
+
+        readonly public DeliveryStats stats;
+        readonly public EventTimestamps timestamps;
+
+        public CandleResponse(string market, Interval interval, Candle candle, bool isClosed, DeliveryStats stats, EventTimestamps timestamps)
+        {
+            this.market = market;
+            this.interval = interval;
+            this.candle = candle;
+            this.isClosed = isClosed;
+            this.stats = stats;
+            this.timestamps = timestamps;
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+namespace OpenLimits
+{
+    public enum ConnectionState
+    {
+        Connecting,
+        Connected,
+        Reconnecting,
+        GaveUp
+    }
+}
\ No newline at end of file
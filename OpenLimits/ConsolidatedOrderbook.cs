@@ -0,0 +1,145 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Collections.Generic;
+    using System.Runtime.InteropServices;
+
+    public struct BidOffer
+    {
+        // Null for an empty side
+        public readonly VenueLevel? bid;
+        public readonly VenueLevel? ask;
+
+        public BidOffer(VenueLevel? bid, VenueLevel? ask)
+        {
+            this.bid = bid;
+            this.ask = ask;
+        }
+    }
+
+    public class ConsolidatedDepth
+    {
+        readonly public IEnumerable<VenueLevel> bids;
+        readonly public IEnumerable<VenueLevel> asks;
+
+        public ConsolidatedDepth(IEnumerable<VenueLevel> bids, IEnumerable<VenueLevel> asks)
+        {
+            this.bids = bids;
+            this.asks = asks;
+        }
+    }
+
+    // Merges the order books of one asset pair from several exchange clients. Each venue is an order book
+    // subscription on one client, its levels stay tagged with the venue instead of being summed.
+    public class ConsolidatedOrderbook : IDisposable
+    {
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "create_consolidated_book", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult CreateConsolidatedBook(out IntPtr book);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "free_consolidated_book", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern void FreeConsolidatedBook(IntPtr book);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "consolidated_book_add_venue", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult AddVenue(IntPtr book, IntPtr client, IntPtr subhandle, string market, out uint venue, out ulong subscriptionId);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "consolidated_book_best", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult Best(IntPtr book, out FFIVenueLevel bid, out FFIVenueLevel ask);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "consolidated_book_venue_best", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult VenueBest(IntPtr book, uint venue, out FFIVenueLevel bid, out FFIVenueLevel ask);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "consolidated_book_depth", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult Depth(IntPtr book,
+            IntPtr bidsBuff, ulong bidsLen, out ulong actualBidsLen,
+            IntPtr asksBuff, ulong asksLen, out ulong actualAsksLen
+        );
+
+        readonly public string pair;
+        private IntPtr _book_handle;
+
+        // `pair` is only a label, each venue is added with its exchange specific market name
+        public ConsolidatedOrderbook(string pair) {
+            this.pair = pair;
+            ExchangeClient.handleResult(CreateConsolidatedBook(out _book_handle));
+        }
+
+        // Subscribes to the order book of `market` on the client. Returns the venue id levels are tagged with.
+        public uint AddVenue(ExchangeClient client, string market) {
+            ExchangeClient.handleResult(AddVenue(_book_handle, client.ClientHandle, client.SubHandle, market, out var venue, out var subscriptionId));
+            return venue;
+        }
+
+        private static BidOffer ToBidOffer(FFIVenueLevel bid, FFIVenueLevel ask) {
+            var levels = new BidOffer(bid.ToVenueLevel(), ask.ToVenueLevel());
+            bid.Dispose();
+            ask.Dispose();
+            return levels;
+        }
+
+        // Best bid and ask across all venues
+        public BidOffer BestBidOffer() {
+            ExchangeClient.handleResult(Best(_book_handle, out var bid, out var ask));
+            return ToBidOffer(bid, ask);
+        }
+
+        public BidOffer BestBidOffer(uint venue) {
+            ExchangeClient.handleResult(VenueBest(_book_handle, venue, out var bid, out var ask));
+            return ToBidOffer(bid, ask);
+        }
+
+        // Best ask minus best bid across all venues. Negative when the venues are crossed.
+        public decimal? Spread() {
+            var top = BestBidOffer();
+            if (top.bid == null || top.ask == null) {
+                return null;
+            }
+            return top.ask.Value.price - top.bid.Value.price;
+        }
+
+        // Profit per unit of buying at the best ask of `buyVenue` and selling at the best bid of `sellVenue`
+        public decimal? CrossVenueSpread(uint buyVenue, uint sellVenue) {
+            var ask = BestBidOffer(buyVenue).ask;
+            var bid = BestBidOffer(sellVenue).bid;
+            if (bid == null || ask == null) {
+                return null;
+            }
+            return bid.Value.price - ask.Value.price;
+        }
+
+        // The best `levels` bids and asks across all venues, best first
+        unsafe public ConsolidatedDepth Depth(int levels) {
+            var bids = new FFIVenueLevel[levels];
+            var asks = new FFIVenueLevel[levels];
+            var bidsList = new List<VenueLevel>();
+            var asksList = new List<VenueLevel>();
+
+            fixed (FFIVenueLevel* bidBuff = bids.AsSpan()) {
+                fixed (FFIVenueLevel* askBuff = asks.AsSpan()) {
+                    ExchangeClient.handleResult(
+                        Depth(
+                            _book_handle,
+                            (IntPtr)bidBuff, (ulong) levels, out var actualBidsLen,
+                            (IntPtr)askBuff, (ulong) levels, out var actualAsksLen
+                        )
+                    );
+                    for (int i = 0 ; i < (int)actualBidsLen ; i ++) {
+                        bidsList.Add(bids[i].ToVenueLevel().Value);
+                        bids[i].Dispose();
+                    }
+                    for (int i = 0 ; i < (int)actualAsksLen ; i ++) {
+                        asksList.Add(asks[i].ToVenueLevel().Value);
+                        asks[i].Dispose();
+                    }
+                }
+            }
+            return new ConsolidatedDepth(bidsList, asksList);
+        }
+
+        public void Dispose() {
+            if (_book_handle != IntPtr.Zero) {
+                FreeConsolidatedBook(_book_handle);
+                _book_handle = IntPtr.Zero;
+            }
+        }
+    }
+}
\ No newline at end of file
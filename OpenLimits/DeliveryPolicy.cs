@@ -0,0 +1,68 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    public enum DeliveryMode
+    {
+        All,
+        Conflate,
+        DropOldest
+    }
+
+    // Controls how events of a single subscription reach a slow consumer.
+    // Conflate merges order book updates and trades and delivers them at most every conflateMs,
+    // DropOldest keeps only the latest maxPending undelivered events.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct DeliveryPolicy
+    {
+        public readonly DeliveryMode mode;
+        public readonly ulong conflateMs;
+        public readonly ulong maxPending;
+
+        public DeliveryPolicy(DeliveryMode mode, ulong conflateMs, ulong maxPending)
+        {
+            this.mode = mode;
+            this.conflateMs = conflateMs;
+            this.maxPending = maxPending;
+        }
+
+        public static DeliveryPolicy All = new DeliveryPolicy(DeliveryMode.All, 0, 0);
+
+        public static DeliveryPolicy Conflate(ulong conflateMs) {
+            return new DeliveryPolicy(DeliveryMode.Conflate, conflateMs, 0);
+        }
+
+        public static DeliveryPolicy DropOldest(ulong maxPending) {
+            return new DeliveryPolicy(DeliveryMode.DropOldest, 0, maxPending);
+        }
+    }
+
+    public struct DeliveryStats
+    {
+        // Events lost to a full event queue right before this one
+        public readonly ulong dropped;
+        // Updates merged into this one by conflation
+        public readonly ulong conflated;
+        // Events of this subscription dropped or merged away so far
+        public readonly ulong totalDropped;
+        public readonly ulong totalConflated;
+
+        public DeliveryStats(ulong dropped, ulong conflated, ulong totalDropped, ulong totalConflated)
+        {
+            this.dropped = dropped;
+            this.conflated = conflated;
+            this.totalDropped = totalDropped;
+            this.totalConflated = totalConflated;
+        }
+
+        public override string ToString()
+        {
+            return "DeliveryStats{" +
+                "dropped=" + dropped +
+                ", conflated=" + conflated +
+                ", totalDropped=" + totalDropped +
+                ", totalConflated=" + totalConflated +
+                '}';
+        }
+    }
+}
\ No newline at end of file
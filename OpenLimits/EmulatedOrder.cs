@@ -0,0 +1,75 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Runtime.InteropServices;
+
+    public enum EmulatedKind
+    {
+        TrailingStop,
+        Oco,
+        Iceberg,
+        Bracket
+    }
+
+    // The strings are owned by the emulator and freed once the callback returns
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFIEmulatedOrder
+    {
+        public readonly ulong id;
+        public readonly EmulatedKind kind;
+        public readonly FFIOrder order;
+        public readonly IntPtr exchangeOrderId;
+        public readonly IntPtr triggerPrice;
+        public readonly ResultTag errorTag;
+        public readonly IntPtr errorMessage;
+
+        public EmulatedOrder ToEmulatedOrder() {
+            var message = this.errorMessage == IntPtr.Zero ? "Unknown error" : CString.ToString(this.errorMessage);
+            return new EmulatedOrder(
+                this.id,
+                this.kind,
+                this.order.ToOrder(),
+                CString.ToString(this.exchangeOrderId),
+                CString.ToString(this.triggerPrice),
+                ExchangeClient.errorFromResult(this.errorTag, message)
+            );
+        }
+    }
+
+    // Status of an order emulated by an OrderEmulator. `order` describes the whole emulated order,
+    // its id is the emulated id.
+    public struct EmulatedOrder
+    {
+        public readonly ulong id;
+        public readonly EmulatedKind kind;
+        public readonly Order order;
+        // Exchange order currently working for it, null if none
+        public readonly string exchangeOrderId;
+        // Price the local stop fires at, null if none is armed
+        public readonly string triggerPrice;
+        // Why the emulated order was rejected, null unless it was
+        public readonly Exception error;
+
+        public EmulatedOrder(ulong id, EmulatedKind kind, Order order, string exchangeOrderId, string triggerPrice, Exception error)
+        {
+            this.id = id;
+            this.kind = kind;
+            this.order = order;
+            this.exchangeOrderId = exchangeOrderId;
+            this.triggerPrice = triggerPrice;
+            this.error = error;
+        }
+
+        public override string ToString()
+        {
+            return "EmulatedOrder{" +
+                "id=" + id +
+                ", kind=" + kind +
+                ", order=" + order +
+                ", exchangeOrderId=" + exchangeOrderId +
+                ", triggerPrice=" + triggerPrice +
+                ", error=" + error +
+                "}";
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    public struct EventTimestamps
+    {
+        // Nanoseconds on a monotonic clock shared by every client in the process
+        public readonly ulong receivedMonotonicNs;
+        // Unix milliseconds when the message arrived
+        public readonly ulong receivedAt;
+        // Unix milliseconds reported by the exchange, 0 when the exchange does not send one
+        public readonly ulong exchangeTime;
+
+        public EventTimestamps(ulong receivedMonotonicNs, ulong receivedAt, ulong exchangeTime)
+        {
+            this.receivedMonotonicNs = receivedMonotonicNs;
+            this.receivedAt = receivedAt;
+            this.exchangeTime = exchangeTime;
+        }
+
+        public override string ToString()
+        {
+            return "EventTimestamps{" +
+                "receivedMonotonicNs=" + receivedMonotonicNs +
+                ", receivedAt=" + receivedAt +
+                ", exchangeTime=" + exchangeTime +
+                '}';
+        }
+    }
+
+    // Exchange to local receive latency over the most recent messages of a subscription.
+    // Negative values mean the exchange clock is ahead of the local one.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct LatencyStats
+    {
+        public readonly ulong samples;
+        public readonly long lastMs;
+        public readonly long minMs;
+        public readonly long maxMs;
+        public readonly double meanMs;
+        public readonly long p50Ms;
+        public readonly long p99Ms;
+
+        public override string ToString()
+        {
+            return "LatencyStats{" +
+                "samples=" + samples +
+                ", lastMs=" + lastMs +
+                ", minMs=" + minMs +
+                ", maxMs=" + maxMs +
+                ", meanMs=" + meanMs +
+                ", p50Ms=" + p50Ms +
+                ", p99Ms=" + p99Ms +
+                '}';
+        }
+    }
+}
\ No newline at end of file
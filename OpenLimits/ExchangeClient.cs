@@ -9,105 +9,212 @@ namespace OpenLimits
     public class ExchangeClient
     {
         static HashSet<ExchangeClient> _clients = new HashSet<ExchangeClient>();
-        private void handleResult(FFIResult result) {
+        // Native clients are never freed, the kill switch reaches disconnected clients too
+        static int _initializedClients = 0;
+        internal static void handleResult(FFIResult result) {
+            var error = errorFromFFIResult(result);
+            if (error != null) {
+                throw error;
+            }
+        }
+
+        // Frees the message of the result
+        internal static Exception errorFromFFIResult(FFIResult result) {
             string message = "Unknown error";
             if (result.message.ToInt64() != 0) {
                 message = CString.ToString(result.message);
                 FreeString(result.message);
             }
-            switch(result.tag) {
-                case ResultTag.Ok: return;
+            return errorFromResult(result.tag, message);
+        }
+
+        internal static Exception errorFromResult(ResultTag tag, string message) {
+            switch(tag) {
+                case ResultTag.Ok: return null;
                 case ResultTag.InvalidArgument:
-                    throw new ArgumentException(message);
+                    return new ArgumentException(message);
                 case ResultTag.BinanceError:
-                    throw new BinanceError(message);
+                    return new BinanceError(message);
                 case ResultTag.CoinbaseError:
-                    throw new CoinbaseError(message);
+                    return new CoinbaseError(message);
                 case ResultTag.NashProtocolError:
-                    throw new NashProtocolError(message);
+                    return new NashProtocolError(message);
                 case ResultTag.MissingImplementation:
-                    throw new MissingImplementation(message);
+                    return new MissingImplementation(message);
                 case ResultTag.AssetNotFound:
-                    throw new AssetNotFound(message);
+                    return new AssetNotFound(message);
                 case ResultTag.NoApiKeySet:
-                    throw new NoApiKeySet(message);
+                    return new NoApiKeySet(message);
                 case ResultTag.InternalServerError:
-                    throw new InternalServerError(message);
+                    return new InternalServerError(message);
                 case ResultTag.ServiceUnavailable:
-                    throw new ServiceUnavailable(message);
+                    return new ServiceUnavailable(message);
                 case ResultTag.Unauthorized:
-                    throw new Unauthorized(message);
+                    return new Unauthorized(message);
                 case ResultTag.SymbolNotFound:
-                    throw new SymbolNotFound(message);
+                    return new SymbolNotFound(message);
                 case ResultTag.SocketError:
-                    throw new SocketError(message);
+                    return new SocketError(message);
                 case ResultTag.GetTimestampFailed:
-                    throw new GetTimestampFailed(message);
+                    return new GetTimestampFailed(message);
                 case ResultTag.ReqError:
-                    throw new ReqError(message);
+                    return new ReqError(message);
                 case ResultTag.InvalidHeaderError:
-                    throw new InvalidHeaderError(message);
+                    return new InvalidHeaderError(message);
                 case ResultTag.InvalidPayloadSignature:
-                    throw new InvalidPayloadSignature(message);
+                    return new InvalidPayloadSignature(message);
                 case ResultTag.IoError:
-                    throw new IoError(message);
+                    return new IoError(message);
                 case ResultTag.PoisonError:
-                    throw new PoisonError(message);
+                    return new PoisonError(message);
                 case ResultTag.JsonError:
-                    throw new JsonError(message);
+                    return new JsonError(message);
                 case ResultTag.ParseFloatError:
-                    throw new ParseFloatError(message);
+                    return new ParseFloatError(message);
                 case ResultTag.UrlParserError:
-                    throw new UrlParserError(message);
+                    return new UrlParserError(message);
                 case ResultTag.Tungstenite:
-                    throw new Tungstenite(message);
+                    return new Tungstenite(message);
                 case ResultTag.TimestampError:
-                    throw new TimestampError(message);
+                    return new TimestampError(message);
                 case ResultTag.UnkownResponse:
-                    throw new UnkownResponse(message);
+                    return new UnkownResponse(message);
                 case ResultTag.NotParsableResponse:
-                    throw new NotParsableResponse(message);
+                    return new NotParsableResponse(message);
                 case ResultTag.MissingParameter:
-                    throw new MissingParameter(message);     
+                    return new MissingParameter(message);     
                 case ResultTag.WebSocketMessageNotSupported:
-                    throw new WebSocketMessageNotSupported(message);            }
+                    return new WebSocketMessageNotSupported(message);
+                case ResultTag.InitializeException:
+                    return new InitializeException(message);
+                case ResultTag.SubscribeException:
+                    return new SubscribeException(message);
+                case ResultTag.NoMarketPair:
+                    return new NoMarketPair(message);
+                case ResultTag.RiskRejected:
+                    return new RiskRejected(message);
+                case ResultTag.RateLimited:
+                    return new RateLimited(message);
+            }
+            return new OpenLimitsError(message);
         }
-        /// Used by rust to write data directly to C# thus avoiding changing ownership
-        private FFITrade[] subTradesBuff = new FFITrade[1024];
-        private FFIAskBid[] subAsksBuff = new FFIAskBid[1024];
-        private FFIAskBid[] subBidsBuff = new FFIAskBid[1024];
+        /// Stream events are read from the rust owned queue into this buffer by the event thread
+        private FFIEvent[] eventsBuff = new FFIEvent[256];
+        const ulong EventQueueCapacity = 4096;
 
         // Callbacks from rust into C#. Some callbacks come in a "private" and public version.
         // Some objects, especially those containing strings or array of objects will be serialized into a
         // C# version after arriving. Strings exchanged from rust to C# must be freed manually. So it is important not to expose
         // The internals
-        public delegate void OnError();
-        public delegate void OnPing();
+        public delegate void OnError(WebSocketError error);
+        public delegate void OnPing(Subscription subscription);
         public delegate void OnDisconnect();
+        public delegate void OnConnectionState(ConnectionState state, uint attempt);
         public delegate void OnOrderbook(OrderbookResponse orderbook);
-        unsafe private delegate void OnOrderbookFFI(ulong bidActualValueLen, ulong askActualValueLen, IntPtr market, ulong lastUpdateId, ulong updateId);
         public delegate void OnTrades(TradesResponse trades);
-        private delegate void OnTradesFFI(ulong bidActualValueLen, IntPtr market);
-        private OnError onErrorCb;
+        public delegate void OnCandle(CandleResponse candle);
+        public delegate void OnTicker(TickerResponse ticker);
+        public delegate void OnOrderUpdate(OrderUpdateResponse orders);
+        public delegate void OnFill(FillResponse fills);
+        public delegate void OnBalanceUpdate(BalanceUpdateResponse balances);
+        public delegate void OnEventsDropped(ulong count);
+        public delegate void OnDryRun(string simulated);
+        // Called with stale = true when a feed went silent, and with stale = false once data arrives again
+        public delegate void OnStaleFeed(Subscription subscription, bool stale, ulong silentMs);
+        public delegate void OnTradeStats(Subscription subscription, IEnumerable<TradeStats> stats);
+        public delegate void OnBookAnalytics(Subscription subscription, BookAnalytics analytics, IEnumerable<DepthAtDistance> depth);
         private List<OnError> onErrorCbs = new List<OnError>();
 
-        private OnPing onPingCb;
         private List<OnPing> onPingCbs = new List<OnPing>();
-        private OnOrderbookFFI onOrderbookCb;
-        private OnTradesFFI onTradesCb;
+        private List<OnEventsDropped> onEventsDroppedCbs = new List<OnEventsDropped>();
+        private List<OnStaleFeed> onStaleFeedCbs = new List<OnStaleFeed>();
+        private List<OnTradeStats> onTradeStatsCbs = new List<OnTradeStats>();
+        private List<OnBookAnalytics> onBookAnalyticsCbs = new List<OnBookAnalytics>();
+        private OnDryRun onDryRun;
+        // Kept alive for as long as rust may call it
+        private OnNativeDryRun onNativeDryRunCb;
+
+        // Every subscription is handed to rust as user data and routed straight back to its subscriber
+        private class OrderbookSubscriber {
+            public readonly string market;
+            public readonly OnOrderbook callback;
+
+            public OrderbookSubscriber(string market, OnOrderbook callback) {
+                this.market = market;
+                this.callback = callback;
+            }
+        }
+        private class TradesSubscriber {
+            public readonly string market;
+            public readonly OnTrades callback;
+
+            public TradesSubscriber(string market, OnTrades callback) {
+                this.market = market;
+                this.callback = callback;
+            }
+        }
+        private class CandleSubscriber {
+            public readonly string market;
+            public readonly Interval interval;
+            public readonly OnCandle callback;
+
+            public CandleSubscriber(string market, Interval interval, OnCandle callback) {
+                this.market = market;
+                this.interval = interval;
+                this.callback = callback;
+            }
+        }
+        private class TickerSubscriber {
+            public readonly string market;
+            public readonly OnTicker callback;
 
-        private Dictionary<string, List<OnOrderbook>> onOrderbookCbs = new Dictionary<string, List<OnOrderbook>>();
-        private Dictionary<string, List<OnTrades>> onTradesCbs = new Dictionary<string, List<OnTrades>>();
+            public TickerSubscriber(string market, OnTicker callback) {
+                this.market = market;
+                this.callback = callback;
+            }
+        }
+        private class OrderUpdateSubscriber {
+            public readonly string market;
+            public readonly OnOrderUpdate callback;
+
+            public OrderUpdateSubscriber(string market, OnOrderUpdate callback) {
+                this.market = market;
+                this.callback = callback;
+            }
+        }
+        private class FillSubscriber {
+            public readonly string market;
+            public readonly OnFill callback;
+
+            public FillSubscriber(string market, OnFill callback) {
+                this.market = market;
+                this.callback = callback;
+            }
+        }
+        private class BalanceUpdateSubscriber {
+            public readonly OnBalanceUpdate callback;
+
+            public BalanceUpdateSubscriber(OnBalanceUpdate callback) {
+                this.callback = callback;
+            }
+        }
+        private List<GCHandle> subscriberHandles = new List<GCHandle>();
     
         private OnDisconnect onDisconnectCb;
         private List<OnDisconnect> onDisconnectCbs = new List<OnDisconnect>();
 
+        private OnConnectionState onConnectionStateCb;
+        private List<OnConnectionState> onConnectionStateCbs = new List<OnConnectionState>();
+
 
        
-        const string NativeLib = "libopenlimits_sharp";
+        internal const string NativeLib = "libopenlimits_sharp";
 
         unsafe private IntPtr _client_handle;
         unsafe private IntPtr _sub_handle;
+        private readonly object subHandleLock = new object();
+        internal IntPtr ClientHandle => _client_handle;
+        internal IntPtr SubHandle => _sub_handle;
 
         [DllImport(NativeLib, EntryPoint = "free_string", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
         unsafe private static extern void FreeStringInternal(IntPtr handle);
@@ -120,7 +227,10 @@ namespace OpenLimits
         
         
         [DllImport(NativeLib, EntryPoint = "disconnect", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
-        unsafe internal static extern void Disconnect(IntPtr subhandle);
+        unsafe internal static extern FFIResult Disconnect(IntPtr subhandle);
+
+        [DllImport(NativeLib, EntryPoint = "free_subscriptions", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern void FreeSubscriptions(IntPtr subhandle);
 
         [DllImport(NativeLib, EntryPoint = "init_binance", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
         unsafe private static extern FFIResult InitBinance(BinanceClientConfig config, out IntPtr client);
@@ -135,13 +245,48 @@ namespace OpenLimits
         
         [DllImport(NativeLib, EntryPoint = "init_subscriptions", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
         unsafe private static extern FFIResult InitCbs(IntPtr client,
-            OnError onError, OnPing onPing, OnOrderbookFFI onOrderbook, OnTradesFFI onTrades, OnDisconnect onDisconnect,
-            IntPtr bidBuffPtr, UIntPtr bidBufLen,
-            IntPtr askBuffPtr, UIntPtr askBufLen,
-            IntPtr taskBuffPtr, UIntPtr tradeBufLen,
+            OnDisconnect onDisconnect,
+            OnConnectionState onConnectionState, ReconnectPolicy reconnectPolicy,
+            UIntPtr eventQueueCapacity,
             out IntPtr subhandle
         );
 
+        [DllImport(NativeLib, EntryPoint = "wait_events", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult WaitEvents(IntPtr subhandle, IntPtr buffPtr, UIntPtr valueBufLen, ulong timeoutMs, out UIntPtr actualValueLen);
+
+        [DllImport(NativeLib, EntryPoint = "poll_events", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult PollEvents(IntPtr subhandle, IntPtr buffPtr, UIntPtr valueBufLen, out UIntPtr actualValueLen);
+
+        [DllImport(NativeLib, EntryPoint = "get_latency_stats", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetLatencyStats(IntPtr subhandle, ulong subscriptionId, out LatencyStats stats);
+
+        [DllImport(NativeLib, EntryPoint = "set_stale_policy", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SetStalePolicy(IntPtr subhandle, ulong subscriptionId, StalePolicy policy);
+
+        [DllImport(NativeLib, EntryPoint = "enable_trade_stats", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult EnableTradeStats(IntPtr subhandle, ulong subscriptionId, ulong[] windowsMs, ulong windowsLen, ulong pushIntervalMs);
+
+        [DllImport(NativeLib, EntryPoint = "get_trade_stats", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetTradeStats(IntPtr subhandle, ulong subscriptionId, IntPtr buffPtr, ulong bufLen, out ulong actualLen);
+
+        [DllImport(NativeLib, EntryPoint = "enable_book_analytics", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult EnableBookAnalytics(IntPtr subhandle, ulong subscriptionId, BookAnalyticsConfig config, double[] distancesBps, ulong distancesLen);
+
+        [DllImport(NativeLib, EntryPoint = "disable_book_analytics", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult DisableBookAnalytics(IntPtr subhandle, ulong subscriptionId);
+
+        [DllImport(NativeLib, EntryPoint = "get_book_analytics", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetBookAnalytics(IntPtr subhandle, ulong subscriptionId, out BookAnalytics analytics, IntPtr depthBuffPtr, ulong depthBufLen, out ulong actualDepthLen);
+
+        [DllImport(NativeLib, EntryPoint = "free_events", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe internal static extern void FreeEvents(IntPtr buffPtr, UIntPtr len);
+
+        [DllImport(NativeLib, EntryPoint = "start_journal", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult StartJournal(IntPtr subhandle, string dir, JournalConfig config);
+
+        [DllImport(NativeLib, EntryPoint = "stop_journal", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult StopJournal(IntPtr subhandle);
+
 
         [DllImport(NativeLib, EntryPoint = "order_book", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
         unsafe private static extern FFIResult Orderbook(IntPtr client, string market,
@@ -173,19 +318,102 @@ namespace OpenLimits
             TimeInForce tif,
             ulong tifDuration,
             bool postOnly,
+            string clientOrderId,
             out FFIOrder order
         );
         
+        [DllImport(NativeLib, EntryPoint = "place_orders", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult PlaceOrders(IntPtr client, [In] FFIOrderRequest[] requests, ulong requestsLen, IntPtr results, IntPtr orders);
+
+        [DllImport(NativeLib, EntryPoint = "cancel_orders", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult CancelOrders(IntPtr client, [In] FFICancelRequest[] requests, ulong requestsLen, IntPtr results);
+
+        [DllImport(NativeLib, EntryPoint = "set_risk_limits", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SetRiskLimits(IntPtr client, RiskLimits limits);
+
+        [DllImport(NativeLib, EntryPoint = "set_position_limit", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SetPositionLimit(IntPtr client, string asset, string maxPosition);
+
+        [DllImport(NativeLib, EntryPoint = "engage_kill_switch", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult EngageKillSwitch(IntPtr results, UIntPtr bufLen, out UIntPtr actualLen);
+
+        [DllImport(NativeLib, EntryPoint = "release_kill_switch", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult ReleaseKillSwitchInternal();
+
+        [DllImport(NativeLib, EntryPoint = "get_kill_switch", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetKillSwitch([MarshalAs(UnmanagedType.U1)] out bool engaged);
+
+        [DllImport(NativeLib, EntryPoint = "set_retry_policy", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SetRetryPolicy(IntPtr client, FFIRetryPolicy policy, ulong retryable);
+
+        private delegate void OnNativeDryRun(IntPtr message, IntPtr userData);
+
+        [DllImport(NativeLib, EntryPoint = "set_dry_run", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SetDryRun(IntPtr client, [MarshalAs(UnmanagedType.U1)] bool enabled, OnNativeDryRun onSimulated, IntPtr userData);
+
+        [DllImport(NativeLib, EntryPoint = "set_rate_limits", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SetRateLimits(IntPtr client, RateLimits limits);
+
+        [DllImport(NativeLib, EntryPoint = "get_rate_limits", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetRateLimits(IntPtr client, out RateLimits limits);
+
+        [DllImport(NativeLib, EntryPoint = "set_request_weight", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SetRequestWeight(IntPtr client, Endpoint endpoint, uint weight);
+
+        [DllImport(NativeLib, EntryPoint = "get_rate_limit_usage", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetRateLimitUsage(IntPtr client, out RateLimitUsage usage);
+
+        [DllImport(NativeLib, EntryPoint = "set_order_validation", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SetOrderValidation(IntPtr client, ValidationMode mode);
+
+        [DllImport(NativeLib, EntryPoint = "normalize_order", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult NormalizeOrder(IntPtr client, string market, Side side, string qty, string price, ValidationMode mode, out IntPtr normalizedQty, out IntPtr normalizedPrice);
+
+        [DllImport(NativeLib, EntryPoint = "amend_order", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult AmendOrder(IntPtr client, string orderId, string market, string newPrice, string newSize, string clientOrderId,
+            out FFIAmendResult result,
+            out FFIOrder replacement,
+            out FFIOrder restored
+        );
+
+        [DllImport(NativeLib, EntryPoint = "place_stop_order", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult PlaceStopOrder(IntPtr client, string market,
+            string qty,
+            Side side,
+            string triggerPrice,
+            TriggerDirection direction,
+            string limitPrice,
+            TimeInForce tif,
+            ulong tifDuration,
+            string clientOrderId,
+            out FFIOrder order
+        );
+
         [DllImport(NativeLib, EntryPoint = "get_all_open_orders", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
         unsafe private static extern FFIResult GetAllOpenOrders(IntPtr client,
             IntPtr buffPtr, UIntPtr valueBufLen, out UIntPtr actualValueLen
         );
 
         [DllImport(NativeLib, EntryPoint = "subscribe_orderbook", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
-        unsafe private static extern FFIResult SubscribeToOrderbook(IntPtr client, IntPtr subhandle, string market);
+        unsafe private static extern FFIResult SubscribeToOrderbook(IntPtr client, IntPtr subhandle, string market, IntPtr onEvent, IntPtr userData, DeliveryPolicy delivery, out ulong subscriptionId);
 
         [DllImport(NativeLib, EntryPoint = "subscribe_trades", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
-        unsafe private static extern FFIResult SubscribeToTrades(IntPtr client, IntPtr subhandle, string market);
+        unsafe private static extern FFIResult SubscribeToTrades(IntPtr client, IntPtr subhandle, string market, IntPtr onEvent, IntPtr userData, DeliveryPolicy delivery, out ulong subscriptionId);
+
+        [DllImport(NativeLib, EntryPoint = "subscribe_candles", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SubscribeToCandles(IntPtr client, IntPtr subhandle, string market, Interval interval, IntPtr onEvent, IntPtr userData, DeliveryPolicy delivery, out ulong subscriptionId);
+
+        [DllImport(NativeLib, EntryPoint = "subscribe_ticker", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SubscribeToTicker(IntPtr client, IntPtr subhandle, string market, IntPtr onEvent, IntPtr userData, DeliveryPolicy delivery, out ulong subscriptionId);
+
+        [DllImport(NativeLib, EntryPoint = "subscribe_account_orders", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SubscribeToOrderUpdates(IntPtr client, IntPtr subhandle, string market, ulong pollIntervalMs, IntPtr onEvent, IntPtr userData, DeliveryPolicy delivery, out ulong subscriptionId);
+
+        [DllImport(NativeLib, EntryPoint = "subscribe_account_trades", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SubscribeToFills(IntPtr client, IntPtr subhandle, string market, ulong pollIntervalMs, IntPtr onEvent, IntPtr userData, DeliveryPolicy delivery, out ulong subscriptionId);
+
+        [DllImport(NativeLib, EntryPoint = "subscribe_account_balances", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult SubscribeToBalances(IntPtr client, IntPtr subhandle, ulong pollIntervalMs, IntPtr onEvent, IntPtr userData, DeliveryPolicy delivery, out ulong subscriptionId);
 
         [DllImport(NativeLib, EntryPoint = "get_order_history", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
         unsafe private static extern FFIResult GetOrderHistory(IntPtr client,
@@ -216,72 +444,109 @@ namespace OpenLimits
         [DllImport(NativeLib, EntryPoint = "get_order", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
         unsafe private static extern FFIResult GetOrder(IntPtr client,  string orderId, string market, out FFIOrder result);
 
+        [DllImport(NativeLib, EntryPoint = "get_order_by_client_id", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetOrderByClientId(IntPtr client, string clientOrderId, out FFIOrder result);
+
+        [DllImport(NativeLib, EntryPoint = "cancel_order_by_client_id", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult CancelOrderByClientId(IntPtr client, string clientOrderId);
+
+        [DllImport(NativeLib, EntryPoint = "forget_client_order_id", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult ForgetClientOrderId(IntPtr client, string clientOrderId);
+
         [DllImport(NativeLib, EntryPoint = "receive_pairs", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
         unsafe private static extern FFIResult ReceivePairs(IntPtr client, IntPtr buffPtr, UIntPtr valueBufLen, out UIntPtr actualValueLen);
 
         private void handleFFIResult(FFIResult result) {
         }
-        private void onPingHandler() {
-            foreach(var callback in this.onPingCbs) {
-                callback();
-            }
-        }
-        private void onErrorHandler() {
-            foreach(var callback in this.onErrorCbs) {
-                callback();
-            }
-        }
-        unsafe private void onTradesHandler(ulong tradeBuffLen, IntPtr marketStr) {
-            var market = CString.ToString(marketStr);
-            FreeString(marketStr);
-            var tradesList = new List<Trade>();
-            
-            for (int i = 0 ; i < (int)tradeBuffLen ; i ++) {
-                tradesList.Add(subTradesBuff[i].ToTrade());
-                subTradesBuff[i].Dispose();
-            }
-
-            if (!onTradesCbs.ContainsKey(market)) {
-                return;
-            }
-            var trades = new TradesResponse(market, tradesList);
-            this.onTradesCbs.TryGetValue(market, out var callbacks);
-            foreach(var callback in callbacks) {
-                callback(trades);
-            }
-        }
-        unsafe private void onOrderbookHandler(ulong bidActualValueLen, ulong askActualValueLen, IntPtr marketStr, ulong lastUpdateId, ulong updateId) {
-            var market = CString.ToString(marketStr);
-            FreeString(marketStr);
-           
-            var bidsList = new List<AskBid>();
-            var asksList = new List<AskBid>();
-
-            
-            for (int i = 0 ; i < (int)bidActualValueLen ; i ++) {
-                bidsList.Add(subBidsBuff[i].ToAskBid());
-                subBidsBuff[i].Dispose();
-            }
-            
-            for (int i = 0 ; i < (int)askActualValueLen ; i ++) {
-                asksList.Add(subAsksBuff[i].ToAskBid());
-                subAsksBuff[i].Dispose();
+        private void onEvent(FFIEvent ffiEvent) {
+            if (ffiEvent.dropped > 0) {
+                foreach(var callback in this.onEventsDroppedCbs) {
+                    callback(ffiEvent.dropped);
+                }
             }
 
-            if (!onOrderbookCbs.ContainsKey(market)) {
-                return;
-            }
-            var latestOrderbook = new OrderbookResponse(
-                market,
-                asksList,
-                bidsList,
-                lastUpdateId,
-                updateId
-            );
-
-            this.onOrderbookCbs.TryGetValue(market, out var callbacks);
-            foreach(var callback in callbacks) {
-                callback(latestOrderbook);
+            switch(ffiEvent.kind) {
+                case WebSocketEventKind.Ping:
+                    var subscription = ffiEvent.subscription.ToSubscription();
+                    foreach(var callback in this.onPingCbs) {
+                        callback(subscription);
+                    }
+                    break;
+                case WebSocketEventKind.Stale:
+                case WebSocketEventKind.Resumed:
+                    var staleSubscription = ffiEvent.subscription.ToSubscription();
+                    foreach(var callback in this.onStaleFeedCbs) {
+                        callback(staleSubscription, ffiEvent.kind == WebSocketEventKind.Stale, ffiEvent.silentMs);
+                    }
+                    break;
+                case WebSocketEventKind.TradeStats:
+                    var statsSubscription = ffiEvent.subscription.ToSubscription();
+                    var tradeStats = ffiEvent.ToTradeStats();
+                    foreach(var callback in this.onTradeStatsCbs) {
+                        callback(statsSubscription, tradeStats);
+                    }
+                    break;
+                case WebSocketEventKind.BookAnalytics:
+                    var analyticsSubscription = ffiEvent.subscription.ToSubscription();
+                    var depth = ffiEvent.ToDepth();
+                    foreach(var callback in this.onBookAnalyticsCbs) {
+                        callback(analyticsSubscription, ffiEvent.bookAnalytics, depth);
+                    }
+                    break;
+                case WebSocketEventKind.Error:
+                case WebSocketEventKind.Unsupported:
+                    var message = CString.ToString(ffiEvent.errorMessage);
+                    var error = new WebSocketError(ffiEvent.kind, ffiEvent.subscription.ToSubscription(), errorFromResult(ffiEvent.errorTag, message));
+                    foreach(var callback in this.onErrorCbs) {
+                        callback(error);
+                    }
+                    break;
+                case WebSocketEventKind.Trades:
+                    var tradesSubscriber = (TradesSubscriber) GCHandle.FromIntPtr(ffiEvent.subscription.userData).Target;
+                    tradesSubscriber.callback(new TradesResponse(tradesSubscriber.market, ffiEvent.ToTrades(), ffiEvent.ToDeliveryStats(), ffiEvent.ToTimestamps()));
+                    break;
+                case WebSocketEventKind.OrderbookSnapshot:
+                case WebSocketEventKind.OrderbookDiff:
+                    var orderbookSubscriber = (OrderbookSubscriber) GCHandle.FromIntPtr(ffiEvent.subscription.userData).Target;
+                    orderbookSubscriber.callback(new OrderbookResponse(
+                        orderbookSubscriber.market,
+                        ffiEvent.ToAsks(),
+                        ffiEvent.ToBids(),
+                        ffiEvent.lastUpdateId,
+                        ffiEvent.updateId,
+                        ffiEvent.kind == WebSocketEventKind.OrderbookSnapshot,
+                        ffiEvent.ToDeliveryStats(),
+                        ffiEvent.ToTimestamps()
+                    ));
+                    break;
+                case WebSocketEventKind.Candle:
+                case WebSocketEventKind.CandleClosed:
+                    var candleSubscriber = (CandleSubscriber) GCHandle.FromIntPtr(ffiEvent.subscription.userData).Target;
+                    candleSubscriber.callback(new CandleResponse(
+                        candleSubscriber.market,
+                        candleSubscriber.interval,
+                        ffiEvent.candle,
+                        ffiEvent.kind == WebSocketEventKind.CandleClosed,
+                        ffiEvent.ToDeliveryStats(),
+                        ffiEvent.ToTimestamps()
+                    ));
+                    break;
+                case WebSocketEventKind.Ticker:
+                    var tickerSubscriber = (TickerSubscriber) GCHandle.FromIntPtr(ffiEvent.subscription.userData).Target;
+                    tickerSubscriber.callback(new TickerResponse(tickerSubscriber.market, ffiEvent.ticker, ffiEvent.ToDeliveryStats(), ffiEvent.ToTimestamps()));
+                    break;
+                case WebSocketEventKind.OrderUpdate:
+                    var orderUpdateSubscriber = (OrderUpdateSubscriber) GCHandle.FromIntPtr(ffiEvent.subscription.userData).Target;
+                    orderUpdateSubscriber.callback(new OrderUpdateResponse(orderUpdateSubscriber.market, ffiEvent.ToOrders(), ffiEvent.ToDeliveryStats(), ffiEvent.ToTimestamps()));
+                    break;
+                case WebSocketEventKind.Fill:
+                    var fillSubscriber = (FillSubscriber) GCHandle.FromIntPtr(ffiEvent.subscription.userData).Target;
+                    fillSubscriber.callback(new FillResponse(fillSubscriber.market, ffiEvent.ToTrades(), ffiEvent.ToDeliveryStats(), ffiEvent.ToTimestamps()));
+                    break;
+                case WebSocketEventKind.BalanceUpdate:
+                    var balanceUpdateSubscriber = (BalanceUpdateSubscriber) GCHandle.FromIntPtr(ffiEvent.subscription.userData).Target;
+                    balanceUpdateSubscriber.callback(new BalanceUpdateResponse(ffiEvent.ToBalances(), ffiEvent.ToDeliveryStats(), ffiEvent.ToTimestamps()));
+                    break;
             }
         }
         EventWaitHandle ewh = new EventWaitHandle(false, EventResetMode.ManualReset);
@@ -293,60 +558,74 @@ namespace OpenLimits
             foreach(var callback in this.onDisconnectCbs) {
                 callback();
             }
-        }
 
-        unsafe private IntPtr InitCbs() {
-            _clients.Add(this);
-            fixed (FFIAskBid* bidBuff = subBidsBuff.AsSpan()) {
-                fixed (FFIAskBid* askBuff = subAsksBuff.AsSpan()) {
-                    fixed (FFITrade* tradeBuff = subTradesBuff.AsSpan()) {
-                        this.onOrderbookCb = this.onOrderbookHandler;
-                        this.onTradesCb = this.onTradesHandler;
-                        this.onPingCb = this.onPingHandler;
-                        this.onErrorCb = this.onErrorHandler;
-                        this.onTradesCb = this.onTradesHandler;
-                        this.onDisconnectCb = this.onDisconnect;
-                        InitCbs(
-                            _client_handle,
-                            this.onErrorCb,
-                            this.onPingCb,
-                            this.onOrderbookCb,
-                            this.onTradesCb,
-                            this.onDisconnectCb,
-
-                            (IntPtr)bidBuff, (UIntPtr)subBidsBuff.Length,
-                            (IntPtr)askBuff, (UIntPtr)subAsksBuff.Length,
-                            (IntPtr)tradeBuff, (UIntPtr)subTradesBuff.Length,
-                            out var handle
-                        );
-                        return handle;
-                    }
+            // Without an event pump nothing else uses the handle, otherwise the pump frees it once it stopped
+            lock (subHandleLock) {
+                if (ewhThreadHandle == null) {
+                    freeSubHandle();
                 }
             }
         }
 
-        unsafe public ExchangeClient(BinanceClientConfig config) {
+        // Callers hold subHandleLock
+        private void freeSubHandle() {
+            if (_sub_handle != IntPtr.Zero) {
+                FreeSubscriptions(_sub_handle);
+                _sub_handle = IntPtr.Zero;
+            }
+        }
+
+        private void onConnectionStateHandler(ConnectionState state, uint attempt) {
+            foreach(var callback in this.onConnectionStateCbs) {
+                callback(state, attempt);
+            }
+        }
+
+        unsafe private IntPtr InitCbs(ReconnectPolicy reconnectPolicy) {
+            _clients.Add(this);
+            Interlocked.Increment(ref _initializedClients);
+            this.onDisconnectCb = this.onDisconnect;
+            this.onConnectionStateCb = this.onConnectionStateHandler;
+            InitCbs(
+                _client_handle,
+                this.onDisconnectCb,
+                this.onConnectionStateCb,
+                reconnectPolicy,
+                (UIntPtr)EventQueueCapacity,
+                out var handle
+            );
+            return handle;
+        }
+
+        public ExchangeClient(BinanceClientConfig config): this(config, ReconnectPolicy.Default) { }
+
+        unsafe public ExchangeClient(BinanceClientConfig config, ReconnectPolicy reconnectPolicy) {
             handleResult(
                 ExchangeClient.InitBinance(config, out var client_handle)
             );
             
             _client_handle = client_handle;
-            _sub_handle = InitCbs();
+            _sub_handle = InitCbs(reconnectPolicy);
         }
 
-        unsafe public ExchangeClient(NashClientConfig config) {
+        public ExchangeClient(NashClientConfig config): this(config, ReconnectPolicy.Default) { }
+
+        unsafe public ExchangeClient(NashClientConfig config, ReconnectPolicy reconnectPolicy) {
             handleResult(
                 ExchangeClient.InitNash(config.apikey, config.secret, config.clientId, config.environment, config.timeout, config.affiliateCode, out var client_handle)
             );
             _client_handle = client_handle;
-            _sub_handle = InitCbs();
+            _sub_handle = InitCbs(reconnectPolicy);
         }
-        unsafe public ExchangeClient(CoinbaseClientConfig config) {
+
+        public ExchangeClient(CoinbaseClientConfig config): this(config, ReconnectPolicy.Default) { }
+
+        unsafe public ExchangeClient(CoinbaseClientConfig config, ReconnectPolicy reconnectPolicy) {
             handleResult(
                 ExchangeClient.InitCoinbase(config.apikey, config.secret, config.passphrase, config.sandbox, out var client_handle)
             );
             _client_handle = client_handle;
-            _sub_handle = InitCbs();
+            _sub_handle = InitCbs(reconnectPolicy);
         }
 
         unsafe public double GetPriceTicker(string market) {
@@ -450,6 +729,7 @@ namespace OpenLimits
                 request.timeInForce,
                 request.timeInForceDurationMs,
                 request.postOnly,
+                request.clientOrderId,
                 out FFIOrder ffiOrder
             ));
             var order = ffiOrder.ToOrder();
@@ -467,6 +747,7 @@ namespace OpenLimits
                 request.timeInForce,
                 request.timeInForceDurationMs,
                 request.postOnly,
+                request.clientOrderId,
                 out FFIOrder ffiOrder
             ));
             var order = ffiOrder.ToOrder();
@@ -485,6 +766,27 @@ namespace OpenLimits
                 TimeInForce.GTC,
                 0,
                 false,
+                request.clientOrderId,
+                out FFIOrder ffiOrder
+            ));
+            var order = ffiOrder.ToOrder();
+            ffiOrder.Dispose();
+            return order;
+        }
+
+        // Fails with MissingImplementation on exchanges without a native stop order endpoint
+        unsafe public Order PlaceStopOrder(StopOrderRequest request) {
+            handleResult(ExchangeClient.PlaceStopOrder(
+                _client_handle,
+                request.market,
+                request.size,
+                request.side,
+                request.triggerPrice,
+                request.direction,
+                request.limitPrice,
+                request.timeInForce,
+                request.timeInForceDurationMs,
+                request.clientOrderId,
                 out FFIOrder ffiOrder
             ));
             var order = ffiOrder.ToOrder();
@@ -500,6 +802,185 @@ namespace OpenLimits
             ));
         }
 
+        // Sends all orders concurrently. A failed order doesn't stop the others, check every result.
+        unsafe public List<BatchOrderResult> PlaceOrders(IList<OrderRequest> requests) {
+            var ffiRequests = new FFIOrderRequest[requests.Count];
+            for (int i = 0 ; i < requests.Count ; i ++) {
+                ffiRequests[i] = requests[i].ToFFI();
+            }
+            var results = new FFIResult[requests.Count];
+            var orders = new FFIOrder[requests.Count];
+            var batch = new List<BatchOrderResult>();
+            fixed (FFIResult* resultsBuff = results.AsSpan()) {
+                fixed (FFIOrder* ordersBuff = orders.AsSpan()) {
+                    handleResult(ExchangeClient.PlaceOrders(_client_handle, ffiRequests, (ulong)ffiRequests.Length, (IntPtr)resultsBuff, (IntPtr)ordersBuff));
+                }
+            }
+            for (int i = 0 ; i < requests.Count ; i ++) {
+                var error = errorFromFFIResult(results[i]);
+                if (error != null) {
+                    batch.Add(new BatchOrderResult(requests[i], null, error));
+                    continue;
+                }
+                batch.Add(new BatchOrderResult(requests[i], orders[i].ToOrder(), null));
+                orders[i].Dispose();
+            }
+            return batch;
+        }
+
+        // Cancels all orders concurrently. A failed cancellation doesn't stop the others, check every result.
+        unsafe public List<BatchCancelResult> CancelOrders(IList<string> orderIds, string market) {
+            var ffiRequests = new FFICancelRequest[orderIds.Count];
+            for (int i = 0 ; i < orderIds.Count ; i ++) {
+                ffiRequests[i] = new FFICancelRequest { orderId = orderIds[i], market = market };
+            }
+            var results = new FFIResult[orderIds.Count];
+            var batch = new List<BatchCancelResult>();
+            fixed (FFIResult* resultsBuff = results.AsSpan()) {
+                handleResult(ExchangeClient.CancelOrders(_client_handle, ffiRequests, (ulong)ffiRequests.Length, (IntPtr)resultsBuff));
+            }
+            for (int i = 0 ; i < orderIds.Count ; i ++) {
+                batch.Add(new BatchCancelResult(orderIds[i], errorFromFFIResult(results[i])));
+            }
+            return batch;
+        }
+
+        unsafe public List<BatchCancelResult> CancelOrders(IList<string> orderIds) {
+            return CancelOrders(orderIds, null);
+        }
+
+        // Sets how orders are checked against their market's increments and minimums before they are sent, Reject by default
+        // Orders breaching a limit fail with RiskRejected before they are sent
+        unsafe public void SetRiskLimits(RiskLimits limits) {
+            handleResult(ExchangeClient.SetRiskLimits(_client_handle, limits));
+        }
+
+        // maxPosition is the largest absolute balance of asset orders may lead to, counting working orders. null removes the limit.
+        unsafe public void SetPositionLimit(string asset, string maxPosition) {
+            handleResult(ExchangeClient.SetPositionLimit(_client_handle, asset, maxPosition));
+        }
+
+        // Blocks new orders on every client and cancels all their open orders.
+        // Returns the errors of the clients whose orders could not be canceled.
+        unsafe public static List<Exception> EngageKillSwitch() {
+            var results = new FFIResult[Math.Max(_initializedClients, 1)];
+            UIntPtr actualLen;
+            fixed (FFIResult* resultsBuff = results.AsSpan()) {
+                handleResult(ExchangeClient.EngageKillSwitch((IntPtr)resultsBuff, (UIntPtr)results.Length, out actualLen));
+            }
+            var errors = new List<Exception>();
+            for (int i = 0 ; i < (int)actualLen ; i ++) {
+                var error = errorFromFFIResult(results[i]);
+                if (error != null) {
+                    errors.Add(error);
+                }
+            }
+            return errors;
+        }
+
+        // Orders canceled by the kill switch stay canceled
+        unsafe public static void ReleaseKillSwitch() {
+            handleResult(ExchangeClient.ReleaseKillSwitchInternal());
+        }
+
+        unsafe public static bool KillSwitchEngaged {
+            get {
+                handleResult(ExchangeClient.GetKillSwitch(out bool engaged));
+                return engaged;
+            }
+        }
+
+        // Clients start with RetryPolicy.Default
+        unsafe public void SetRetryPolicy(RetryPolicy policy) {
+            handleResult(ExchangeClient.SetRetryPolicy(_client_handle, policy.ToFFI(), policy.RetryableTags()));
+        }
+
+        // In dry run PlaceOrder, PlaceOrders, CancelOrder, CancelOrders and CancelAllOrders are validated
+        // and risk checked but never sent. Each call is reported to onSimulated, which is required when
+        // enabling, and placements return orders with simulated set. Simulated orders stay cancelable after
+        // dry run is turned off; AmendOrder throws for them and while in dry run. CancelAllOrders in dry
+        // run only cancels and returns the open simulated orders, the exchange is not read.
+        unsafe public void SetDryRun(bool enabled, OnDryRun onSimulated = null) {
+            this.onDryRun = onSimulated;
+            this.onNativeDryRunCb = onSimulated == null ? null : new OnNativeDryRun(this.onNativeDryRun);
+            handleResult(ExchangeClient.SetDryRun(_client_handle, enabled, this.onNativeDryRunCb, IntPtr.Zero));
+        }
+
+        private void onNativeDryRun(IntPtr message, IntPtr userData) {
+            // The message is owned by rust
+            var onDryRun = this.onDryRun;
+            if (onDryRun != null) {
+                onDryRun(CString.ToString(message));
+            }
+        }
+
+        // Calls wait for budget up to limits.maxWaitMs and throw RateLimited past it.
+        // The order emulator and account streams share the budget and wait as long as it takes.
+        unsafe public void SetRateLimits(RateLimits limits) {
+            handleResult(ExchangeClient.SetRateLimits(_client_handle, limits));
+        }
+
+        unsafe public RateLimits GetRateLimits() {
+            handleResult(ExchangeClient.GetRateLimits(_client_handle, out var limits));
+            return limits;
+        }
+
+        unsafe public void SetRequestWeight(Endpoint endpoint, uint weight) {
+            handleResult(ExchangeClient.SetRequestWeight(_client_handle, endpoint, weight));
+        }
+
+        unsafe public RateLimitUsage GetRateLimitUsage() {
+            handleResult(ExchangeClient.GetRateLimitUsage(_client_handle, out var usage));
+            return usage;
+        }
+
+        unsafe public void SetOrderValidation(ValidationMode mode) {
+            handleResult(ExchangeClient.SetOrderValidation(_client_handle, mode));
+        }
+
+        // Checks or rounds size and price like placing the order with `mode` would, without sending it. price is null for market orders.
+        unsafe public NormalizedOrder NormalizeOrder(string market, Side side, string size, string price, ValidationMode mode) {
+            handleResult(ExchangeClient.NormalizeOrder(_client_handle, market, side, size, price, mode, out var normalizedSize, out var normalizedPrice));
+            var normalized = new NormalizedOrder(CString.ToString(normalizedSize), CString.ToString(normalizedPrice));
+            FreeString(normalizedSize);
+            FreeString(normalizedPrice);
+            return normalized;
+        }
+
+        // Changes the price and/or size of an open limit order, null keeps the current value. newSize is the
+        // quantity left to work afterwards. Check the result, a failed step doesn't throw.
+        unsafe public AmendResult AmendOrder(string orderId, string market, string newPrice, string newSize, string clientOrderId) {
+            handleResult(ExchangeClient.AmendOrder(
+                _client_handle,
+                orderId,
+                market,
+                newPrice,
+                newSize,
+                clientOrderId,
+                out var result,
+                out var ffiReplacement,
+                out var ffiRestored
+            ));
+            var cancelError = errorFromFFIResult(result.cancel);
+            var replacementError = errorFromFFIResult(result.replacement);
+            var restoreError = errorFromFFIResult(result.restore);
+            Order? replacement = null;
+            if (result.replacementSent && replacementError == null) {
+                replacement = ffiReplacement.ToOrder();
+                ffiReplacement.Dispose();
+            }
+            Order? restored = null;
+            if (result.restoreSent && restoreError == null) {
+                restored = ffiRestored.ToOrder();
+                ffiRestored.Dispose();
+            }
+            return new AmendResult(result.native, cancelError, result.replacementSent, replacement, replacementError, result.restoreSent, restored, restoreError);
+        }
+
+        unsafe public AmendResult AmendOrder(string orderId, string market, string newPrice, string newSize) {
+            return AmendOrder(orderId, market, newPrice, newSize, null);
+        }
+
         unsafe public Order GetOrder(string orderId, string market) {
             handleResult(ExchangeClient.GetOrder(
                 _client_handle,
@@ -517,6 +998,37 @@ namespace OpenLimits
             CancelOrder(orderId, null);
         }
 
+        // Client order ids are never sent to the exchange. They only find orders placed through this client since
+        // it was created, and not the order of a placement that failed without a definite answer: its id stays
+        // unresolved until open orders are checked by hand and ForgetClientOrderId is called.
+        unsafe public Order GetOrderByClientId(string clientOrderId) {
+            handleResult(ExchangeClient.GetOrderByClientId(
+                _client_handle,
+                clientOrderId,
+                out var result
+            ));
+
+            var order = result.ToOrder();
+            result.Dispose();
+            return order;
+        }
+
+        unsafe public void CancelOrderByClientId(string clientOrderId) {
+            handleResult(ExchangeClient.CancelOrderByClientId(
+                _client_handle,
+                clientOrderId
+            ));
+        }
+
+        // Call once open orders show that a placement which failed without a definite answer did not go through,
+        // so the client order id can be placed again
+        unsafe public void ForgetClientOrderId(string clientOrderId) {
+            handleResult(ExchangeClient.ForgetClientOrderId(
+                _client_handle,
+                clientOrderId
+            ));
+        }
+
         unsafe public Order MarketSell(MarketOrderRequest request) {
             handleResult(ExchangeClient.PlaceOrder(
                 _client_handle,
@@ -528,6 +1040,7 @@ namespace OpenLimits
                 TimeInForce.GTC,
                 0,
                 false,
+                request.clientOrderId,
                 out FFIOrder ffiOrder
             ));
             var order = ffiOrder.ToOrder();
@@ -658,17 +1171,57 @@ namespace OpenLimits
             return pairs;
         }
 
-        private void WaitForEwh() {
-            ewh.WaitOne();
+        // Reads events off the rust queue until the subscription thread disconnects.
+        // Subscriber callbacks run on this thread, never on the rust subscription thread.
+        unsafe private void PumpEvents() {
+            while (!ewh.WaitOne(0)) {
+                fixed (FFIEvent* buff = eventsBuff.AsSpan()) {
+                    handleFFIResult(WaitEvents(this._sub_handle, (IntPtr)buff, (UIntPtr)eventsBuff.Length, 100, out var actualLen));
+                    try {
+                        for (int i = 0 ; i < (int)actualLen ; i ++) {
+                            this.onEvent(eventsBuff[i]);
+                        }
+                    } finally {
+                        FreeEvents((IntPtr)buff, actualLen);
+                    }
+                }
+            }
+
+            foreach(var handle in this.subscriberHandles) {
+                handle.Free();
+            }
+            this.subscriberHandles.Clear();
+
+            lock (subHandleLock) {
+                freeSubHandle();
+            }
         }
 
         private void SetupEWH() {
-            if (ewhThreadHandle != null) {
-                return;
+            lock (subHandleLock) {
+                if (ewhThreadHandle != null || _sub_handle == IntPtr.Zero) {
+                    return;
+                }
+
+                ewhThreadHandle = new Thread(this.PumpEvents);
+                ewhThreadHandle.Start();
             }
+        }
+
+        public void SubscribeToEventsDropped(OnEventsDropped cb) {
+            this.onEventsDroppedCbs.Add(cb);
+        }
+
+        public void SubscribeToStaleFeeds(OnStaleFeed cb) {
+            this.onStaleFeedCbs.Add(cb);
+        }
+
+        public void SubscribeToTradeStats(OnTradeStats cb) {
+            this.onTradeStatsCbs.Add(cb);
+        }
 
-            ewhThreadHandle = new Thread(this.WaitForEwh);
-            ewhThreadHandle.Start();
+        public void SubscribeToBookAnalytics(OnBookAnalytics cb) {
+            this.onBookAnalyticsCbs.Add(cb);
         }
 
         public void Listen(
@@ -682,31 +1235,161 @@ namespace OpenLimits
 
         }
 
-        unsafe public void SubscribeToOrderbook(string market, OnOrderbook onOrderbook) {
-            if (!this.onOrderbookCbs.ContainsKey(market)) {
-                this.onOrderbookCbs.Add(market, new List<OnOrderbook>());
-            }
-            this.onOrderbookCbs.TryGetValue(market, out var callbacks);
-            callbacks.Add(onOrderbook);
-            handleFFIResult(SubscribeToOrderbook(this._client_handle, this._sub_handle, market));
+        public ulong SubscribeToOrderbook(string market, OnOrderbook onOrderbook) {
+            return SubscribeToOrderbook(market, onOrderbook, DeliveryPolicy.All);
+        }
+        unsafe public ulong SubscribeToOrderbook(string market, OnOrderbook onOrderbook, DeliveryPolicy delivery) {
+            var subscriber = GCHandle.Alloc(new OrderbookSubscriber(market, onOrderbook));
+            this.subscriberHandles.Add(subscriber);
+            handleFFIResult(SubscribeToOrderbook(this._client_handle, this._sub_handle, market, IntPtr.Zero, GCHandle.ToIntPtr(subscriber), delivery, out var subscriptionId));
             this.SetupEWH();
+            return subscriptionId;
         }
-        unsafe public void SubscribeToTrades(string market, OnTrades onTrades) {
-            if (!this.onTradesCbs.ContainsKey(market)) {
-                this.onTradesCbs.Add(market, new List<OnTrades>());
-            }
-            this.onTradesCbs.TryGetValue(market, out var callbacks);
-            callbacks.Add(onTrades);
-            handleFFIResult(SubscribeToTrades(this._client_handle, this._sub_handle, market));
+        public ulong SubscribeToTrades(string market, OnTrades onTrades) {
+            return SubscribeToTrades(market, onTrades, DeliveryPolicy.All);
+        }
+        unsafe public ulong SubscribeToTrades(string market, OnTrades onTrades, DeliveryPolicy delivery) {
+            var subscriber = GCHandle.Alloc(new TradesSubscriber(market, onTrades));
+            this.subscriberHandles.Add(subscriber);
+            handleFFIResult(SubscribeToTrades(this._client_handle, this._sub_handle, market, IntPtr.Zero, GCHandle.ToIntPtr(subscriber), delivery, out var subscriptionId));
+            this.SetupEWH();
+            return subscriptionId;
+        }
+
+        // Candles and tickers are built from the trade stream
+        public ulong SubscribeToCandles(string market, Interval interval, OnCandle onCandle) {
+            return SubscribeToCandles(market, interval, onCandle, DeliveryPolicy.All);
+        }
+        unsafe public ulong SubscribeToCandles(string market, Interval interval, OnCandle onCandle, DeliveryPolicy delivery) {
+            var subscriber = GCHandle.Alloc(new CandleSubscriber(market, interval, onCandle));
+            this.subscriberHandles.Add(subscriber);
+            handleFFIResult(SubscribeToCandles(this._client_handle, this._sub_handle, market, interval, IntPtr.Zero, GCHandle.ToIntPtr(subscriber), delivery, out var subscriptionId));
+            this.SetupEWH();
+            return subscriptionId;
+        }
+        public ulong SubscribeToTicker(string market, OnTicker onTicker) {
+            return SubscribeToTicker(market, onTicker, DeliveryPolicy.All);
+        }
+        unsafe public ulong SubscribeToTicker(string market, OnTicker onTicker, DeliveryPolicy delivery) {
+            var subscriber = GCHandle.Alloc(new TickerSubscriber(market, onTicker));
+            this.subscriberHandles.Add(subscriber);
+            handleFFIResult(SubscribeToTicker(this._client_handle, this._sub_handle, market, IntPtr.Zero, GCHandle.ToIntPtr(subscriber), delivery, out var subscriptionId));
+            this.SetupEWH();
+            return subscriptionId;
+        }
+
+        // Account streams poll the exchange over REST, a null market covers every market
+        public const ulong DefaultAccountPollIntervalMs = 1000;
+
+        public ulong SubscribeToOrderUpdates(string market, OnOrderUpdate onOrderUpdate) {
+            return SubscribeToOrderUpdates(market, onOrderUpdate, DefaultAccountPollIntervalMs, DeliveryPolicy.All);
+        }
+        unsafe public ulong SubscribeToOrderUpdates(string market, OnOrderUpdate onOrderUpdate, ulong pollIntervalMs, DeliveryPolicy delivery) {
+            var subscriber = GCHandle.Alloc(new OrderUpdateSubscriber(market, onOrderUpdate));
+            this.subscriberHandles.Add(subscriber);
+            handleFFIResult(SubscribeToOrderUpdates(this._client_handle, this._sub_handle, market, pollIntervalMs, IntPtr.Zero, GCHandle.ToIntPtr(subscriber), delivery, out var subscriptionId));
             this.SetupEWH();
+            return subscriptionId;
+        }
+        public ulong SubscribeToFills(string market, OnFill onFill) {
+            return SubscribeToFills(market, onFill, DefaultAccountPollIntervalMs, DeliveryPolicy.All);
+        }
+        unsafe public ulong SubscribeToFills(string market, OnFill onFill, ulong pollIntervalMs, DeliveryPolicy delivery) {
+            var subscriber = GCHandle.Alloc(new FillSubscriber(market, onFill));
+            this.subscriberHandles.Add(subscriber);
+            handleFFIResult(SubscribeToFills(this._client_handle, this._sub_handle, market, pollIntervalMs, IntPtr.Zero, GCHandle.ToIntPtr(subscriber), delivery, out var subscriptionId));
+            this.SetupEWH();
+            return subscriptionId;
+        }
+        public ulong SubscribeToBalances(OnBalanceUpdate onBalanceUpdate) {
+            return SubscribeToBalances(onBalanceUpdate, DefaultAccountPollIntervalMs, DeliveryPolicy.All);
+        }
+        unsafe public ulong SubscribeToBalances(OnBalanceUpdate onBalanceUpdate, ulong pollIntervalMs, DeliveryPolicy delivery) {
+            var subscriber = GCHandle.Alloc(new BalanceUpdateSubscriber(onBalanceUpdate));
+            this.subscriberHandles.Add(subscriber);
+            handleFFIResult(SubscribeToBalances(this._client_handle, this._sub_handle, pollIntervalMs, IntPtr.Zero, GCHandle.ToIntPtr(subscriber), delivery, out var subscriptionId));
+            this.SetupEWH();
+            return subscriptionId;
+        }
+
+        unsafe public LatencyStats GetLatencyStats(ulong subscriptionId) {
+            handleResult(GetLatencyStats(this._sub_handle, subscriptionId, out var stats));
+            return stats;
+        }
+
+        // Writes every order book and trade message of this client's subscriptions to rolling compressed
+        // files in dir. Read them back with JournalReader.
+        public void StartJournal(string dir) {
+            StartJournal(dir, JournalConfig.Default);
+        }
+        unsafe public void StartJournal(string dir, JournalConfig config) {
+            handleResult(StartJournal(this._sub_handle, dir, config));
+        }
+        unsafe public void StopJournal() {
+            handleResult(StopJournal(this._sub_handle));
+        }
+
+        unsafe public void SetStalePolicy(ulong subscriptionId, StalePolicy policy) {
+            handleResult(SetStalePolicy(this._sub_handle, subscriptionId, policy));
+        }
+
+        // Keeps rolling trade statistics of a subscription in rust. With a non zero pushIntervalMs they are
+        // also delivered to SubscribeToTradeStats callbacks on that interval.
+        public void EnableTradeStats(ulong subscriptionId) {
+            EnableTradeStats(subscriptionId, TradeStats.DefaultWindowsMs, 0);
+        }
+        unsafe public void EnableTradeStats(ulong subscriptionId, ulong[] windowsMs, ulong pushIntervalMs) {
+            handleResult(EnableTradeStats(this._sub_handle, subscriptionId, windowsMs, (ulong)windowsMs.Length, pushIntervalMs));
+        }
+        unsafe public void DisableTradeStats(ulong subscriptionId) {
+            handleResult(EnableTradeStats(this._sub_handle, subscriptionId, new ulong[0], 0, 0));
+        }
+
+        // One entry per window, in the order the windows were enabled
+        unsafe public TradeStats[] GetTradeStats(ulong subscriptionId) {
+            var stats = new TradeStats[64];
+            ulong actualLen;
+            fixed (TradeStats* buff = stats) {
+                handleResult(GetTradeStats(this._sub_handle, subscriptionId, (IntPtr)buff, (ulong)stats.Length, out actualLen));
+            }
+            Array.Resize(ref stats, (int)actualLen);
+            return stats;
+        }
+
+        // Computes spread, imbalance, microprice, pressure and the depth within each of distancesBps of mid
+        // on a local copy of an order book subscription. Pushed analytics go to SubscribeToBookAnalytics callbacks.
+        unsafe public void EnableBookAnalytics(ulong subscriptionId, BookAnalyticsConfig config, double[] distancesBps) {
+            handleResult(EnableBookAnalytics(this._sub_handle, subscriptionId, config, distancesBps, (ulong)distancesBps.Length));
+        }
+        unsafe public void DisableBookAnalytics(ulong subscriptionId) {
+            handleResult(DisableBookAnalytics(this._sub_handle, subscriptionId));
+        }
+
+        public BookAnalytics GetBookAnalytics(ulong subscriptionId) {
+            return GetBookAnalytics(subscriptionId, out _);
+        }
+        // depth holds one entry per configured distance, and is empty while either side of the book is
+        unsafe public BookAnalytics GetBookAnalytics(ulong subscriptionId, out DepthAtDistance[] depth) {
+            depth = new DepthAtDistance[64];
+            BookAnalytics analytics;
+            ulong actualLen;
+            fixed (DepthAtDistance* buff = depth) {
+                handleResult(GetBookAnalytics(this._sub_handle, subscriptionId, out analytics, (IntPtr)buff, (ulong)depth.Length, out actualLen));
+            }
+            Array.Resize(ref depth, (int)actualLen);
+            return analytics;
         }
 
         public void SubscribeToDisconnect(OnDisconnect cb) {
             this.onDisconnectCbs.Add(cb);
         }
 
+        public void SubscribeToConnectionState(OnConnectionState cb) {
+            this.onConnectionStateCbs.Add(cb);
+        }
+
         unsafe public void Disconnect() {
-            Disconnect(_sub_handle);
+            handleResult(Disconnect(_sub_handle));
         }
     }
 }
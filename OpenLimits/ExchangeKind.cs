@@ -0,0 +1,9 @@
+namespace OpenLimits
+{
+    public enum ExchangeKind
+    {
+        Binance,
+        Coinbase,
+        Nash
+    }
+}
\ No newline at end of file
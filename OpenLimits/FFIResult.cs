@@ -44,7 +44,9 @@ namespace OpenLimits
 
         InitializeException,
         SubscribeException,
-        NoMarketPair
+        NoMarketPair,
+        RiskRejected,
+        RateLimited
     }
 
     public class OpenLimitsError: Exception {
@@ -140,4 +142,12 @@ namespace OpenLimits
     public class NoMarketPair : OpenLimitsError {
         public NoMarketPair(string message): base(message) { }
     };
+    // The message starts with the name of the breached limit
+    public class RiskRejected : OpenLimitsError {
+        public RiskRejected(string message): base(message) { }
+    };
+    // The call would have waited longer for the client's rate limit budget than RateLimits.maxWaitMs
+    public class RateLimited : OpenLimitsError {
+        public RateLimited(string message): base(message) { }
+    };
 }
\ No newline at end of file
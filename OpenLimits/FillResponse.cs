@@ -0,0 +1,21 @@
+namespace OpenLimits
+{
+    using System.Collections.Generic;
+
+    // Own trades made since the subscription started
+    public class FillResponse
+    {
+        readonly public string market;
+        readonly public IEnumerable<Trade> fills;
+        readonly public DeliveryStats stats;
+        readonly public EventTimestamps timestamps;
+
+        public FillResponse(string market, IEnumerable<Trade> fills, DeliveryStats stats, EventTimestamps timestamps)
+        {
+            this.market = market;
+            this.fills = fills;
+            this.stats = stats;
+            this.timestamps = timestamps;
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,25 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    // Segments are closed, compressed and indexed once they hold maxSegmentBytes uncompressed bytes
+    // or are maxSegmentMs old, 0 disables either limit. With unsupported, messages the generic stream does
+    // not support are kept too, as their debug formatting rather than the payload sent by the exchange.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct JournalConfig
+    {
+        public readonly ulong maxSegmentBytes;
+        public readonly ulong maxSegmentMs;
+        [MarshalAs(UnmanagedType.U1)]
+        public readonly bool unsupported;
+
+        public JournalConfig(ulong maxSegmentBytes, ulong maxSegmentMs, bool unsupported)
+        {
+            this.maxSegmentBytes = maxSegmentBytes;
+            this.maxSegmentMs = maxSegmentMs;
+            this.unsupported = unsupported;
+        }
+
+        public static JournalConfig Default = new JournalConfig(64 * 1024 * 1024, 60 * 60 * 1000, false);
+    }
+}
\ No newline at end of file
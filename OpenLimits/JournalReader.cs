@@ -0,0 +1,96 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Collections;
+    using System.Collections.Generic;
+    using System.Runtime.InteropServices;
+
+    // Iterates the journaled records of one market received within a time window, oldest first
+    public class JournalReader : IEnumerable<JournalRecord>, IDisposable
+    {
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "open_journal_reader", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult OpenJournalReader(string dir, string market, ulong fromMs, ulong toMs, out IntPtr reader);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "read_journal", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult ReadJournal(IntPtr reader, IntPtr buffPtr, UIntPtr buffLen, out UIntPtr actualLen);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "free_journal_reader", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern void FreeJournalReader(IntPtr reader);
+
+        private IntPtr _reader_handle;
+        private FFIEvent[] eventsBuff = new FFIEvent[256];
+
+        // fromMs and toMs are unix ms, both inclusive
+        public JournalReader(string dir, string market, ulong fromMs, ulong toMs) {
+            ExchangeClient.handleResult(OpenJournalReader(dir, market, fromMs, toMs, out _reader_handle));
+        }
+
+        // Reads the next batch of records, an empty batch means the reader is done
+        unsafe public List<JournalRecord> Read() {
+            var records = new List<JournalRecord>();
+            fixed (FFIEvent* buff = eventsBuff.AsSpan()) {
+                ExchangeClient.handleResult(ReadJournal(_reader_handle, (IntPtr)buff, (UIntPtr)eventsBuff.Length, out var actualLen));
+                try {
+                    for (int i = 0 ; i < (int)actualLen ; i ++) {
+                        records.Add(ToRecord(eventsBuff[i]));
+                    }
+                } finally {
+                    ExchangeClient.FreeEvents((IntPtr)buff, actualLen);
+                }
+            }
+            return records;
+        }
+
+        private static JournalRecord ToRecord(FFIEvent ffiEvent) {
+            var market = CString.ToString(ffiEvent.subscription.market);
+            OrderbookResponse orderbook = null;
+            IEnumerable<Trade> trades = null;
+            string unsupported = null;
+            switch(ffiEvent.kind) {
+                case WebSocketEventKind.OrderbookSnapshot:
+                case WebSocketEventKind.OrderbookDiff:
+                    orderbook = new OrderbookResponse(
+                        market,
+                        ffiEvent.ToAsks(),
+                        ffiEvent.ToBids(),
+                        ffiEvent.lastUpdateId,
+                        ffiEvent.updateId,
+                        ffiEvent.kind == WebSocketEventKind.OrderbookSnapshot,
+                        default(DeliveryStats),
+                        ffiEvent.ToTimestamps()
+                    );
+                    break;
+                case WebSocketEventKind.Trades:
+                    trades = ffiEvent.ToTrades();
+                    break;
+                default:
+                    unsupported = CString.ToString(ffiEvent.errorMessage);
+                    break;
+            }
+            return new JournalRecord(ffiEvent.kind, market, ffiEvent.receivedAt, orderbook, trades, unsupported);
+        }
+
+        public IEnumerator<JournalRecord> GetEnumerator() {
+            while (true) {
+                var records = Read();
+                foreach (var record in records) {
+                    yield return record;
+                }
+                if (records.Count < eventsBuff.Length) {
+                    yield break;
+                }
+            }
+        }
+
+        IEnumerator IEnumerable.GetEnumerator() {
+            return GetEnumerator();
+        }
+
+        public void Dispose() {
+            if (_reader_handle != IntPtr.Zero) {
+                FreeJournalReader(_reader_handle);
+                _reader_handle = IntPtr.Zero;
+            }
+        }
+    }
+}
\ No newline at end of file
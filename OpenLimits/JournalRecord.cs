@@ -0,0 +1,27 @@
+namespace OpenLimits
+{
+    using System.Collections.Generic;
+
+    // A journaled order book update, trade batch or unsupported exchange message.
+    // Only the field matching the kind is set, unsupported holds the debug formatted message.
+    public class JournalRecord
+    {
+        readonly public WebSocketEventKind kind;
+        readonly public string market;
+        // Unix ms the message was received at
+        readonly public ulong receivedAt;
+        readonly public OrderbookResponse orderbook;
+        readonly public IEnumerable<Trade> trades;
+        readonly public string unsupported;
+
+        public JournalRecord(WebSocketEventKind kind, string market, ulong receivedAt, OrderbookResponse orderbook, IEnumerable<Trade> trades, string unsupported)
+        {
+            this.kind = kind;
+            this.market = market;
+            this.receivedAt = receivedAt;
+            this.orderbook = orderbook;
+            this.trades = trades;
+            this.unsupported = unsupported;
+        }
+    }
+}
\ No newline at end of file
@@ -8,8 +8,16 @@ namespace OpenLimits
         public readonly TimeInForce timeInForce;
         public readonly ulong timeInForceDurationMs;
         public readonly bool postOnly;
+        // Generated when null. The id is never sent to the exchange, only this client knows it: placing again with
+        // the id of an order this client placed returns that order instead of sending a new one.
+        public readonly string clientOrderId;
 
         public LimitOrderRequest(string price, string size, string market, TimeInForce timeInForce, ulong timeInForceDurationMs, bool postOnly)
+            : this(price, size, market, timeInForce, timeInForceDurationMs, postOnly, null)
+        {
+        }
+
+        public LimitOrderRequest(string price, string size, string market, TimeInForce timeInForce, ulong timeInForceDurationMs, bool postOnly, string clientOrderId)
         {
             this.price = price;
             this.size = size;
@@ -17,6 +25,11 @@ namespace OpenLimits
             this.timeInForce = timeInForce;
             this.timeInForceDurationMs = timeInForceDurationMs;
             this.postOnly = postOnly;
+            this.clientOrderId = clientOrderId;
+        }
+
+        public LimitOrderRequest WithClientOrderId(string clientOrderId) {
+            return new LimitOrderRequest(price, size, market, timeInForce, timeInForceDurationMs, postOnly, clientOrderId);
         }
 
         public static LimitOrderRequest immediateOrCancel(string price, string size, string market) {
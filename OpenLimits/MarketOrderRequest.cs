@@ -4,11 +4,19 @@ namespace OpenLimits
     {
         public readonly string size;
         public readonly string market;
+        // Generated when null. The id is never sent to the exchange, only this client knows it: placing again with
+        // the id of an order this client placed returns that order instead of sending a new one.
+        public readonly string clientOrderId;
 
-        public MarketOrderRequest(string size, string market)
+        public MarketOrderRequest(string size, string market) : this(size, market, null)
+        {
+        }
+
+        public MarketOrderRequest(string size, string market, string clientOrderId)
         {
             this.size = size;
             this.market = market;
+            this.clientOrderId = clientOrderId;
         }
     }
 }
\ No newline at end of file
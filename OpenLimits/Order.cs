@@ -2,6 +2,7 @@ namespace OpenLimits
 {
     using System;
     using System.Globalization;
+    using System.Runtime.InteropServices;
 
     internal struct FFIOrder
     {
@@ -15,10 +16,13 @@ namespace OpenLimits
         public readonly IntPtr size;
         public readonly IntPtr price;
         public readonly IntPtr remaining;
+        [MarshalAs(UnmanagedType.U1)]
+        public readonly bool simulated;
 
         public void Dispose() {
             ExchangeClient.FreeString(id);
             ExchangeClient.FreeString(marketPair);
+            ExchangeClient.FreeString(clientOrderId);
             ExchangeClient.FreeString(size);
             ExchangeClient.FreeString(price);
             ExchangeClient.FreeString(remaining);
@@ -35,7 +39,8 @@ namespace OpenLimits
                 this.status,
                 CString.ToString(this.size),
                 CString.ToString(this.price),
-                CString.ToString(this.remaining)
+                CString.ToString(this.remaining),
+                this.simulated
             );
         }
     }
@@ -52,8 +57,10 @@ namespace OpenLimits
         public readonly decimal size;
         public readonly decimal? price;
         public readonly decimal? remaining;
+        // Returned by a dry run, the exchange never saw the order
+        public readonly bool simulated;
 
-        public Order(string id, string marketPair, string clientOrderId, ulong createdAt, OrderType orderType, Side side, OrderStatus status, string size, string price, string remaining)
+        public Order(string id, string marketPair, string clientOrderId, ulong createdAt, OrderType orderType, Side side, OrderStatus status, string size, string price, string remaining, bool simulated = false)
         {
             this.id = id;
             this.marketPair = marketPair;
@@ -62,6 +69,7 @@ namespace OpenLimits
             this.orderType = orderType;
             this.side = side;
             this.status = status;
+            this.simulated = simulated;
             
             this.size = decimal.Parse(size, System.Globalization.NumberStyles.AllowDecimalPoint, CultureInfo.InvariantCulture);
             this.price = price == null ? default(decimal?) : decimal.Parse(price, System.Globalization.NumberStyles.AllowDecimalPoint, CultureInfo.InvariantCulture);
@@ -91,6 +99,7 @@ namespace OpenLimits
                 ", size='" + size + '\'' +
                 ", price='" + price + '\'' +
                 ", remaining='" + remaining + '\'' +
+                ", simulated=" + simulated +
                 '}';
         }
     }
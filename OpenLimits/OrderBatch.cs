@@ -0,0 +1,115 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Runtime.InteropServices;
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFIOrderRequest
+    {
+        public string market;
+        public string qty;
+        [MarshalAs(UnmanagedType.U1)]
+        public bool limit;
+        public string price;
+        public Side side;
+        public TimeInForce tif;
+        public ulong tifDuration;
+        [MarshalAs(UnmanagedType.U1)]
+        public bool postOnly;
+        public string clientOrderId;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFICancelRequest
+    {
+        public string orderId;
+        public string market;
+    }
+
+    // One order of a PlaceOrders batch
+    public struct OrderRequest
+    {
+        public readonly Side side;
+        public readonly bool limit;
+        public readonly string market;
+        public readonly string size;
+        // Null for market orders
+        public readonly string price;
+        public readonly TimeInForce timeInForce;
+        public readonly ulong timeInForceDurationMs;
+        public readonly bool postOnly;
+        public readonly string clientOrderId;
+
+        public OrderRequest(Side side, LimitOrderRequest request)
+        {
+            this.side = side;
+            this.limit = true;
+            this.market = request.market;
+            this.size = request.size;
+            this.price = request.price;
+            this.timeInForce = request.timeInForce;
+            this.timeInForceDurationMs = request.timeInForceDurationMs;
+            this.postOnly = request.postOnly;
+            this.clientOrderId = request.clientOrderId;
+        }
+
+        public OrderRequest(Side side, MarketOrderRequest request)
+        {
+            this.side = side;
+            this.limit = false;
+            this.market = request.market;
+            this.size = request.size;
+            this.price = null;
+            this.timeInForce = TimeInForce.GTC;
+            this.timeInForceDurationMs = 0;
+            this.postOnly = false;
+            this.clientOrderId = request.clientOrderId;
+        }
+
+        internal FFIOrderRequest ToFFI() {
+            return new FFIOrderRequest {
+                market = this.market,
+                qty = this.size,
+                limit = this.limit,
+                price = this.price,
+                side = this.side,
+                tif = this.timeInForce,
+                tifDuration = this.timeInForceDurationMs,
+                postOnly = this.postOnly,
+                clientOrderId = this.clientOrderId
+            };
+        }
+    }
+
+    // Outcome of one order of a batch. Exactly one of order and error is set.
+    public struct BatchOrderResult
+    {
+        public readonly OrderRequest request;
+        public readonly Order? order;
+        public readonly Exception error;
+
+        public BatchOrderResult(OrderRequest request, Order? order, Exception error)
+        {
+            this.request = request;
+            this.order = order;
+            this.error = error;
+        }
+
+        public bool Succeeded => error == null;
+    }
+
+    // Outcome of one cancellation of a batch, error is null if the order was canceled
+    public struct BatchCancelResult
+    {
+        public readonly string orderId;
+        public readonly Exception error;
+
+        public BatchCancelResult(string orderId, Exception error)
+        {
+            this.orderId = orderId;
+            this.error = error;
+        }
+
+        public bool Succeeded => error == null;
+    }
+}
\ No newline at end of file
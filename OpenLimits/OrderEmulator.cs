@@ -0,0 +1,103 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Runtime.InteropServices;
+
+    // Emulates trailing stop, OCO, iceberg and bracket orders locally on top of plain limit and market orders.
+    // Local stops only fire on trades of the markets passed to Watch. Updates arrive on background threads.
+    public class OrderEmulator : IDisposable
+    {
+        unsafe private delegate void OnNativeUpdate(FFIEmulatedOrder* order, IntPtr userData);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "create_order_emulator", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult CreateOrderEmulator(IntPtr client, ulong pollIntervalMs, OnNativeUpdate onUpdate, IntPtr userData, out IntPtr emulator);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "free_order_emulator", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern void FreeOrderEmulator(IntPtr emulator);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "order_emulator_watch", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult Watch(IntPtr emulator, IntPtr client, IntPtr subhandle, string market, out ulong subscriptionId);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "emulate_trailing_stop", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult EmulateTrailingStop(IntPtr emulator, string market, Side side, string qty, string trail, out ulong id);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "emulate_oco", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult EmulateOco(IntPtr emulator, string market, Side side, string qty, string limitPrice, string stopPrice, out ulong id);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "emulate_iceberg", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult EmulateIceberg(IntPtr emulator, string market, Side side, string qty, string clipQty, string price, out ulong id);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "emulate_bracket", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult EmulateBracket(IntPtr emulator, string market, Side side, string qty, string entryPrice, string takeProfitPrice, string stopPrice, out ulong id);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "cancel_emulated_order", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult CancelEmulatedOrder(IntPtr emulator, ulong id);
+
+        public delegate void OnEmulatedOrder(EmulatedOrder order);
+
+        private IntPtr _emulator_handle;
+        private readonly ExchangeClient client;
+        private readonly OnEmulatedOrder onUpdate;
+        // Kept alive for as long as rust may call it
+        private OnNativeUpdate onNativeUpdateCb;
+
+        public OrderEmulator(ExchangeClient client, OnEmulatedOrder onUpdate): this(client, 1000, onUpdate)
+        {
+        }
+
+        // Child orders on the exchange are polled every pollIntervalMs
+        unsafe public OrderEmulator(ExchangeClient client, ulong pollIntervalMs, OnEmulatedOrder onUpdate) {
+            this.client = client;
+            this.onUpdate = onUpdate;
+            this.onNativeUpdateCb = this.onNativeUpdate;
+            ExchangeClient.handleResult(CreateOrderEmulator(client.ClientHandle, pollIntervalMs, this.onNativeUpdateCb, IntPtr.Zero, out _emulator_handle));
+        }
+
+        unsafe private void onNativeUpdate(FFIEmulatedOrder* order, IntPtr userData) {
+            this.onUpdate(order->ToEmulatedOrder());
+        }
+
+        // Feeds the trades of `market` to the local stops. Returns the id of the trade subscription.
+        public ulong Watch(string market) {
+            ExchangeClient.handleResult(Watch(_emulator_handle, client.ClientHandle, client.SubHandle, market, out var subscriptionId));
+            return subscriptionId;
+        }
+
+        // Sells at market once the price fell `trail` below its high since placing it, or buys once it rose `trail` above its low
+        public ulong TrailingStop(string market, Side side, string size, string trail) {
+            ExchangeClient.handleResult(EmulateTrailingStop(_emulator_handle, market, side, size, trail, out var id));
+            return id;
+        }
+
+        // Limit order at limitPrice, replaced by a market order if the price reaches stopPrice first
+        public ulong Oco(string market, Side side, string size, string limitPrice, string stopPrice) {
+            ExchangeClient.handleResult(EmulateOco(_emulator_handle, market, side, size, limitPrice, stopPrice, out var id));
+            return id;
+        }
+
+        // Works `size` at `price` in limit orders of at most clipSize, placing the next one once the last filled
+        public ulong Iceberg(string market, Side side, string size, string clipSize, string price) {
+            ExchangeClient.handleResult(EmulateIceberg(_emulator_handle, market, side, size, clipSize, price, out var id));
+            return id;
+        }
+
+        // Limit entry at entryPrice that, once filled, is closed out by an OCO of takeProfitPrice and stopPrice
+        public ulong Bracket(string market, Side side, string size, string entryPrice, string takeProfitPrice, string stopPrice) {
+            ExchangeClient.handleResult(EmulateBracket(_emulator_handle, market, side, size, entryPrice, takeProfitPrice, stopPrice, out var id));
+            return id;
+        }
+
+        // Disarms the emulated order and cancels its working exchange order
+        public void Cancel(ulong id) {
+            ExchangeClient.handleResult(CancelEmulatedOrder(_emulator_handle, id));
+        }
+
+        // Stops emulating, working exchange orders are left as they are
+        public void Dispose() {
+            if (_emulator_handle != IntPtr.Zero) {
+                FreeOrderEmulator(_emulator_handle);
+                _emulator_handle = IntPtr.Zero;
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+namespace OpenLimits
+{
+    using System.Collections.Generic;
+
+    // Own orders that were created or changed status or remaining size since the previous poll
+    public class OrderUpdateResponse
+    {
+        readonly public string market;
+        readonly public IEnumerable<Order> orders;
+        readonly public DeliveryStats stats;
+        readonly public EventTimestamps timestamps;
+
+        public OrderUpdateResponse(string market, IEnumerable<Order> orders, DeliveryStats stats, EventTimestamps timestamps)
+        {
+            this.market = market;
+            this.orders = orders;
+            this.stats = stats;
+            this.timestamps = timestamps;
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,35 @@
+namespace OpenLimits
+{
+    // How orders are checked against the increments and minimums of their market before they are sent
+    public enum ValidationMode
+    {
+        // Orders are sent as they are
+        Off,
+        // Orders off the increments or below the minimums throw ArgumentException
+        Reject,
+        // Size is rounded down, buy prices down and sell prices up to the increments.
+        // Orders still below the minimums afterwards throw ArgumentException.
+        Round
+    }
+
+    public struct NormalizedOrder
+    {
+        public readonly string size;
+        // Null for market orders
+        public readonly string price;
+
+        public NormalizedOrder(string size, string price)
+        {
+            this.size = size;
+            this.price = price;
+        }
+
+        public override string ToString()
+        {
+            return "NormalizedOrder{" +
+                "size=" + size +
+                ", price=" + price +
+                "}";
+        }
+    }
+}
\ No newline at end of file
@@ -11,13 +11,30 @@ namespace OpenLimits
         readonly public ulong lastUpdateId;
         readonly public ulong updateId;
 
-        public OrderbookResponse(string market, IEnumerable<AskBid> asks, IEnumerable<AskBid> bids, ulong lastUpdateId, ulong updateId)
+        // False when the update only contains the levels that changed since the previous one
+        readonly public bool isSnapshot;
+
+        readonly public DeliveryStats stats;
+        readonly public EventTimestamps timestamps;
+
+        public OrderbookResponse(string market, IEnumerable<AskBid> asks, IEnumerable<AskBid> bids, ulong lastUpdateId, ulong updateId): this(market, asks, bids, lastUpdateId, updateId, true)
+        {
+        }
+
+        public OrderbookResponse(string market, IEnumerable<AskBid> asks, IEnumerable<AskBid> bids, ulong lastUpdateId, ulong updateId, bool isSnapshot): this(market, asks, bids, lastUpdateId, updateId, isSnapshot, default(DeliveryStats), default(EventTimestamps))
+        {
+        }
+
+        public OrderbookResponse(string market, IEnumerable<AskBid> asks, IEnumerable<AskBid> bids, ulong lastUpdateId, ulong updateId, bool isSnapshot, DeliveryStats stats, EventTimestamps timestamps)
         {
             this.market = market;
             this.asks = asks;
             this.bids = bids;
             this.lastUpdateId = lastUpdateId;
             this.updateId = updateId;
+            this.isSnapshot = isSnapshot;
+            this.stats = stats;
+            this.timestamps = timestamps;
         }
     }
 }
\ No newline at end of file
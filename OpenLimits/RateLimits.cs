@@ -0,0 +1,84 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    // REST calls of the client, each taking a weight of the exchange's request budget
+    public enum Endpoint
+    {
+        OrderBook,
+        PriceTicker,
+        HistoricRates,
+        HistoricTrades,
+        PlaceOrder,
+        CancelOrder,
+        CancelAllOrders,
+        GetOrder,
+        OrderHistory,
+        OpenOrders,
+        TradeHistory,
+        AccountBalances,
+        MarketPairs
+    }
+
+    // Clients start with the published limits of their exchange, read them with GetRateLimits.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct RateLimits
+    {
+        // Request weight allowed per window, 0 turns the request budget off
+        public readonly uint requestWeight;
+        public readonly ulong requestWindowMs;
+        // Orders allowed per window on top of their request weight, 0 turns the order budget off
+        public readonly uint orderCount;
+        public readonly ulong orderWindowMs;
+        // Longest a call waits for budget before throwing RateLimited, 0 fails right away
+        public readonly ulong maxWaitMs;
+
+        public RateLimits(uint requestWeight, ulong requestWindowMs, uint orderCount, ulong orderWindowMs, ulong maxWaitMs)
+        {
+            this.requestWeight = requestWeight;
+            this.requestWindowMs = requestWindowMs;
+            this.orderCount = orderCount;
+            this.orderWindowMs = orderWindowMs;
+            this.maxWaitMs = maxWaitMs;
+        }
+
+        public RateLimits WithMaxWaitMs(ulong maxWaitMs)
+        {
+            return new RateLimits(requestWeight, requestWindowMs, orderCount, orderWindowMs, maxWaitMs);
+        }
+
+        public override string ToString()
+        {
+            return "RateLimits{" +
+                "requestWeight=" + requestWeight +
+                ", requestWindowMs=" + requestWindowMs +
+                ", orderCount=" + orderCount +
+                ", orderWindowMs=" + orderWindowMs +
+                ", maxWaitMs=" + maxWaitMs +
+                '}';
+        }
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct RateLimitUsage
+    {
+        // Goes over requestWeight while calls are waiting for budget
+        public readonly double requestWeightUsed;
+        public readonly uint requestWeight;
+        public readonly double ordersUsed;
+        public readonly uint orderCount;
+        // Calls that threw RateLimited since the client was created
+        public readonly ulong rejected;
+
+        public override string ToString()
+        {
+            return "RateLimitUsage{" +
+                "requestWeightUsed=" + requestWeightUsed +
+                ", requestWeight=" + requestWeight +
+                ", ordersUsed=" + ordersUsed +
+                ", orderCount=" + orderCount +
+                ", rejected=" + rejected +
+                '}';
+        }
+    }
+}
\ No newline at end of file
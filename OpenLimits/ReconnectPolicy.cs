@@ -0,0 +1,23 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    // Controls how the subscription thread reconnects after the websocket drops.
+    // A maxAttempts of 0 keeps retrying forever.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct ReconnectPolicy
+    {
+        public readonly uint maxAttempts;
+        public readonly ulong initialBackoffMs;
+        public readonly ulong maxBackoffMs;
+
+        public ReconnectPolicy(uint maxAttempts, ulong initialBackoffMs, ulong maxBackoffMs)
+        {
+            this.maxAttempts = maxAttempts;
+            this.initialBackoffMs = initialBackoffMs;
+            this.maxBackoffMs = maxBackoffMs;
+        }
+
+        public static ReconnectPolicy Default = new ReconnectPolicy(0, 500, 30000);
+    }
+}
\ No newline at end of file
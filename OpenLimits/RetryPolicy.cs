@@ -0,0 +1,68 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Collections.Generic;
+    using System.Runtime.InteropServices;
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFIRetryPolicy
+    {
+        public uint maxAttempts;
+        public ulong initialBackoffMs;
+        public ulong maxBackoffMs;
+        public double jitter;
+    }
+
+    // Read-only calls failing with one of the retryable exception types are retried. Placements are
+    // never retried, a failed placement may still have reached the exchange.
+    public class RetryPolicy
+    {
+        // Attempts per call including the first one, 1 turns retries off
+        public readonly uint maxAttempts;
+        // Doubled after every failed attempt up to maxBackoffMs
+        public readonly ulong initialBackoffMs;
+        public readonly ulong maxBackoffMs;
+        // Share of each backoff, 0 to 1, taken off at random
+        public readonly double jitter;
+        public readonly Type[] retryable;
+
+        public RetryPolicy(uint maxAttempts, ulong initialBackoffMs, ulong maxBackoffMs, double jitter, params Type[] retryable)
+        {
+            this.maxAttempts = maxAttempts;
+            this.initialBackoffMs = initialBackoffMs;
+            this.maxBackoffMs = maxBackoffMs;
+            this.jitter = jitter;
+            this.retryable = retryable;
+        }
+
+        public static RetryPolicy Default = new RetryPolicy(3, 200, 2000, 0.5, typeof(ReqError), typeof(ServiceUnavailable), typeof(InternalServerError));
+        public static RetryPolicy Disabled = new RetryPolicy(1, 0, 0, 0);
+
+        internal FFIRetryPolicy ToFFI()
+        {
+            return new FFIRetryPolicy {
+                maxAttempts = maxAttempts,
+                initialBackoffMs = initialBackoffMs,
+                maxBackoffMs = maxBackoffMs,
+                jitter = jitter
+            };
+        }
+
+        // Bit 1 << tag for the result tag of every retryable exception type
+        internal ulong RetryableTags()
+        {
+            var types = new HashSet<Type>(retryable);
+            ulong tags = 0;
+            foreach (ResultTag tag in Enum.GetValues(typeof(ResultTag))) {
+                var error = ExchangeClient.errorFromResult(tag, "");
+                if (error != null && types.Remove(error.GetType())) {
+                    tags |= 1UL << (int)tag;
+                }
+            }
+            foreach (var type in types) {
+                throw new ArgumentException(type.Name + " is not an error of the library");
+            }
+            return tags;
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,28 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    // Every limit is off at 0. Notional is in the quote asset, market orders are valued at the mid price.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct RiskLimits
+    {
+        public readonly double maxOrderNotional;
+        public readonly uint maxOpenOrdersPerMarket;
+        public readonly uint maxOrdersPerSecond;
+        // Limit prices further than this from mid are rejected
+        public readonly double priceBandBps;
+        // Open orders and balances are reloaded from the exchange once older than this
+        public readonly ulong syncIntervalMs;
+
+        public RiskLimits(double maxOrderNotional, uint maxOpenOrdersPerMarket, uint maxOrdersPerSecond, double priceBandBps, ulong syncIntervalMs)
+        {
+            this.maxOrderNotional = maxOrderNotional;
+            this.maxOpenOrdersPerMarket = maxOpenOrdersPerMarket;
+            this.maxOrdersPerSecond = maxOrdersPerSecond;
+            this.priceBandBps = priceBandBps;
+            this.syncIntervalMs = syncIntervalMs;
+        }
+
+        public static RiskLimits Disabled = new RiskLimits(0, 0, 0, 0, 0);
+    }
+}
\ No newline at end of file
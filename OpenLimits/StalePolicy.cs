@@ -0,0 +1,29 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    public enum StaleAction
+    {
+        Notify,
+        Resubscribe,
+        Reconnect
+    }
+
+    // A subscription goes stale when no market data arrived for thresholdMs. Pings do not count.
+    // Besides reporting it, the subscription can be resubscribed or the whole connection reconnected.
+    // Both are retried every thresholdMs while the feed stays silent.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct StalePolicy
+    {
+        public readonly ulong thresholdMs;
+        public readonly StaleAction action;
+
+        public StalePolicy(ulong thresholdMs, StaleAction action)
+        {
+            this.thresholdMs = thresholdMs;
+            this.action = action;
+        }
+
+        public static StalePolicy Disabled = new StalePolicy(0, StaleAction.Notify);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,45 @@
+namespace OpenLimits
+{
+    public enum TriggerDirection
+    {
+        // Triggers once the price is at or above the trigger price
+        RisesTo,
+        // Triggers once the price is at or below the trigger price
+        FallsTo
+    }
+
+    public struct StopOrderRequest
+    {
+        public readonly string market;
+        public readonly Side side;
+        public readonly string size;
+        public readonly string triggerPrice;
+        public readonly TriggerDirection direction;
+        // A stop market order when null
+        public readonly string limitPrice;
+        public readonly TimeInForce timeInForce;
+        public readonly ulong timeInForceDurationMs;
+        // Generated when null
+        public readonly string clientOrderId;
+
+        public StopOrderRequest(string market, Side side, string size, string triggerPrice, TriggerDirection direction, string limitPrice, TimeInForce timeInForce, ulong timeInForceDurationMs, string clientOrderId)
+        {
+            this.market = market;
+            this.side = side;
+            this.size = size;
+            this.triggerPrice = triggerPrice;
+            this.direction = direction;
+            this.limitPrice = limitPrice;
+            this.timeInForce = timeInForce;
+            this.timeInForceDurationMs = timeInForceDurationMs;
+            this.clientOrderId = clientOrderId;
+        }
+
+        public static StopOrderRequest stopMarket(Side side, string size, string market, string triggerPrice, TriggerDirection direction) {
+            return new StopOrderRequest(market, side, size, triggerPrice, direction, null, TimeInForce.GTC, 0, null);
+        }
+        public static StopOrderRequest stopLimit(Side side, string size, string market, string triggerPrice, TriggerDirection direction, string limitPrice) {
+            return new StopOrderRequest(market, side, size, triggerPrice, direction, limitPrice, TimeInForce.GTC, 0, null);
+        }
+    }
+}
\ No newline at end of file
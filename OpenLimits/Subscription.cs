@@ -0,0 +1,57 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Runtime.InteropServices;
+
+    public enum SubscriptionKind
+    {
+        Orderbook,
+        Trades,
+        Candles,
+        Ticker,
+        AccountOrders,
+        AccountTrades,
+        AccountBalances
+    }
+
+    // The market string is owned by the subscription and copied while the event is read
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFISubscription
+    {
+        public readonly ulong id;
+        public readonly SubscriptionKind kind;
+        public readonly IntPtr market;
+        public readonly IntPtr userData;
+
+        public Subscription ToSubscription() {
+            return new Subscription(
+                this.id,
+                this.kind,
+                CString.ToString(this.market)
+            );
+        }
+    }
+
+    public struct Subscription
+    {
+        public readonly ulong id;
+        public readonly SubscriptionKind kind;
+        public readonly string market;
+
+        public Subscription(ulong id, SubscriptionKind kind, string market)
+        {
+            this.id = id;
+            this.kind = kind;
+            this.market = market;
+        }
+
+        public override string ToString()
+        {
+            return "Subscription{" +
+                "id=" + id +
+                ", kind=" + kind +
+                ", market='" + market + '\'' +
+                '}';
+        }
+    }
+}
\ No newline at end of file
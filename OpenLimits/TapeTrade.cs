@@ -0,0 +1,71 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Runtime.InteropServices;
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFITapeTrade
+    {
+        public readonly uint venue;
+        public readonly ExchangeKind exchange;
+        public readonly IntPtr symbol;
+        [MarshalAs(UnmanagedType.U1)]
+        public readonly bool late;
+        public readonly FFITrade trade;
+
+        public void Dispose() {
+            ExchangeClient.FreeString(symbol);
+            trade.Dispose();
+        }
+
+        public TapeTrade ToTapeTrade() {
+            return new TapeTrade(
+                this.venue,
+                this.exchange,
+                CString.ToString(this.symbol),
+                this.trade.ToTrade(),
+                this.late
+            );
+        }
+    }
+
+    // A trade of a merged trade tape, tagged with the venue it happened on
+    public struct TapeTrade
+    {
+        public readonly uint venue;
+        public readonly ExchangeKind exchange;
+        public readonly string symbol;
+        public readonly Trade trade;
+        // Arrived after a later trade was already released, the reordering window was too short for it
+        public readonly bool late;
+
+        public TapeTrade(uint venue, ExchangeKind exchange, string symbol, Trade trade, bool late)
+        {
+            this.venue = venue;
+            this.exchange = exchange;
+            this.symbol = symbol;
+            this.trade = trade;
+            this.late = late;
+        }
+
+        public override string ToString()
+        {
+            return "TapeTrade { venue=" + venue + ", exchange=" + exchange + ", symbol=" + symbol + ", trade=" + trade + ", late=" + late + "}";
+        }
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct TapeStats
+    {
+        // Trades still inside the reordering window
+        public readonly ulong pending;
+        public readonly ulong late;
+        // Trades dropped because more than the tape capacity were pending
+        public readonly ulong dropped;
+
+        public override string ToString()
+        {
+            return "TapeStats { pending=" + pending + ", late=" + late + ", dropped=" + dropped + "}";
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    // Ticker built from the trade stream, with statistics over the last 24 hours
+    [StructLayout(LayoutKind.Sequential)]
+    public struct Ticker
+    {
+        public readonly double price;
+        public readonly double qty;
+        public readonly ulong time;
+        public readonly double open24h;
+        public readonly double high24h;
+        public readonly double low24h;
+        public readonly double volume24h;
+        public readonly double priceChangePercent24h;
+
+        public override string ToString()
+        {
+            return "Ticker{" +
+                "price=" + price +
+                ", qty=" + qty +
+                ", time=" + time +
+                ", open24h=" + open24h +
+                ", high24h=" + high24h +
+                ", low24h=" + low24h +
+                ", volume24h=" + volume24h +
+                ", priceChangePercent24h=" + priceChangePercent24h +
+                '}';
+        }
+    }
+}
\ No newline at end of file
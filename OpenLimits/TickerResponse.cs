@@ -0,0 +1,18 @@
+namespace OpenLimits
+{
+    public class TickerResponse
+    {
+        readonly public string market;
+        readonly public Ticker ticker;
+        readonly public DeliveryStats stats;
+        readonly public EventTimestamps timestamps;
+
+        public TickerResponse(string market, Ticker ticker, DeliveryStats stats, EventTimestamps timestamps)
+        {
+            this.market = market;
+            this.ticker = ticker;
+            this.stats = stats;
+            this.timestamps = timestamps;
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,35 @@
+namespace OpenLimits
+{
+    using System.Runtime.InteropServices;
+
+    // Rolling statistics over the trades a subscription received in the last windowMs.
+    // Windows follow local receive time, so they keep moving while the market is quiet.
+    [StructLayout(LayoutKind.Sequential)]
+    public struct TradeStats
+    {
+        public static readonly ulong[] DefaultWindowsMs = new ulong[] { 60 * 1000, 5 * 60 * 1000, 60 * 60 * 1000 };
+
+        public readonly ulong windowMs;
+        public readonly ulong count;
+        public readonly double volume;
+        // Volume of trades where the taker bought
+        public readonly double buyVolume;
+        public readonly double sellVolume;
+        public readonly double notional;
+        // 0 when the window holds no trades
+        public readonly double vwap;
+
+        public override string ToString()
+        {
+            return "TradeStats{" +
+                "windowMs=" + windowMs +
+                ", count=" + count +
+                ", volume=" + volume +
+                ", buyVolume=" + buyVolume +
+                ", sellVolume=" + sellVolume +
+                ", notional=" + notional +
+                ", vwap=" + vwap +
+                '}';
+        }
+    }
+}
\ No newline at end of file
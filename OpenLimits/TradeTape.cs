@@ -0,0 +1,95 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Collections.Generic;
+    using System.Runtime.InteropServices;
+    using System.Threading;
+
+    // Merges the trade streams of several exchange clients into one stream ordered by exchange time.
+    // Trades are held for windowMs after they arrive so trades from slower venues can be sorted in before them.
+    public class TradeTape : IDisposable
+    {
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "create_trade_tape", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult CreateTradeTape(ulong windowMs, UIntPtr capacity, out IntPtr tape);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "free_trade_tape", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern void FreeTradeTape(IntPtr tape);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "trade_tape_add_venue", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult AddVenue(IntPtr tape, IntPtr client, IntPtr subhandle, string market, string symbol, out uint venue, out ulong subscriptionId);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "trade_tape_wait", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult Wait(IntPtr tape, IntPtr buffPtr, UIntPtr buffLen, ulong timeoutMs, out UIntPtr actualLen);
+
+        [DllImport(ExchangeClient.NativeLib, EntryPoint = "trade_tape_stats", ExactSpelling = true, CallingConvention = CallingConvention.Cdecl)]
+        unsafe private static extern FFIResult GetStats(IntPtr tape, out TapeStats stats);
+
+        public delegate void OnTapeTrades(IEnumerable<TapeTrade> trades);
+
+        private IntPtr _tape_handle;
+        private FFITapeTrade[] tradesBuff = new FFITapeTrade[256];
+        private Thread listenThread = null;
+        private volatile bool stopped = false;
+
+        public TradeTape(ulong windowMs): this(windowMs, 4096)
+        {
+        }
+
+        public TradeTape(ulong windowMs, ulong capacity) {
+            ExchangeClient.handleResult(CreateTradeTape(windowMs, (UIntPtr)capacity, out _tape_handle));
+        }
+
+        // Subscribes to the trades of `market` on the client, tagging them with the canonical `symbol`.
+        // Returns the venue id trades of this client are tagged with.
+        public uint AddVenue(ExchangeClient client, string market, string symbol) {
+            ExchangeClient.handleResult(AddVenue(_tape_handle, client.ClientHandle, client.SubHandle, market, symbol, out var venue, out var subscriptionId));
+            return venue;
+        }
+
+        // Blocks up to timeoutMs for trades that left the reordering window
+        unsafe public List<TapeTrade> Wait(ulong timeoutMs) {
+            var trades = new List<TapeTrade>();
+            fixed (FFITapeTrade* buff = tradesBuff.AsSpan()) {
+                ExchangeClient.handleResult(Wait(_tape_handle, (IntPtr)buff, (UIntPtr)tradesBuff.Length, timeoutMs, out var actualLen));
+                for (int i = 0 ; i < (int)actualLen ; i ++) {
+                    trades.Add(tradesBuff[i].ToTapeTrade());
+                    tradesBuff[i].Dispose();
+                }
+            }
+            return trades;
+        }
+
+        // Delivers the merged trades on a background thread until the tape is disposed
+        public void Listen(OnTapeTrades onTrades) {
+            if (listenThread != null) {
+                return;
+            }
+            listenThread = new Thread(() => {
+                while (!stopped) {
+                    var trades = Wait(100);
+                    if (trades.Count > 0) {
+                        onTrades(trades);
+                    }
+                }
+            });
+            listenThread.Start();
+        }
+
+        public TapeStats GetStats() {
+            ExchangeClient.handleResult(GetStats(_tape_handle, out var stats));
+            return stats;
+        }
+
+        public void Dispose() {
+            stopped = true;
+            if (listenThread != null) {
+                listenThread.Join();
+                listenThread = null;
+            }
+            if (_tape_handle != IntPtr.Zero) {
+                FreeTradeTape(_tape_handle);
+                _tape_handle = IntPtr.Zero;
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -6,11 +6,19 @@ namespace OpenLimits
     {
         readonly public string market;
         readonly public IEnumerable<Trade> trades;
+        readonly public DeliveryStats stats;
+        readonly public EventTimestamps timestamps;
 
-        public TradesResponse(string market, IEnumerable<Trade> trades)
+        public TradesResponse(string market, IEnumerable<Trade> trades): this(market, trades, default(DeliveryStats), default(EventTimestamps))
+        {
+        }
+
+        public TradesResponse(string market, IEnumerable<Trade> trades, DeliveryStats stats, EventTimestamps timestamps)
         {
             this.market = market;
             this.trades = trades;
+            this.stats = stats;
+            this.timestamps = timestamps;
         }
     }
 }
\ No newline at end of file
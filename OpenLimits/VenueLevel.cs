@@ -0,0 +1,55 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Runtime.InteropServices;
+    using System.Globalization;
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFIVenueLevel
+    {
+        public readonly uint venue;
+        public readonly ExchangeKind exchange;
+        public readonly IntPtr price;
+        public readonly IntPtr qty;
+
+        public void Dispose() {
+            ExchangeClient.FreeString(price);
+            ExchangeClient.FreeString(qty);
+        }
+
+        // Null when the side of the book is empty
+        public VenueLevel? ToVenueLevel() {
+            if (this.price == IntPtr.Zero) {
+                return null;
+            }
+            return new VenueLevel(
+                this.venue,
+                this.exchange,
+                CString.ToString(this.price),
+                CString.ToString(this.qty)
+            );
+        }
+    }
+
+    // A price level of a consolidated order book and the venue it can be traded on
+    public struct VenueLevel
+    {
+        public readonly uint venue;
+        public readonly ExchangeKind exchange;
+        public readonly decimal price;
+        public readonly decimal qty;
+
+        public VenueLevel(uint venue, ExchangeKind exchange, string price, string qty)
+        {
+            this.venue = venue;
+            this.exchange = exchange;
+            this.price = Decimal.Parse(price, System.Globalization.NumberStyles.AllowDecimalPoint, CultureInfo.InvariantCulture);
+            this.qty = Decimal.Parse(qty, System.Globalization.NumberStyles.AllowDecimalPoint, CultureInfo.InvariantCulture);
+        }
+
+        public override string ToString()
+        {
+            return "VenueLevel { venue=" + venue + ", exchange=" + exchange + ", price=" + price + ", qty=" + qty + "}";
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,28 @@
+namespace OpenLimits
+{
+    using System;
+
+    // An error or unsupported message reported by a websocket subscription
+    public class WebSocketError
+    {
+        readonly public WebSocketEventKind kind;
+        readonly public Subscription subscription;
+        readonly public Exception error;
+
+        public WebSocketError(WebSocketEventKind kind, Subscription subscription, Exception error)
+        {
+            this.kind = kind;
+            this.subscription = subscription;
+            this.error = error;
+        }
+
+        public override string ToString()
+        {
+            return "WebSocketError{" +
+                "kind=" + kind +
+                ", subscription=" + subscription +
+                ", error='" + error.Message + '\'' +
+                '}';
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,113 @@
+namespace OpenLimits
+{
+    using System;
+    using System.Collections.Generic;
+    using System.Runtime.InteropServices;
+
+    // A stream event handed out by wait_events/poll_events. Every pointer is owned by rust
+    // and released with free_events, so the arrays are copied out and never disposed here.
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct FFIEvent
+    {
+        public readonly WebSocketEventKind kind;
+        public readonly FFISubscription subscription;
+        public readonly IntPtr bids;
+        public readonly ulong bidsLen;
+        public readonly IntPtr asks;
+        public readonly ulong asksLen;
+        public readonly IntPtr trades;
+        public readonly ulong tradesLen;
+        public readonly IntPtr orders;
+        public readonly ulong ordersLen;
+        public readonly IntPtr balances;
+        public readonly ulong balancesLen;
+        public readonly IntPtr tradeStats;
+        public readonly ulong tradeStatsLen;
+        public readonly IntPtr depth;
+        public readonly ulong depthLen;
+        public readonly ulong lastUpdateId;
+        public readonly ulong updateId;
+        public readonly Candle candle;
+        public readonly Ticker ticker;
+        public readonly BookAnalytics bookAnalytics;
+        public readonly ResultTag errorTag;
+        public readonly IntPtr errorMessage;
+        public readonly ulong silentMs;
+        public readonly ulong dropped;
+        public readonly ulong conflated;
+        public readonly ulong totalDropped;
+        public readonly ulong totalConflated;
+        public readonly ulong receivedMonotonicNs;
+        public readonly ulong receivedAt;
+        public readonly ulong exchangeTime;
+
+        public EventTimestamps ToTimestamps() {
+            return new EventTimestamps(this.receivedMonotonicNs, this.receivedAt, this.exchangeTime);
+        }
+
+        public DeliveryStats ToDeliveryStats() {
+            return new DeliveryStats(this.dropped, this.conflated, this.totalDropped, this.totalConflated);
+        }
+
+        unsafe public List<AskBid> ToBids() {
+            return ToAskBids((FFIAskBid*)this.bids, this.bidsLen);
+        }
+
+        unsafe public List<AskBid> ToAsks() {
+            return ToAskBids((FFIAskBid*)this.asks, this.asksLen);
+        }
+
+        unsafe public List<Trade> ToTrades() {
+            var trades = new List<Trade>();
+            var buff = (FFITrade*)this.trades;
+            for (int i = 0 ; i < (int)this.tradesLen ; i ++) {
+                trades.Add(buff[i].ToTrade());
+            }
+            return trades;
+        }
+
+        unsafe public List<Order> ToOrders() {
+            var orders = new List<Order>();
+            var buff = (FFIOrder*)this.orders;
+            for (int i = 0 ; i < (int)this.ordersLen ; i ++) {
+                orders.Add(buff[i].ToOrder());
+            }
+            return orders;
+        }
+
+        unsafe public List<Balance> ToBalances() {
+            var balances = new List<Balance>();
+            var buff = (FFIBalance*)this.balances;
+            for (int i = 0 ; i < (int)this.balancesLen ; i ++) {
+                balances.Add(buff[i].ToBalance());
+            }
+            return balances;
+        }
+
+        unsafe public List<TradeStats> ToTradeStats() {
+            var stats = new List<TradeStats>();
+            var buff = (TradeStats*)this.tradeStats;
+            for (int i = 0 ; i < (int)this.tradeStatsLen ; i ++) {
+                stats.Add(buff[i]);
+            }
+            return stats;
+        }
+
+        unsafe public List<DepthAtDistance> ToDepth() {
+            var depth = new List<DepthAtDistance>();
+            var buff = (DepthAtDistance*)this.depth;
+            for (int i = 0 ; i < (int)this.depthLen ; i ++) {
+                depth.Add(buff[i]);
+            }
+            return depth;
+        }
+
+        unsafe private static List<AskBid> ToAskBids(FFIAskBid* buff, ulong len) {
+            var askBids = new List<AskBid>();
+            for (int i = 0 ; i < (int)len ; i ++) {
+                askBids.Add(buff[i].ToAskBid());
+            }
+            return askBids;
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+namespace OpenLimits
+{
+    public enum WebSocketEventKind
+    {
+        Ping,
+        OrderbookSnapshot,
+        OrderbookDiff,
+        Trades,
+        Error,
+        Unsupported,
+        Candle,
+        CandleClosed,
+        Ticker,
+        OrderUpdate,
+        Fill,
+        BalanceUpdate,
+        Stale,
+        Resumed,
+        TradeStats,
+        BookAnalytics
+    }
+}
\ No newline at end of file
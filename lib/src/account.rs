@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use rust_decimal::Decimal;
+use openlimits::{
+  any_exchange::AnyExchange,
+  exchange::ExchangeAccount,
+  model::{Balance, GetOrderRequest, Order, TradeHistoryRequest}
+};
+use crate::{FFIWsEventKind, OpenlimitsSharpError, error_parts, openlimits_error_parts, order_status_to_ffi};
+use crate::events::EventPayload;
+use crate::ratelimit::{FFIEndpoint, RateLimiter};
+
+#[derive(Debug, Copy, Clone)]
+pub enum AccountStream {
+  Orders,
+  Trades,
+  Balances,
+}
+
+fn order_changed(previous: &Order, current: &Order) -> bool {
+  order_status_to_ffi(previous.status.clone()) as u32 != order_status_to_ffi(current.status.clone()) as u32
+    || previous.remaining != current.remaining
+}
+
+// REST polling fallback for account streams, since the generic websocket layer has no user data streams.
+// Orders and balances start with the current state, fills only report trades made after the first poll.
+// Polls wait for the client's rate limit budget instead of failing.
+pub struct AccountPoller {
+  stream: AccountStream,
+  market: Option<String>,
+  open_orders: HashMap<String, Order>,
+  seen_trades: HashSet<String>,
+  balances: HashMap<String, (Decimal, Decimal)>,
+  seeded: bool,
+  limiter: Arc<RateLimiter>,
+}
+
+impl AccountPoller {
+  pub fn new(stream: AccountStream, market: Option<String>, limiter: Arc<RateLimiter>) -> Self {
+    AccountPoller {
+      stream,
+      market,
+      open_orders: HashMap::new(),
+      seen_trades: HashSet::new(),
+      balances: HashMap::new(),
+      seeded: false,
+      limiter,
+    }
+  }
+
+  // Waits for the rate limit budget of a call as long as the client allows
+  async fn throttle(&self, endpoint: FFIEndpoint) -> Result<(), OpenlimitsSharpError> {
+    self.limiter.acquire_async(endpoint, self.limiter.deadline()).await.map_err(OpenlimitsSharpError::RateLimited)
+  }
+
+  pub async fn poll(&mut self, exchange: &AnyExchange) -> Result<Vec<(FFIWsEventKind, EventPayload)>, OpenlimitsSharpError> {
+    let updates = match self.stream {
+      AccountStream::Orders => self.poll_orders(exchange).await?,
+      AccountStream::Trades => self.poll_trades(exchange).await?,
+      AccountStream::Balances => self.poll_balances(exchange).await?,
+    };
+    self.seeded = true;
+    Ok(updates)
+  }
+
+  async fn poll_orders(&mut self, exchange: &AnyExchange) -> Result<Vec<(FFIWsEventKind, EventPayload)>, OpenlimitsSharpError> {
+    let market = self.market.clone();
+    self.throttle(FFIEndpoint::OpenOrders).await?;
+    let open: Vec<Order> = exchange.get_all_open_orders().await?
+      .into_iter()
+      .filter(|order| market.as_ref().map(|market| &order.market_pair == market).unwrap_or(true))
+      .collect();
+
+    let mut changed = Vec::new();
+    let mut errors = Vec::new();
+    let open_ids: HashSet<String> = open.iter().map(|order| order.id.clone()).collect();
+    // Orders that left the open set were filled, canceled or expired, ask for their final state.
+    // An order stays in the set until its final state is read, so a failed read is retried next poll.
+    let closed: Vec<Order> = self.open_orders.values().filter(|order| !open_ids.contains(&order.id)).cloned().collect();
+    for order in closed {
+      if let Err(e) = self.throttle(FFIEndpoint::GetOrder).await {
+        errors.push(error_parts(&e));
+        break;
+      }
+      let final_order = exchange.get_order(&GetOrderRequest {
+        id: order.id.clone(),
+        market_pair: Some(order.market_pair.clone())
+      }).await;
+      match final_order {
+        Ok(final_order) => {
+          self.open_orders.remove(&order.id);
+          changed.push(final_order);
+        },
+        Err(e) => errors.push(openlimits_error_parts(&e))
+      }
+    }
+    for order in open {
+      let is_changed = match self.open_orders.get(&order.id) {
+        Some(previous) => order_changed(previous, &order),
+        None => true
+      };
+      if is_changed {
+        changed.push(order.clone());
+      }
+      self.open_orders.insert(order.id.clone(), order);
+    }
+
+    let mut updates = Vec::new();
+    if !changed.is_empty() {
+      updates.push((FFIWsEventKind::OrderUpdate, EventPayload::Orders(changed)));
+    }
+    for (tag, message) in errors {
+      updates.push((FFIWsEventKind::Error, EventPayload::Error(tag, message)));
+    }
+    Ok(updates)
+  }
+
+  async fn poll_trades(&mut self, exchange: &AnyExchange) -> Result<Vec<(FFIWsEventKind, EventPayload)>, OpenlimitsSharpError> {
+    self.throttle(FFIEndpoint::TradeHistory).await?;
+    let trades = exchange.get_trade_history(&TradeHistoryRequest {
+      market_pair: self.market.clone(),
+      order_id: None,
+      paginator: None
+    }).await?;
+
+    let fills: Vec<_> = trades.iter().filter(|trade| !self.seen_trades.contains(&trade.id)).cloned().collect();
+    // Only the latest page is remembered, older trades never show up in it again
+    self.seen_trades = trades.into_iter().map(|trade| trade.id).collect();
+
+    if !self.seeded || fills.is_empty() {
+      return Ok(Vec::new());
+    }
+    Ok(vec![(FFIWsEventKind::Fill, EventPayload::Trades(fills))])
+  }
+
+  async fn poll_balances(&mut self, exchange: &AnyExchange) -> Result<Vec<(FFIWsEventKind, EventPayload)>, OpenlimitsSharpError> {
+    self.throttle(FFIEndpoint::AccountBalances).await?;
+    let balances = exchange.get_account_balances(None).await?;
+
+    let changed: Vec<Balance> = balances.into_iter().filter(|balance| {
+      let current = (balance.total, balance.free);
+      self.balances.insert(balance.asset.clone(), current) != Some(current)
+    }).collect();
+
+    if changed.is_empty() {
+      return Ok(Vec::new());
+    }
+    Ok(vec![(FFIWsEventKind::BalanceUpdate, EventPayload::Balances(changed))])
+  }
+}
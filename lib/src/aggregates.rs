@@ -0,0 +1,318 @@
+use std::collections::VecDeque;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::*;
+use openlimits::model::{Candle, Trade};
+use crate::{FFIInterval, FFIWsEventKind};
+use crate::events::EventPayload;
+
+const MINUTE_MS: u64 = 60_000;
+const DAY_MS: u64 = 24 * 60 * MINUTE_MS;
+// 1970-01-01 was a Thursday, weeks start on Monday
+const WEEK_OFFSET_MS: u64 = 3 * DAY_MS;
+
+fn interval_ms(interval: FFIInterval) -> u64 {
+  match interval {
+    FFIInterval::OneMinute => MINUTE_MS,
+    FFIInterval::ThreeMinutes => 3 * MINUTE_MS,
+    FFIInterval::FiveMinutes => 5 * MINUTE_MS,
+    FFIInterval::FifteenMinutes => 15 * MINUTE_MS,
+    FFIInterval::ThirtyMinutes => 30 * MINUTE_MS,
+    FFIInterval::OneHour => 60 * MINUTE_MS,
+    FFIInterval::TwoHours => 2 * 60 * MINUTE_MS,
+    FFIInterval::FourHours => 4 * 60 * MINUTE_MS,
+    FFIInterval::SixHours => 6 * 60 * MINUTE_MS,
+    FFIInterval::EightHours => 8 * 60 * MINUTE_MS,
+    FFIInterval::TwelveHours => 12 * 60 * MINUTE_MS,
+    FFIInterval::OneDay => DAY_MS,
+    FFIInterval::ThreeDays => 3 * DAY_MS,
+    FFIInterval::OneWeek => 7 * DAY_MS,
+    FFIInterval::OneMonth => 0,
+  }
+}
+
+// Day of the month (1 based) for days since the unix epoch, from Howard Hinnant's civil_from_days
+fn day_of_month(days: u64) -> u64 {
+  let z = days + 719_468;
+  let doe = z % 146_097;
+  let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  doy - (153 * mp + 2) / 5 + 1
+}
+
+// Start of the candle a trade at `time` (unix ms) belongs to, in UTC
+pub fn bucket_start(interval: FFIInterval, time: u64) -> u64 {
+  match interval {
+    FFIInterval::OneMonth => {
+      let days = time / DAY_MS;
+      (days - (day_of_month(days) - 1)) * DAY_MS
+    },
+    FFIInterval::OneWeek => {
+      let week = interval_ms(interval);
+      ((time + WEEK_OFFSET_MS) / week * week).saturating_sub(WEEK_OFFSET_MS)
+    },
+    _ => {
+      let length = interval_ms(interval);
+      time / length * length
+    }
+  }
+}
+
+fn start_candle(time: u64, trade: &Trade) -> Candle {
+  Candle {
+    time,
+    low: trade.price,
+    high: trade.price,
+    open: trade.price,
+    close: trade.price,
+    volume: trade.qty,
+  }
+}
+
+fn add_to_candle(candle: &mut Candle, trade: &Trade) {
+  candle.low = std::cmp::min(candle.low, trade.price);
+  candle.high = std::cmp::max(candle.high, trade.price);
+  candle.close = trade.price;
+  candle.volume += trade.qty;
+}
+
+// Builds candles of one interval from the trade stream. Trades belonging to an already closed candle are ignored.
+pub struct CandleBuilder {
+  interval: FFIInterval,
+  current: Option<Candle>,
+  last_trade_time: u64,
+}
+
+impl CandleBuilder {
+  fn update(&mut self, trades: &[Trade]) -> Vec<(FFIWsEventKind, EventPayload)> {
+    let mut events = Vec::new();
+    for trade in trades {
+      let start = bucket_start(self.interval, trade.created_at);
+      match &mut self.current {
+        Some(candle) if candle.time == start => add_to_candle(candle, trade),
+        Some(candle) if candle.time > start => continue,
+        _ => {
+          if let Some(closed) = self.current.take() {
+            events.push((FFIWsEventKind::CandleClosed, EventPayload::Candle(closed, self.last_trade_time)));
+          }
+          self.current = Some(start_candle(start, trade));
+        }
+      }
+      self.last_trade_time = trade.created_at;
+    }
+    if let Some(candle) = &self.current {
+      if !trades.is_empty() {
+        events.push((FFIWsEventKind::Candle, EventPayload::Candle(candle.clone(), self.last_trade_time)));
+      }
+    }
+    events
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct TickerState {
+  pub price: Decimal,
+  pub qty: Decimal,
+  pub time: u64,
+  pub open_24h: Decimal,
+  pub high_24h: Decimal,
+  pub low_24h: Decimal,
+  pub volume_24h: Decimal,
+}
+
+// Last trade plus rolling 24 hour statistics, kept as one minute candles
+#[derive(Default)]
+pub struct TickerBuilder {
+  minutes: VecDeque<Candle>,
+}
+
+impl TickerBuilder {
+  fn update(&mut self, trades: &[Trade]) -> Vec<(FFIWsEventKind, EventPayload)> {
+    let last = match trades.iter().max_by_key(|trade| trade.created_at) {
+      Some(last) => last,
+      None => return Vec::new()
+    };
+    for trade in trades {
+      let start = bucket_start(FFIInterval::OneMinute, trade.created_at);
+      match self.minutes.iter_mut().rev().find(|candle| candle.time == start) {
+        Some(candle) => add_to_candle(candle, trade),
+        None => {
+          let position = self.minutes.iter().position(|candle| candle.time > start).unwrap_or_else(|| self.minutes.len());
+          self.minutes.insert(position, start_candle(start, trade));
+        }
+      }
+    }
+    let window_start = last.created_at.saturating_sub(DAY_MS);
+    while self.minutes.front().map(|candle| candle.time + MINUTE_MS <= window_start).unwrap_or(false) {
+      self.minutes.pop_front();
+    }
+
+    let ticker = TickerState {
+      price: last.price,
+      qty: last.qty,
+      time: last.created_at,
+      open_24h: self.minutes.front().map(|candle| candle.open).unwrap_or(last.price),
+      high_24h: self.minutes.iter().map(|candle| candle.high).max().unwrap_or(last.price),
+      low_24h: self.minutes.iter().map(|candle| candle.low).min().unwrap_or(last.price),
+      volume_24h: self.minutes.iter().map(|candle| candle.volume).sum(),
+    };
+    vec![(FFIWsEventKind::Ticker, EventPayload::Ticker(ticker))]
+  }
+}
+
+// Streams synthesized from trades, for exchanges and markets without a native stream
+pub enum Aggregate {
+  Candles(CandleBuilder),
+  Ticker(TickerBuilder),
+}
+
+impl Aggregate {
+  pub fn candles(interval: FFIInterval) -> Self {
+    Aggregate::Candles(CandleBuilder {
+      interval,
+      current: None,
+      last_trade_time: 0,
+    })
+  }
+
+  pub fn ticker() -> Self {
+    Aggregate::Ticker(TickerBuilder::default())
+  }
+
+  pub fn update(&mut self, trades: &[Trade]) -> Vec<(FFIWsEventKind, EventPayload)> {
+    match self {
+      Aggregate::Candles(builder) => builder.update(trades),
+      Aggregate::Ticker(builder) => builder.update(trades),
+    }
+  }
+}
+
+pub fn price_change_percent(ticker: &TickerState) -> f64 {
+  if ticker.open_24h.is_zero() {
+    return 0.0;
+  }
+  ((ticker.price - ticker.open_24h) / ticker.open_24h * Decimal::from(100)).to_f64().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::FromStr;
+  use openlimits::model::Side;
+  use super::*;
+
+  const HOUR_MS: u64 = 60 * MINUTE_MS;
+  // Midnight UTC of the dates, in unix ms
+  const DEC_01_2019: u64 = 1_575_158_400_000;
+  const DEC_30_2019: u64 = 1_577_664_000_000;
+  const DEC_31_2019: u64 = 1_577_750_400_000;
+  const JAN_01_2020: u64 = 1_577_836_800_000;
+  const FEB_01_2020: u64 = 1_580_515_200_000;
+  const FEB_24_2020: u64 = 1_582_502_400_000;
+  const FEB_28_2020: u64 = 1_582_848_000_000;
+  const FEB_29_2020: u64 = 1_582_934_400_000;
+  const MAR_01_2020: u64 = 1_583_020_800_000;
+  const MAR_02_2020: u64 = 1_583_107_200_000;
+  const FEB_01_2100: u64 = 4_105_123_200_000;
+  const MAR_01_2100: u64 = 4_107_542_400_000;
+
+  fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+  }
+
+  fn trade(created_at: u64, price: &str) -> Trade {
+    Trade {
+      id: created_at.to_string(),
+      buyer_order_id: None,
+      seller_order_id: None,
+      market_pair: String::from("btcusd"),
+      price: dec(price),
+      qty: dec("1"),
+      fees: None,
+      side: Side::Buy,
+      liquidity: None,
+      created_at,
+    }
+  }
+
+  fn ticker(builder: &mut TickerBuilder, trades: &[Trade]) -> TickerState {
+    match builder.update(trades).pop() {
+      Some((FFIWsEventKind::Ticker, EventPayload::Ticker(ticker))) => ticker,
+      _ => panic!("Expected a ticker")
+    }
+  }
+
+  #[test]
+  fn day_of_month_across_month_year_and_leap_days() {
+    assert_eq!(day_of_month(0), 1);
+    assert_eq!(day_of_month(DEC_31_2019 / DAY_MS), 31);
+    assert_eq!(day_of_month(JAN_01_2020 / DAY_MS), 1);
+    assert_eq!(day_of_month(FEB_28_2020 / DAY_MS), 28);
+    assert_eq!(day_of_month(FEB_29_2020 / DAY_MS), 29);
+    assert_eq!(day_of_month(MAR_01_2020 / DAY_MS), 1);
+    // 2100 is not a leap year
+    assert_eq!(day_of_month(MAR_01_2100 / DAY_MS - 1), 28);
+    assert_eq!(day_of_month(MAR_01_2100 / DAY_MS), 1);
+  }
+
+  #[test]
+  fn month_buckets_start_on_the_first() {
+    assert_eq!(bucket_start(FFIInterval::OneMonth, JAN_01_2020 - 1), DEC_01_2019);
+    assert_eq!(bucket_start(FFIInterval::OneMonth, JAN_01_2020), JAN_01_2020);
+    assert_eq!(bucket_start(FFIInterval::OneMonth, FEB_29_2020 + 15 * HOUR_MS), FEB_01_2020);
+    assert_eq!(bucket_start(FFIInterval::OneMonth, MAR_01_2020 - 1), FEB_01_2020);
+    assert_eq!(bucket_start(FFIInterval::OneMonth, MAR_01_2020), MAR_01_2020);
+    assert_eq!(bucket_start(FFIInterval::OneMonth, MAR_01_2100 - 1), FEB_01_2100);
+  }
+
+  #[test]
+  fn week_buckets_start_on_monday() {
+    assert_eq!(bucket_start(FFIInterval::OneWeek, JAN_01_2020 + 12 * HOUR_MS), DEC_30_2019);
+    // 2020-03-01 was a Sunday
+    assert_eq!(bucket_start(FFIInterval::OneWeek, MAR_01_2020 + 12 * HOUR_MS), FEB_24_2020);
+    assert_eq!(bucket_start(FFIInterval::OneWeek, MAR_02_2020), MAR_02_2020);
+  }
+
+  #[test]
+  fn fixed_buckets_across_the_leap_day() {
+    assert_eq!(bucket_start(FFIInterval::OneDay, FEB_29_2020 + 12 * HOUR_MS), FEB_29_2020);
+    assert_eq!(bucket_start(FFIInterval::FourHours, MAR_01_2020 - MINUTE_MS), FEB_29_2020 + 20 * HOUR_MS);
+    assert_eq!(bucket_start(FFIInterval::ThreeDays, MAR_01_2020) % (3 * DAY_MS), 0);
+    assert_eq!(bucket_start(FFIInterval::FifteenMinutes, JAN_01_2020 + 14 * MINUTE_MS + 59_999), JAN_01_2020);
+  }
+
+  #[test]
+  fn ticker_rolls_over_the_leap_day_into_march() {
+    let mut builder = TickerBuilder::default();
+    ticker(&mut builder, &[trade(FEB_28_2020 + 23 * HOUR_MS + 59 * MINUTE_MS + 30_000, "100")]);
+    ticker(&mut builder, &[trade(FEB_29_2020 + 12 * HOUR_MS, "120")]);
+    let state = ticker(&mut builder, &[trade(MAR_01_2020 - MINUTE_MS, "110")]);
+    assert_eq!((state.open_24h, state.volume_24h), (dec("100"), dec("3")));
+
+    // A day after the first trade's minute ended, it leaves the window
+    let state = ticker(&mut builder, &[trade(MAR_01_2020 + 30_000, "90")]);
+    assert_eq!(state.price, dec("90"));
+    assert_eq!(state.time, MAR_01_2020 + 30_000);
+    assert_eq!((state.open_24h, state.high_24h, state.low_24h), (dec("120"), dec("120"), dec("90")));
+    assert_eq!(state.volume_24h, dec("3"));
+  }
+
+  #[test]
+  fn ticker_rolls_over_the_new_year() {
+    let mut builder = TickerBuilder::default();
+    ticker(&mut builder, &[trade(DEC_31_2019, "50")]);
+    let state = ticker(&mut builder, &[trade(JAN_01_2020 + 59_999, "60")]);
+    assert_eq!((state.open_24h, state.volume_24h), (dec("50"), dec("2")));
+    let state = ticker(&mut builder, &[trade(JAN_01_2020 + MINUTE_MS, "70")]);
+    assert_eq!((state.open_24h, state.low_24h, state.volume_24h), (dec("60"), dec("60"), dec("2")));
+  }
+
+  #[test]
+  fn ticker_sorts_in_late_trades_of_the_window() {
+    let mut builder = TickerBuilder::default();
+    ticker(&mut builder, &[trade(FEB_01_2020 + 2 * HOUR_MS, "100")]);
+    // The latest trade of a batch is the ticker price, whatever the order
+    let state = ticker(&mut builder, &[trade(FEB_01_2020 + 3 * HOUR_MS, "110"), trade(FEB_01_2020 + HOUR_MS, "80")]);
+    assert_eq!((state.price, state.open_24h, state.low_24h), (dec("110"), dec("80"), dec("80")));
+    assert_eq!(price_change_percent(&state), 37.5);
+  }
+}
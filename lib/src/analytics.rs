@@ -0,0 +1,159 @@
+use rust_decimal::Decimal;
+use rust_decimal::prelude::*;
+use openlimits::model::OrderBookResponse;
+use crate::book::LocalBook;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFIBookAnalyticsConfig {
+  // Levels per side used for imbalance and pressure
+  top_levels: u32,
+  // 0 turns periodic BookAnalytics events off
+  push_interval_ms: u64,
+  // Also push BookAnalytics after every order book update
+  push_on_update: bool,
+}
+
+// Every price field is 0 while the side it needs is empty
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFIBookAnalytics {
+  update_id: u64,
+  best_bid: f64,
+  best_bid_qty: f64,
+  best_ask: f64,
+  best_ask_qty: f64,
+  mid: f64,
+  spread: f64,
+  spread_bps: f64,
+  // (bid qty - ask qty) / (bid qty + ask qty) over the top levels, from -1 (all asks) to 1 (all bids)
+  imbalance: f64,
+  // Mid weighted by the opposite side's top of book quantity
+  microprice: f64,
+  // Like imbalance, with every level weighted by 1 / (1 + its distance from mid in bps)
+  pressure: f64,
+}
+
+// Liquidity resting within `distance_bps` of mid
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFIDepthAtDistance {
+  distance_bps: f64,
+  bid_qty: f64,
+  ask_qty: f64,
+  bid_notional: f64,
+  ask_notional: f64,
+}
+
+fn ratio(bid: f64, ask: f64) -> f64 {
+  match bid + ask {
+    total if total > 0.0 => (bid - ask) / total,
+    _ => 0.0
+  }
+}
+
+fn to_f64(value: Decimal) -> f64 {
+  value.to_f64().unwrap_or_default()
+}
+
+// Keeps a local copy of a subscription's order book to compute analytics on it without handing out the levels
+pub struct BookAnalytics {
+  config: FFIBookAnalyticsConfig,
+  distances_bps: Vec<f64>,
+  last_push_ms: u64,
+  book: LocalBook,
+}
+
+impl BookAnalytics {
+  pub fn new(config: FFIBookAnalyticsConfig, distances_bps: Vec<f64>, now_ms: u64) -> Self {
+    BookAnalytics {
+      config,
+      distances_bps,
+      last_push_ms: now_ms,
+      book: LocalBook::default(),
+    }
+  }
+
+  // Returns the analytics to push if they are pushed on every update
+  pub fn apply(&mut self, snapshot: bool, resp: &OrderBookResponse) -> Option<(FFIBookAnalytics, Vec<FFIDepthAtDistance>)> {
+    if !self.book.apply(snapshot, resp) || !self.config.push_on_update {
+      return None;
+    }
+    Some((self.analytics(), self.depth()))
+  }
+
+  fn mid(&self) -> Option<f64> {
+    match (self.book.best_bid(), self.book.best_ask()) {
+      (Some((bid, _)), Some((ask, _))) => Some(to_f64((bid + ask) / Decimal::new(2, 0))),
+      _ => None
+    }
+  }
+
+  pub fn analytics(&self) -> FFIBookAnalytics {
+    let top_levels = std::cmp::max(self.config.top_levels, 1) as usize;
+    let mut analytics = FFIBookAnalytics {
+      update_id: self.book.update_id.unwrap_or_default(),
+      ..FFIBookAnalytics::default()
+    };
+    if let Some((price, qty)) = self.book.best_bid() {
+      analytics.best_bid = to_f64(price);
+      analytics.best_bid_qty = to_f64(qty);
+    }
+    if let Some((price, qty)) = self.book.best_ask() {
+      analytics.best_ask = to_f64(price);
+      analytics.best_ask_qty = to_f64(qty);
+    }
+    let mid = match self.mid() {
+      Some(mid) => mid,
+      None => return analytics
+    };
+    analytics.mid = mid;
+    analytics.spread = analytics.best_ask - analytics.best_bid;
+    analytics.spread_bps = analytics.spread / mid * 10_000.0;
+    analytics.microprice = (analytics.best_bid * analytics.best_ask_qty + analytics.best_ask * analytics.best_bid_qty)
+      / (analytics.best_bid_qty + analytics.best_ask_qty);
+
+    let bids: Vec<(f64, f64)> = self.book.bids.iter().rev().take(top_levels).map(|(price, qty)| (to_f64(*price), to_f64(*qty))).collect();
+    let asks: Vec<(f64, f64)> = self.book.asks.iter().take(top_levels).map(|(price, qty)| (to_f64(*price), to_f64(*qty))).collect();
+    let weighted = |levels: &[(f64, f64)]| -> f64 {
+      levels.iter().map(|(price, qty)| qty / (1.0 + (price - mid).abs() / mid * 10_000.0)).sum()
+    };
+    analytics.imbalance = ratio(bids.iter().map(|(_, qty)| qty).sum(), asks.iter().map(|(_, qty)| qty).sum());
+    analytics.pressure = ratio(weighted(&bids), weighted(&asks));
+    analytics
+  }
+
+  // One entry per configured distance, empty while either side of the book is
+  pub fn depth(&self) -> Vec<FFIDepthAtDistance> {
+    let mid = match self.mid() {
+      Some(mid) => mid,
+      None => return Vec::new()
+    };
+    self.distances_bps.iter().map(|distance_bps| {
+      let mut depth = FFIDepthAtDistance {
+        distance_bps: *distance_bps,
+        ..FFIDepthAtDistance::default()
+      };
+      let low = mid * (1.0 - distance_bps / 10_000.0);
+      let high = mid * (1.0 + distance_bps / 10_000.0);
+      for (price, qty) in self.book.bids.iter().rev().map(|(price, qty)| (to_f64(*price), to_f64(*qty))).take_while(|(price, _)| *price >= low) {
+        depth.bid_qty += qty;
+        depth.bid_notional += price * qty;
+      }
+      for (price, qty) in self.book.asks.iter().map(|(price, qty)| (to_f64(*price), to_f64(*qty))).take_while(|(price, _)| *price <= high) {
+        depth.ask_qty += qty;
+        depth.ask_notional += price * qty;
+      }
+      depth
+    }).collect()
+  }
+
+  // Analytics to push if the push interval passed since the last push
+  pub fn due(&mut self, now_ms: u64) -> Option<(FFIBookAnalytics, Vec<FFIDepthAtDistance>)> {
+    if self.config.push_interval_ms == 0 || now_ms.saturating_sub(self.last_push_ms) < self.config.push_interval_ms {
+      return None;
+    }
+    self.last_push_ms = now_ms;
+    Some((self.analytics(), self.depth()))
+  }
+}
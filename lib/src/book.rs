@@ -0,0 +1,411 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use rust_decimal::Decimal;
+use openlimits::model::{AskBid, OrderBookResponse};
+use crate::{FFIExchangeKind, FFIWsEventKind};
+use crate::events::EventPayload;
+use crate::journal::{JournalReader, JournalRecord, JournalStream};
+
+// Price levels of one market on one exchange, kept up to date from snapshots and diffs
+#[derive(Default)]
+pub struct LocalBook {
+  pub bids: BTreeMap<Decimal, Decimal>,
+  pub asks: BTreeMap<Decimal, Decimal>,
+  // Newest update id applied, diffs at or below it are late and skipped
+  pub update_id: Option<u64>,
+}
+
+fn apply_levels(side: &mut BTreeMap<Decimal, Decimal>, levels: &[AskBid]) {
+  for level in levels {
+    if level.qty.is_zero() {
+      side.remove(&level.price);
+    } else {
+      side.insert(level.price, level.qty);
+    }
+  }
+}
+
+impl LocalBook {
+  // Returns false if the update was skipped as late
+  pub fn apply(&mut self, snapshot: bool, resp: &OrderBookResponse) -> bool {
+    let update_id = std::cmp::max(resp.update_id, resp.last_update_id);
+    if snapshot {
+      self.bids.clear();
+      self.asks.clear();
+    } else if let (Some(current), Some(update_id)) = (self.update_id, update_id) {
+      if update_id <= current {
+        return false;
+      }
+    }
+    apply_levels(&mut self.bids, &resp.bids);
+    apply_levels(&mut self.asks, &resp.asks);
+    if update_id.is_some() {
+      self.update_id = update_id;
+    }
+    true
+  }
+
+  pub fn best_bid(&self) -> Option<(Decimal, Decimal)> {
+    self.bids.iter().next_back().map(|(price, qty)| (*price, *qty))
+  }
+
+  pub fn best_ask(&self) -> Option<(Decimal, Decimal)> {
+    self.asks.iter().next().map(|(price, qty)| (*price, *qty))
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct VenueLevel {
+  pub venue: u32,
+  pub exchange: FFIExchangeKind,
+  pub price: Decimal,
+  pub qty: Decimal,
+}
+
+struct Venue {
+  exchange: FFIExchangeKind,
+  book: LocalBook,
+}
+
+impl Venue {
+  fn level(&self, venue: u32, (price, qty): (Decimal, Decimal)) -> VenueLevel {
+    VenueLevel {
+      venue,
+      exchange: self.exchange,
+      price,
+      qty
+    }
+  }
+}
+
+// Order books of the same asset pair on several exchanges. Levels are not summed across venues,
+// every level keeps the venue it can be traded on.
+#[derive(Default)]
+pub struct ConsolidatedBook {
+  venues: Vec<Venue>,
+}
+
+impl ConsolidatedBook {
+  pub fn add_venue(&mut self, exchange: FFIExchangeKind) -> u32 {
+    self.venues.push(Venue {
+      exchange,
+      book: LocalBook::default()
+    });
+    (self.venues.len() - 1) as u32
+  }
+
+  pub fn has_venue(&self, venue: u32) -> bool {
+    (venue as usize) < self.venues.len()
+  }
+
+  pub fn apply(&mut self, venue: u32, snapshot: bool, resp: &OrderBookResponse) {
+    if let Some(entry) = self.venues.get_mut(venue as usize) {
+      entry.book.apply(snapshot, resp);
+    }
+  }
+
+  pub fn venue_best_bid(&self, venue: u32) -> Option<VenueLevel> {
+    let entry = self.venues.get(venue as usize)?;
+    entry.book.best_bid().map(|level| entry.level(venue, level))
+  }
+
+  pub fn venue_best_ask(&self, venue: u32) -> Option<VenueLevel> {
+    let entry = self.venues.get(venue as usize)?;
+    entry.book.best_ask().map(|level| entry.level(venue, level))
+  }
+
+  // On equal prices the venue added first wins
+  pub fn best_bid(&self) -> Option<VenueLevel> {
+    (0..self.venues.len() as u32)
+      .filter_map(|venue| self.venue_best_bid(venue))
+      .fold(None, |best: Option<VenueLevel>, level| match best {
+        Some(best) if best.price >= level.price => Some(best),
+        _ => Some(level)
+      })
+  }
+
+  pub fn best_ask(&self) -> Option<VenueLevel> {
+    (0..self.venues.len() as u32)
+      .filter_map(|venue| self.venue_best_ask(venue))
+      .fold(None, |best: Option<VenueLevel>, level| match best {
+        Some(best) if best.price <= level.price => Some(best),
+        _ => Some(level)
+      })
+  }
+
+  // The best `depth` bid levels across all venues, best first
+  pub fn bids(&self, depth: usize) -> Vec<VenueLevel> {
+    let mut levels: Vec<VenueLevel> = self.venues.iter().enumerate().flat_map(|(venue, entry)| {
+      entry.book.bids.iter().rev().take(depth).map(move |(price, qty)| entry.level(venue as u32, (*price, *qty)))
+    }).collect();
+    levels.sort_by(|a, b| b.price.cmp(&a.price));
+    levels.truncate(depth);
+    levels
+  }
+
+  // The best `depth` ask levels across all venues, best first
+  pub fn asks(&self, depth: usize) -> Vec<VenueLevel> {
+    let mut levels: Vec<VenueLevel> = self.venues.iter().enumerate().flat_map(|(venue, entry)| {
+      entry.book.asks.iter().take(depth).map(move |(price, qty)| entry.level(venue as u32, (*price, *qty)))
+    }).collect();
+    levels.sort_by(|a, b| a.price.cmp(&b.price));
+    levels.truncate(depth);
+    levels
+  }
+}
+
+pub type SharedBook = Arc<Mutex<ConsolidatedBook>>;
+
+// Routes the order book updates of a subscription into one venue of a consolidated book
+#[derive(Clone)]
+pub struct BookSink {
+  pub book: SharedBook,
+  pub venue: u32,
+}
+
+impl BookSink {
+  pub fn apply(&self, snapshot: bool, resp: &OrderBookResponse) {
+    self.book.lock().expect("Consolidated book lock poisoned").apply(self.venue, snapshot, resp);
+  }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFIBookReplayState {
+  // Receive time of the last update applied, unix ms
+  time: u64,
+  update_id: u64,
+  applied: u64,
+  // Diffs skipped because they were older than the book
+  skipped: u64,
+  has_snapshot: bool,
+}
+
+// Rebuilds the order book of a market from its journaled snapshots and diffs, applying them with
+// the same rules as the live consolidated book
+pub struct BookReplay {
+  dir: PathBuf,
+  market: String,
+  records: Peekable<JournalReader>,
+  book: LocalBook,
+  state: FFIBookReplayState,
+}
+
+impl BookReplay {
+  pub fn open(dir: PathBuf, market: String) -> io::Result<Self> {
+    let records = JournalReader::open(dir.clone(), market.clone(), 0, u64::MAX)?.peekable();
+    Ok(BookReplay {
+      dir,
+      market,
+      records,
+      book: LocalBook::default(),
+      state: FFIBookReplayState::default(),
+    })
+  }
+
+  fn next_update(&mut self) -> Option<JournalRecord> {
+    loop {
+      let record = self.records.next()?;
+      if record.stream == JournalStream::OrderBook {
+        return Some(record);
+      }
+    }
+  }
+
+  fn apply(&mut self, record: JournalRecord) {
+    let snapshot = match record.kind {
+      FFIWsEventKind::OrderBookSnapshot => true,
+      FFIWsEventKind::OrderBookDiff => false,
+      _ => return
+    };
+    let resp = match &record.payload {
+      EventPayload::OrderBook(resp) => resp,
+      _ => return
+    };
+    if self.book.apply(snapshot, resp) {
+      self.state.applied += 1;
+    } else {
+      self.state.skipped += 1;
+    }
+    self.state.has_snapshot |= snapshot;
+    self.state.time = record.received_ms;
+    self.state.update_id = self.book.update_id.unwrap_or_default();
+  }
+
+  // Applies the next order book update, false once the journal is exhausted
+  pub fn step(&mut self) -> bool {
+    match self.next_update() {
+      Some(record) => {
+        self.apply(record);
+        true
+      },
+      None => false
+    }
+  }
+
+  // Applies every update received up to `time`, starting over when `time` is before the current state
+  pub fn seek(&mut self, time: u64) -> io::Result<()> {
+    if time < self.state.time {
+      *self = BookReplay::open(self.dir.clone(), self.market.clone())?;
+    }
+    // Trades in between are passed over, they must not let a later book update through
+    loop {
+      match self.records.peek() {
+        Some(record) if record.stream != JournalStream::OrderBook => {
+          self.records.next();
+        },
+        Some(record) if record.received_ms <= time => {
+          if let Some(record) = self.records.next() {
+            self.apply(record);
+          }
+        },
+        _ => return Ok(())
+      }
+    }
+  }
+
+  pub fn book(&self) -> &LocalBook {
+    &self.book
+  }
+
+  pub fn state(&self) -> FFIBookReplayState {
+    self.state
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::FromStr;
+  use openlimits::model::{Side, Trade};
+  use super::*;
+  use crate::journal::{FFIJournalConfig, JournalWriter};
+
+  fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+  }
+
+  fn levels(levels: &[(&str, &str)]) -> Vec<AskBid> {
+    levels.iter().map(|(price, qty)| AskBid { price: dec(price), qty: dec(qty) }).collect()
+  }
+
+  fn book(update_id: Option<u64>, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBookResponse {
+    OrderBookResponse {
+      last_update_id: None,
+      update_id,
+      bids: levels(bids),
+      asks: levels(asks),
+    }
+  }
+
+  fn trade(created_at: u64) -> Trade {
+    Trade {
+      id: created_at.to_string(),
+      buyer_order_id: None,
+      seller_order_id: None,
+      market_pair: String::from("btcusd"),
+      price: dec("100"),
+      qty: dec("1"),
+      fees: None,
+      side: Side::Buy,
+      liquidity: None,
+      created_at,
+    }
+  }
+
+  // A journal directory of its own per test, removed when dropped
+  struct TempDir(PathBuf);
+
+  impl TempDir {
+    fn new(name: &str) -> Self {
+      let dir = std::env::temp_dir().join(format!("openlimits-book-{}-{}", std::process::id(), name));
+      std::fs::remove_dir_all(&dir).ok();
+      TempDir(dir)
+    }
+  }
+
+  impl Drop for TempDir {
+    fn drop(&mut self) {
+      std::fs::remove_dir_all(&self.0).ok();
+    }
+  }
+
+  #[test]
+  fn diffs_update_and_remove_levels() {
+    let mut local = LocalBook::default();
+    assert!(local.apply(true, &book(Some(1), &[("99", "1"), ("98", "2")], &[("101", "1")])));
+    assert!(local.apply(false, &book(Some(2), &[("99", "0"), ("97", "3")], &[("101", "4")])));
+    assert_eq!(local.best_bid(), Some((dec("98"), dec("2"))));
+    assert_eq!(local.best_ask(), Some((dec("101"), dec("4"))));
+    assert_eq!(local.bids.len(), 2);
+    assert_eq!(local.update_id, Some(2));
+  }
+
+  #[test]
+  fn diffs_at_or_below_the_book_are_skipped() {
+    let mut local = LocalBook::default();
+    local.apply(true, &book(Some(5), &[("99", "1")], &[]));
+    assert!(!local.apply(false, &book(Some(5), &[("99", "0")], &[])));
+    assert!(!local.apply(false, &book(Some(4), &[("98", "1")], &[])));
+    assert_eq!(local.best_bid(), Some((dec("99"), dec("1"))));
+    // Diffs without an id always apply and keep the id
+    assert!(local.apply(false, &book(None, &[("98", "1")], &[])));
+    assert_eq!(local.update_id, Some(5));
+    // A snapshot replaces the book whatever its id
+    assert!(local.apply(true, &book(Some(3), &[("50", "1")], &[])));
+    assert_eq!(local.bids.len(), 1);
+    assert_eq!(local.update_id, Some(3));
+  }
+
+  #[test]
+  fn seek_stops_at_the_time_when_trades_are_in_between() {
+    let dir = TempDir::new("seek");
+    {
+      let mut writer = JournalWriter::open(dir.0.clone(), FFIJournalConfig::default()).unwrap();
+      writer.record_book(100, "btcusd", true, &book(Some(1), &[("99", "1")], &[("101", "1")])).unwrap();
+      writer.record_trades(150, "btcusd", &[trade(150)]).unwrap();
+      writer.record_book(200, "btcusd", false, &book(Some(2), &[("99", "2")], &[])).unwrap();
+      writer.record_trades(250, "btcusd", &[trade(250)]).unwrap();
+      writer.record_trades(260, "btcusd", &[trade(260)]).unwrap();
+      writer.record_book(300, "btcusd", false, &book(Some(3), &[("99", "3")], &[])).unwrap();
+    }
+
+    let mut replay = BookReplay::open(dir.0.clone(), String::from("btcusd")).unwrap();
+    replay.seek(150).unwrap();
+    assert_eq!(replay.state().time, 100);
+    assert_eq!(replay.book().best_bid(), Some((dec("99"), dec("1"))));
+    replay.seek(260).unwrap();
+    assert_eq!(replay.state().time, 200);
+    assert_eq!(replay.book().best_bid(), Some((dec("99"), dec("2"))));
+    assert_eq!(replay.state().applied, 2);
+    // Seeking back starts over
+    replay.seek(100).unwrap();
+    assert_eq!(replay.state().applied, 1);
+    assert_eq!(replay.book().best_bid(), Some((dec("99"), dec("1"))));
+    replay.seek(u64::MAX).unwrap();
+    assert_eq!(replay.state().update_id, 3);
+    assert!(!replay.step());
+  }
+
+  #[test]
+  fn step_skips_trades_and_late_diffs() {
+    let dir = TempDir::new("step");
+    {
+      let mut writer = JournalWriter::open(dir.0.clone(), FFIJournalConfig::default()).unwrap();
+      writer.record_trades(50, "btcusd", &[trade(50)]).unwrap();
+      writer.record_book(100, "btcusd", true, &book(Some(5), &[("99", "1")], &[])).unwrap();
+      writer.record_book(110, "ethusd", true, &book(Some(1), &[("10", "1")], &[])).unwrap();
+      writer.record_book(120, "btcusd", false, &book(Some(4), &[("99", "0")], &[])).unwrap();
+    }
+
+    let mut replay = BookReplay::open(dir.0.clone(), String::from("btcusd")).unwrap();
+    assert!(replay.step());
+    assert!(replay.state().has_snapshot);
+    assert!(replay.step());
+    assert_eq!(replay.state().skipped, 1);
+    assert_eq!(replay.book().best_bid(), Some((dec("99"), dec("1"))));
+    assert!(!replay.step());
+  }
+}
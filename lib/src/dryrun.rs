@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use rust_decimal::Decimal;
+use openlimits::model::{Order, OrderStatus, OrderType};
+use crate::UserData;
+use crate::orders::{NewOrder, StopOrderRequest, is_terminal, side_from_ffi};
+
+pub type DryRunCallback = extern fn(message: *const c_char, user_data: *mut c_void);
+
+const SIMULATED_ID_PREFIX: &str = "dry-run-";
+
+pub fn is_simulated(order_id: &str) -> bool {
+  order_id.starts_with(SIMULATED_ID_PREFIX)
+}
+
+// Trading calls of a client in dry run are parsed, validated and risk checked like any other, but
+// never reach the exchange. Placements return simulated orders, and every call reports what would
+// have been sent to the callback dry run was enabled with. Simulated orders are kept for the
+// lifetime of the client so they can be looked up and canceled like real ones.
+#[derive(Default)]
+pub struct DryRun {
+  enabled: AtomicBool,
+  next_id: AtomicU64,
+  log: Mutex<Option<(DryRunCallback, UserData)>>,
+  orders: Mutex<HashMap<String, Order>>,
+}
+
+impl DryRun {
+  pub fn enabled(&self) -> bool {
+    self.enabled.load(Ordering::SeqCst)
+  }
+
+  pub fn set(&self, enabled: bool, log: Option<(DryRunCallback, UserData)>) {
+    *self.log.lock().expect("Dry run lock poisoned") = log;
+    self.enabled.store(enabled, Ordering::SeqCst);
+  }
+
+  pub fn log(&self, message: String) {
+    // Only a disabled dry run is without a callback
+    if let Some((callback, user_data)) = *self.log.lock().expect("Dry run lock poisoned") {
+      let message = CString::new(message).unwrap_or_default();
+      callback(message.as_ptr(), user_data.0);
+    }
+  }
+
+  // The order the exchange would have returned right after accepting `new_order`. Limit orders rest
+  // untouched, market orders are filled in full at an unknown price.
+  pub fn place(&self, new_order: &NewOrder, client_order_id: Option<&str>) -> Order {
+    let (market, side, size, price) = new_order.terms();
+    let id = self.next_id();
+    self.log(format!("place {} as {}", new_order.describe(), id));
+    self.keep(Order {
+      id,
+      market_pair: String::from(market),
+      client_order_id: client_order_id.map(String::from),
+      created_at: Some(chrono::Utc::now().timestamp_millis() as u64),
+      order_type: match price {
+        Some(_) => OrderType::Limit,
+        None => OrderType::Market
+      },
+      side: side_from_ffi(side),
+      status: match price {
+        Some(_) => OrderStatus::New,
+        None => OrderStatus::Filled
+      },
+      size,
+      price,
+      remaining: match price {
+        Some(_) => Some(size),
+        None => Some(Decimal::new(0, 0))
+      },
+    })
+  }
+
+  // The stop order the exchange would have returned right after accepting `req`, waiting for its trigger
+  pub fn place_stop(&self, req: &StopOrderRequest, client_order_id: Option<&str>) -> Order {
+    let id = self.next_id();
+    self.log(format!("place {} as {}", req.describe(), id));
+    self.keep(Order {
+      id,
+      market_pair: req.market_pair.clone(),
+      client_order_id: client_order_id.map(String::from),
+      created_at: Some(chrono::Utc::now().timestamp_millis() as u64),
+      order_type: match req.limit_price {
+        Some(_) => OrderType::StopLimit,
+        None => OrderType::StopMarket
+      },
+      side: side_from_ffi(req.side),
+      status: OrderStatus::New,
+      size: req.size,
+      price: req.limit_price,
+      remaining: Some(req.size),
+    })
+  }
+
+  fn next_id(&self) -> String {
+    format!("{}{}", SIMULATED_ID_PREFIX, self.next_id.fetch_add(1, Ordering::SeqCst))
+  }
+
+  fn keep(&self, order: Order) -> Order {
+    self.orders.lock().expect("Dry run lock poisoned").insert(order.id.clone(), order.clone());
+    order
+  }
+
+  pub fn get(&self, id: &str) -> Option<Order> {
+    self.orders.lock().expect("Dry run lock poisoned").get(id).cloned()
+  }
+
+  // Whether canceling `id` only pretends to: in dry run, and for simulated orders even after it
+  pub fn simulates_cancel(&self, id: &str) -> bool {
+    self.enabled() || is_simulated(id)
+  }
+
+  // Cancels a simulated order, returning it unless it was already done. Other orders are only logged.
+  pub fn cancel(&self, id: &str) -> Option<Order> {
+    self.log(format!("cancel {}", id));
+    let mut orders = self.orders.lock().expect("Dry run lock poisoned");
+    let order = orders.get_mut(id).filter(|order| !is_terminal(&order.status))?;
+    order.status = OrderStatus::Canceled;
+    Some(order.clone())
+  }
+
+  // Open simulated orders, of `market` only if given
+  pub fn open(&self, market: Option<&str>) -> Vec<Order> {
+    self.orders.lock().expect("Dry run lock poisoned").values()
+      .filter(|order| !is_terminal(&order.status) && market.map(|market| order.market_pair.eq_ignore_ascii_case(market)).unwrap_or(true))
+      .cloned()
+      .collect()
+  }
+}
@@ -0,0 +1,992 @@
+use std::collections::{HashMap, HashSet};
+use std::os::raw::{c_char, c_void};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+use rust_decimal::Decimal;
+use openlimits::any_exchange::{AnyExchange, InitAnyExchange};
+use openlimits::errors::OpenLimitsError;
+use openlimits::exchange::{ExchangeAccount, ExchangeMarketData, OpenLimits};
+use openlimits::exchange_info::ExchangeInfoRetrieval;
+use openlimits::model::{
+  Balance, CancelOrderRequest, GetOrderRequest, OpenLimitOrderRequest, OpenMarketOrderRequest, Order, OrderBookRequest, OrderStatus, OrderType,
+  Side, TimeInForce, Trade
+};
+use crate::dryrun::{DryRun, is_simulated};
+use crate::orders::{NewOrder, ffi_side, is_terminal};
+use crate::ratelimit::{FFIEndpoint, RateLimiter};
+use crate::risk::{RiskEngine, RiskInputs};
+use crate::validation::{FFIValidationMode, MarketRules};
+use crate::{
+  FFIOrder, OpenLimitsResultTag, OpenlimitsSharpError, UserData, book_mid, error_parts, free_c_str, openlimits_error_parts, order_to_ffi,
+  string_to_c_str
+};
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFIEmulatedKind {
+  TrailingStop,
+  Oco,
+  Iceberg,
+  Bracket,
+}
+
+// Status update of an emulated order. `order` has the shape of an exchange order: its id is the
+// emulated id, size and remaining cover the whole emulated order.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIEmulatedOrder {
+  id: u64,
+  kind: FFIEmulatedKind,
+  order: FFIOrder,
+  // Exchange order currently working for it, null if none
+  exchange_order_id: *mut c_char,
+  // Price the local stop fires at, null if none is armed
+  trigger_price: *mut c_char,
+  error_tag: OpenLimitsResultTag,
+  error_message: *mut c_char,
+}
+
+pub type EmulatedOrderCallback = extern fn(order: *const FFIEmulatedOrder, user_data: *mut c_void);
+
+impl FFIEmulatedOrder {
+  unsafe fn free(self) {
+    free_c_str(self.order.id);
+    free_c_str(self.order.market_pair);
+    free_c_str(self.order.client_order_id);
+    free_c_str(self.order.size);
+    free_c_str(self.order.price);
+    free_c_str(self.order.remaining);
+    free_c_str(self.exchange_order_id);
+    free_c_str(self.trigger_price);
+    free_c_str(self.error_message);
+  }
+}
+
+fn opposite(side: &Side) -> Side {
+  match side {
+    Side::Buy => Side::Sell,
+    Side::Sell => Side::Buy,
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Role {
+  Entry,
+  TakeProfit,
+  Clip,
+  Stop,
+}
+
+// Exchange order working for an emulated order
+struct Live {
+  id: String,
+  role: Role,
+  size: Decimal,
+  filled: Decimal,
+}
+
+// Local stop that fires a market order on `side` once a trade crosses `price`. A trailing stop
+// moves `price` along `trail` behind the best trade price seen.
+struct Trigger {
+  side: Side,
+  price: Option<Decimal>,
+  trail: Option<Decimal>,
+  extreme: Option<Decimal>,
+}
+
+impl Trigger {
+  fn stop(side: Side, price: Decimal) -> Self {
+    Trigger {
+      side,
+      price: Some(price),
+      trail: None,
+      extreme: None,
+    }
+  }
+
+  fn trailing(side: Side, trail: Decimal) -> Self {
+    Trigger {
+      side,
+      price: None,
+      trail: Some(trail),
+      extreme: None,
+    }
+  }
+
+  // Returns true if the trade price fires the stop
+  fn update(&mut self, price: Decimal) -> bool {
+    if let Some(trail) = self.trail {
+      match self.side {
+        Side::Sell if self.extreme.map(|extreme| price > extreme).unwrap_or(true) => {
+          self.extreme = Some(price);
+          self.price = Some(price - trail);
+        },
+        Side::Buy if self.extreme.map(|extreme| price < extreme).unwrap_or(true) => {
+          self.extreme = Some(price);
+          self.price = Some(price + trail);
+        },
+        _ => {}
+      }
+    }
+    match (&self.side, self.price) {
+      (Side::Sell, Some(stop)) => price <= stop,
+      (Side::Buy, Some(stop)) => price >= stop,
+      _ => false
+    }
+  }
+}
+
+enum Plan {
+  TrailingStop,
+  // A take profit limit order on the exchange and a local stop, both for what is left
+  Oco,
+  Iceberg {
+    clip: Decimal,
+    price: Decimal,
+  },
+  // An entry limit order, followed by a take profit and a stop on the opposite side once it filled
+  Bracket {
+    take_profit: Decimal,
+    stop: Decimal,
+    entered: bool,
+  },
+}
+
+struct Emulated {
+  id: u64,
+  kind: FFIEmulatedKind,
+  market: String,
+  side: Side,
+  size: Decimal,
+  price: Option<Decimal>,
+  // Filled of the current leg: the entry of a bracket until it is entered, its exit afterwards
+  filled: Decimal,
+  status: OrderStatus,
+  created_at: u64,
+  plan: Plan,
+  live: Option<Live>,
+  trigger: Option<Trigger>,
+  // An exchange request for it is in flight, market data does not fire its stop meanwhile
+  busy: bool,
+  // The stop fired and the take profit is being canceled, its cancel is not reported as a user cancel
+  firing: bool,
+  error: Option<(OpenLimitsResultTag, String)>,
+}
+
+impl Emulated {
+  fn remaining(&self) -> Decimal {
+    std::cmp::max(self.size - self.filled, Decimal::new(0, 0))
+  }
+
+  // Side orders are currently sent on, the exit side once a bracket is entered
+  fn working_side(&self) -> Side {
+    match self.plan {
+      Plan::Bracket { entered: true, .. } => opposite(&self.side),
+      _ => self.side.clone()
+    }
+  }
+
+  fn is_done(&self) -> bool {
+    is_terminal(&self.status)
+  }
+
+  fn fail(&mut self, error: (OpenLimitsResultTag, String)) {
+    self.status = OrderStatus::Rejected;
+    self.trigger = None;
+    self.error = Some(error);
+  }
+
+  fn to_ffi(&self) -> FFIEmulatedOrder {
+    let (error_tag, error_message) = match &self.error {
+      Some((tag, message)) => (*tag, string_to_c_str(message.clone())),
+      None => (OpenLimitsResultTag::Ok, std::ptr::null_mut())
+    };
+    FFIEmulatedOrder {
+      id: self.id,
+      kind: self.kind,
+      order: order_to_ffi(Order {
+        id: self.id.to_string(),
+        market_pair: self.market.clone(),
+        client_order_id: None,
+        created_at: Some(self.created_at),
+        order_type: match self.plan {
+          Plan::TrailingStop => OrderType::StopMarket,
+          _ => OrderType::Limit
+        },
+        side: self.side.clone(),
+        status: self.status.clone(),
+        size: self.size,
+        price: self.price,
+        remaining: Some(self.remaining()),
+      }),
+      exchange_order_id: match &self.live {
+        Some(live) => string_to_c_str(live.id.clone()),
+        None => std::ptr::null_mut()
+      },
+      trigger_price: match self.trigger.as_ref().and_then(|trigger| trigger.price) {
+        Some(price) => string_to_c_str(price.to_string()),
+        None => std::ptr::null_mut()
+      },
+      error_tag,
+      error_message,
+    }
+  }
+}
+
+struct Placement {
+  market: String,
+  side: Side,
+  size: Decimal,
+  // Market order when None
+  price: Option<Decimal>,
+}
+
+enum Action {
+  Place(u64, Role, Placement),
+  Cancel(u64, String, String),
+  // The stop of the emulated order fired, cancel its live order if any and send the stop market order
+  Fire(u64, Option<(String, String)>),
+  Stop,
+}
+
+#[derive(Default)]
+struct EmulatorState {
+  next_id: u64,
+  watched: HashSet<String>,
+  orders: HashMap<u64, Emulated>,
+}
+
+#[derive(Default)]
+struct Changes {
+  actions: Vec<Action>,
+  updated: Vec<u64>,
+}
+
+// Emulates order types the exchanges lack with plain limit and market orders. Stops fire on the
+// trades of watched markets; the exchange orders are sent and polled from a worker thread with
+// its own REST client.
+pub struct OrderEmulator {
+  state: Mutex<EmulatorState>,
+  actions: Mutex<Sender<Action>>,
+  callback: EmulatedOrderCallback,
+  user_data: UserData,
+  // The client's, the worker waits for its budget instead of failing
+  limiter: Arc<RateLimiter>,
+  // The client's, emulated orders of a client in dry run only send simulated orders
+  dry_run: Arc<DryRun>,
+  // The client's, every order sent for an emulated order is checked against its limits
+  risk: Arc<RiskEngine>,
+  // Market pairs for the position limits, fetched by the worker on first use
+  market_rules: Mutex<MarketRules>,
+}
+
+// Exchange reads for the risk checks of the orders the worker sends, made with its REST client
+struct WorkerInputs<'a> {
+  rt: &'a mut tokio::runtime::Runtime,
+  exchange: &'a AnyExchange,
+  limiter: &'a RateLimiter,
+  market_rules: &'a Mutex<MarketRules>,
+}
+
+impl RiskInputs for WorkerInputs<'_> {
+  fn open_orders(&mut self) -> Result<Vec<Order>, OpenlimitsSharpError> {
+    self.limiter.acquire(FFIEndpoint::OpenOrders, 1, self.limiter.deadline()).map_err(OpenlimitsSharpError::RateLimited)?;
+    Ok(self.rt.block_on(self.exchange.get_all_open_orders())?)
+  }
+
+  fn balances(&mut self) -> Result<Vec<Balance>, OpenlimitsSharpError> {
+    self.limiter.acquire(FFIEndpoint::AccountBalances, 1, self.limiter.deadline()).map_err(OpenlimitsSharpError::RateLimited)?;
+    Ok(self.rt.block_on(self.exchange.get_account_balances(None))?)
+  }
+
+  fn mid_price(&mut self, market: &str) -> Result<Option<Decimal>, OpenlimitsSharpError> {
+    self.limiter.acquire(FFIEndpoint::OrderBook, 1, self.limiter.deadline()).map_err(OpenlimitsSharpError::RateLimited)?;
+    let book = self.rt.block_on(self.exchange.order_book(&OrderBookRequest {
+      market_pair: String::from(market)
+    }))?;
+    Ok(book_mid(&book))
+  }
+
+  fn base_asset(&mut self, market: &str) -> Result<Option<String>, OpenlimitsSharpError> {
+    let mut market_rules = self.market_rules.lock().expect("Emulator market rules lock poisoned");
+    if market_rules.needs_refresh(market) {
+      self.limiter.acquire(FFIEndpoint::MarketPairs, 1, self.limiter.deadline()).map_err(OpenlimitsSharpError::RateLimited)?;
+      market_rules.load(self.rt.block_on(self.exchange.retrieve_pairs())?);
+    }
+    Ok(market_rules.pair(market).map(|pair| pair.base.clone()))
+  }
+}
+
+pub type SharedEmulator = Arc<OrderEmulator>;
+
+impl OrderEmulator {
+  pub fn start(init_params: InitAnyExchange, limiter: Arc<RateLimiter>, risk: Arc<RiskEngine>, dry_run: Arc<DryRun>, poll_interval_ms: u64, callback: EmulatedOrderCallback, user_data: UserData) -> Result<SharedEmulator, OpenLimitsError> {
+    let (actions_tx, actions_rx) = channel();
+    let emulator = Arc::new(OrderEmulator {
+      state: Mutex::new(EmulatorState::default()),
+      actions: Mutex::new(actions_tx),
+      callback,
+      user_data,
+      limiter,
+      dry_run,
+      risk,
+      market_rules: Mutex::new(MarketRules::new(FFIValidationMode::Off)),
+    });
+    let (started_tx, started_rx) = channel();
+    let worker = emulator.clone();
+    let poll_interval = Duration::from_millis(std::cmp::max(poll_interval_ms, 1));
+    std::thread::spawn(move || {
+      let mut rt = match tokio::runtime::Builder::new().basic_scheduler().enable_all().build() {
+        Ok(rt) => rt,
+        Err(e) => {
+          started_tx.send(Err(OpenLimitsError::IoError(e))).ok();
+          return;
+        }
+      };
+      let exchange = match rt.block_on(OpenLimits::instantiate(init_params)) {
+        Ok(exchange) => exchange,
+        Err(e) => {
+          started_tx.send(Err(e)).ok();
+          return;
+        }
+      };
+      started_tx.send(Ok(())).ok();
+      worker.run(&mut rt, &exchange, actions_rx, poll_interval);
+    });
+    started_rx.recv().unwrap_or_else(|_| Err(OpenLimitsError::SocketError()))?;
+    Ok(emulator)
+  }
+
+  pub fn stop(&self) {
+    self.actions.lock().expect("Emulator actions lock poisoned").send(Action::Stop).ok();
+  }
+
+  pub fn watch(&self, market: String) {
+    self.state.lock().expect("Emulator lock poisoned").watched.insert(market);
+  }
+
+  // Runs `f` on the state, then sends the actions and reports the orders it changed
+  fn apply<F, R>(&self, f: F) -> R where F: FnOnce(&mut EmulatorState, &mut Changes) -> R {
+    let mut changes = Changes::default();
+    let (result, updates) = {
+      let mut state = self.state.lock().expect("Emulator lock poisoned");
+      let result = f(&mut state, &mut changes);
+      changes.updated.dedup();
+      let updates: Vec<FFIEmulatedOrder> = changes.updated.iter().filter_map(|id| state.orders.get(id)).map(Emulated::to_ffi).collect();
+      state.orders.retain(|_, order| !order.is_done() || order.live.is_some() || order.busy);
+      (result, updates)
+    };
+    let actions = self.actions.lock().expect("Emulator actions lock poisoned");
+    for action in changes.actions {
+      actions.send(action).ok();
+    }
+    drop(actions);
+    for update in updates {
+      (self.callback)(&update, self.user_data.0);
+      unsafe { update.free() };
+    }
+    result
+  }
+
+  fn add(&self, market: String, side: Side, size: Decimal, price: Option<Decimal>, kind: FFIEmulatedKind, plan: Plan, trigger: Option<Trigger>) -> Result<u64, String> {
+    if size <= Decimal::new(0, 0) {
+      return Err(String::from("Size must be greater than 0"));
+    }
+    self.apply(|state, changes| {
+      if !state.watched.contains(&market) {
+        return Err(format!("Market {} is not watched by the emulator", market));
+      }
+      state.next_id += 1;
+      let id = state.next_id;
+      let mut order = Emulated {
+        id,
+        kind,
+        market,
+        side,
+        size,
+        price,
+        filled: Decimal::new(0, 0),
+        status: OrderStatus::New,
+        created_at: chrono::Utc::now().timestamp_millis() as u64,
+        plan,
+        live: None,
+        trigger,
+        busy: false,
+        firing: false,
+        error: None,
+      };
+      match order.plan {
+        Plan::TrailingStop => order.status = OrderStatus::Open,
+        Plan::Oco => place(&mut order, Role::TakeProfit, size, price, changes),
+        Plan::Iceberg { clip, price } => place(&mut order, Role::Clip, std::cmp::min(clip, size), Some(price), changes),
+        Plan::Bracket { .. } => place(&mut order, Role::Entry, size, price, changes),
+      };
+      changes.updated.push(id);
+      state.orders.insert(id, order);
+      Ok(id)
+    })
+  }
+
+  // Sells `size` at market once the price fell `trail` below its high since now, or buys once it rose `trail` above its low
+  pub fn trailing_stop(&self, market: String, side: Side, size: Decimal, trail: Decimal) -> Result<u64, String> {
+    if trail <= Decimal::new(0, 0) {
+      return Err(String::from("Trail must be greater than 0"));
+    }
+    let trigger = Trigger::trailing(side.clone(), trail);
+    self.add(market, side, size, None, FFIEmulatedKind::TrailingStop, Plan::TrailingStop, Some(trigger))
+  }
+
+  pub fn oco(&self, market: String, side: Side, size: Decimal, limit_price: Decimal, stop_price: Decimal) -> Result<u64, String> {
+    let valid = match side {
+      Side::Sell => limit_price > stop_price,
+      Side::Buy => limit_price < stop_price,
+    };
+    if !valid {
+      return Err(String::from("The limit price must be on the profitable side of the stop price"));
+    }
+    let trigger = Trigger::stop(side.clone(), stop_price);
+    self.add(market, side, size, Some(limit_price), FFIEmulatedKind::Oco, Plan::Oco, Some(trigger))
+  }
+
+  pub fn iceberg(&self, market: String, side: Side, size: Decimal, clip: Decimal, price: Decimal) -> Result<u64, String> {
+    if clip <= Decimal::new(0, 0) {
+      return Err(String::from("Clip size must be greater than 0"));
+    }
+    self.add(market, side, size, Some(price), FFIEmulatedKind::Iceberg, Plan::Iceberg { clip, price }, None)
+  }
+
+  pub fn bracket(&self, market: String, side: Side, size: Decimal, entry_price: Decimal, take_profit: Decimal, stop: Decimal) -> Result<u64, String> {
+    let valid = match side {
+      Side::Buy => take_profit > entry_price && entry_price > stop,
+      Side::Sell => take_profit < entry_price && entry_price < stop,
+    };
+    if !valid {
+      return Err(String::from("The entry price must be between the stop and take profit prices"));
+    }
+    let plan = Plan::Bracket {
+      take_profit,
+      stop,
+      entered: false,
+    };
+    self.add(market, side, size, Some(entry_price), FFIEmulatedKind::Bracket, plan, None)
+  }
+
+  pub fn cancel(&self, id: u64) -> Result<(), String> {
+    self.apply(|state, changes| {
+      let order = match state.orders.get_mut(&id) {
+        Some(order) if !order.is_done() => order,
+        _ => return Err(format!("Unknown or finished emulated order {}", id))
+      };
+      order.status = OrderStatus::Canceled;
+      order.trigger = None;
+      if let Some(live) = &order.live {
+        changes.actions.push(Action::Cancel(id, live.id.clone(), order.market.clone()));
+      }
+      changes.updated.push(id);
+      Ok(())
+    })
+  }
+
+  pub fn on_trades(&self, market: &str, trades: &[Trade]) {
+    self.apply(|state, changes| {
+      for order in state.orders.values_mut().filter(|order| order.market == market && !order.busy && !order.is_done()) {
+        let trigger = match &mut order.trigger {
+          Some(trigger) => trigger,
+          None => continue
+        };
+        if trades.iter().any(|trade| trigger.update(trade.price)) {
+          order.trigger = None;
+          order.busy = true;
+          order.firing = true;
+          let cancel = order.live.as_ref().map(|live| (live.id.clone(), order.market.clone()));
+          changes.actions.push(Action::Fire(order.id, cancel));
+          changes.updated.push(order.id);
+        }
+      }
+    })
+  }
+
+  // Latest state of an exchange order working for an emulated order
+  fn on_child(&self, id: u64, child: &Order) {
+    self.apply(|state, changes| {
+      let order = match state.orders.get_mut(&id) {
+        Some(order) => order,
+        None => return
+      };
+      let live = match &mut order.live {
+        Some(live) if live.id == child.id => live,
+        _ => return
+      };
+      let filled = match child.status {
+        OrderStatus::Filled => live.size,
+        _ => child.remaining.map(|remaining| live.size - remaining).unwrap_or(live.filled)
+      };
+      if filled > live.filled {
+        order.filled += filled - live.filled;
+        live.filled = filled;
+        if let OrderStatus::Open | OrderStatus::New = order.status {
+          order.status = OrderStatus::PartiallyFilled;
+        }
+        changes.updated.push(id);
+      }
+      if !is_terminal(&child.status) {
+        return;
+      }
+      let role = live.role;
+      order.live = None;
+      changes.updated.push(id);
+      if order.firing {
+        return;
+      }
+      if order.is_done() {
+        return;
+      }
+      let remaining = order.remaining();
+      match (role, &child.status) {
+        (Role::Stop, OrderStatus::Filled) => order.status = OrderStatus::Filled,
+        (Role::Stop, status) => order.status = status.clone(),
+        (_, _) if remaining.is_zero() && !matches!(role, Role::Entry) => {
+          order.status = OrderStatus::Filled;
+          order.trigger = None;
+        },
+        (Role::Clip, OrderStatus::Filled) => {
+          let (clip, price) = match order.plan {
+            Plan::Iceberg { clip, price } => (clip, price),
+            _ => return
+          };
+          place(order, Role::Clip, std::cmp::min(clip, remaining), Some(price), changes);
+        },
+        (Role::Entry, _) if !order.filled.is_zero() => {
+          let (take_profit, stop) = match &mut order.plan {
+            Plan::Bracket { take_profit, stop, entered } => {
+              *entered = true;
+              (*take_profit, *stop)
+            },
+            _ => return
+          };
+          order.size = order.filled;
+          order.filled = Decimal::new(0, 0);
+          order.status = OrderStatus::Active;
+          order.trigger = Some(Trigger::stop(opposite(&order.side), stop));
+          let size = order.size;
+          place(order, Role::TakeProfit, size, Some(take_profit), changes);
+        },
+        (_, status) => {
+          // Canceled, rejected or expired on the exchange
+          order.status = status.clone();
+          order.trigger = None;
+        }
+      };
+    })
+  }
+
+  // The stop fired and its live order is settled, returns the stop market order to send
+  fn fired(&self, id: u64) -> Option<Placement> {
+    self.apply(|state, changes| {
+      let order = state.orders.get_mut(&id)?;
+      order.firing = false;
+      changes.updated.push(id);
+      if order.is_done() {
+        order.busy = false;
+        return None;
+      }
+      if order.remaining().is_zero() {
+        order.busy = false;
+        order.status = OrderStatus::Filled;
+        return None;
+      }
+      Some(Placement {
+        market: order.market.clone(),
+        side: order.working_side(),
+        size: order.remaining(),
+        price: None,
+      })
+    })
+  }
+
+  fn on_placed(&self, id: u64, role: Role, size: Decimal, placed: Result<Order, (OpenLimitsResultTag, String)>) {
+    self.apply(|state, changes| {
+      let order = match state.orders.get_mut(&id) {
+        Some(order) => order,
+        None => return
+      };
+      order.busy = false;
+      changes.updated.push(id);
+      match placed {
+        Ok(child) => {
+          if order.is_done() {
+            // Canceled while the placement was in flight
+            changes.actions.push(Action::Cancel(id, child.id.clone(), order.market.clone()));
+          } else if let OrderStatus::New = order.status {
+            order.status = OrderStatus::Open;
+          }
+          order.live = Some(Live {
+            id: child.id,
+            role,
+            size,
+            filled: Decimal::new(0, 0),
+          });
+        },
+        Err(error) => order.fail(error)
+      };
+    })
+  }
+
+  fn live_orders(&self) -> Vec<(u64, String, String)> {
+    let state = self.state.lock().expect("Emulator lock poisoned");
+    state.orders.values()
+      .filter_map(|order| order.live.as_ref().map(|live| (order.id, live.id.clone(), order.market.clone())))
+      .collect()
+  }
+
+  fn refresh(&self, rt: &mut tokio::runtime::Runtime, exchange: &AnyExchange, id: u64, order_id: String, market: String) {
+    let req = GetOrderRequest {
+      id: order_id,
+      market_pair: Some(market)
+    };
+    let child = match is_simulated(&req.id) {
+      true => self.dry_run.get(&req.id),
+      // Reads that fail are made again on the next poll
+      false => match self.limiter.acquire(FFIEndpoint::GetOrder, 1, self.limiter.deadline()) {
+        Ok(()) => rt.block_on(exchange.get_order(&req)).ok(),
+        Err(_) => None
+      }
+    };
+    if let Some(child) = child {
+      self.on_child(id, &child);
+    }
+  }
+
+  // Cancels the live order of emulated order `id` and reads back what became of it
+  fn cancel_live(&self, rt: &mut tokio::runtime::Runtime, exchange: &AnyExchange, id: u64, order_id: String, market: String) {
+    if self.dry_run.simulates_cancel(&order_id) {
+      self.dry_run.cancel(&order_id);
+    } else {
+      let req = CancelOrderRequest {
+        id: order_id.clone(),
+        market_pair: Some(market.clone())
+      };
+      let limited = self.limiter.acquire(FFIEndpoint::CancelOrder, 1, self.limiter.deadline());
+      if limited.is_ok() && rt.block_on(exchange.cancel_order(&req)).is_ok() {
+        self.risk.canceled(&order_id);
+      }
+    }
+    self.refresh(rt, exchange, id, order_id, market);
+  }
+
+  fn send(&self, rt: &mut tokio::runtime::Runtime, exchange: &AnyExchange, id: u64, role: Role, placement: Placement) {
+    let side = ffi_side(&placement.side);
+    // The worker sends one order at a time, so the emulated order's id is unique among the reservations
+    let key = format!("emulated-{}", id);
+    let mut inputs = WorkerInputs {
+      rt: &mut *rt,
+      exchange,
+      limiter: &self.limiter,
+      market_rules: &self.market_rules,
+    };
+    if let Err(e) = self.risk.check(&mut inputs, &key, &placement.market, side, placement.size, placement.price, None) {
+      return self.on_placed(id, role, placement.size, Err(error_parts(&e)));
+    }
+    let new_order = match placement.price {
+      Some(price) => NewOrder::Limit(side, OpenLimitOrderRequest {
+        market_pair: placement.market,
+        size: placement.size,
+        price,
+        time_in_force: TimeInForce::GoodTillCancelled,
+        post_only: false
+      }),
+      None => NewOrder::Market(side, OpenMarketOrderRequest {
+        market_pair: placement.market,
+        size: placement.size
+      })
+    };
+    let placed = match self.dry_run.enabled() {
+      true => Ok(self.dry_run.place(&new_order, None)),
+      false => match self.limiter.acquire(FFIEndpoint::PlaceOrder, 1, self.limiter.deadline()) {
+        Ok(()) => rt.block_on(new_order.send(exchange)).map_err(|e| openlimits_error_parts(&e)),
+        Err(message) => Err((OpenLimitsResultTag::RateLimited, message))
+      }
+    };
+    self.risk.sent(&key, placed.as_ref().ok());
+    self.on_placed(id, role, placement.size, placed);
+  }
+
+  fn run(&self, rt: &mut tokio::runtime::Runtime, exchange: &AnyExchange, actions: Receiver<Action>, poll_interval: Duration) {
+    let mut next_poll = Instant::now() + poll_interval;
+    loop {
+      let action = match actions.recv_timeout(next_poll.saturating_duration_since(Instant::now())) {
+        Ok(Action::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+        Ok(action) => Some(action),
+        Err(RecvTimeoutError::Timeout) => None
+      };
+      match action {
+        Some(Action::Place(id, role, placement)) => self.send(rt, exchange, id, role, placement),
+        Some(Action::Cancel(id, order_id, market)) => self.cancel_live(rt, exchange, id, order_id, market),
+        Some(Action::Fire(id, cancel)) => {
+          if let Some((order_id, market)) = cancel {
+            self.cancel_live(rt, exchange, id, order_id, market);
+          }
+          if let Some(placement) = self.fired(id) {
+            self.send(rt, exchange, id, Role::Stop, placement);
+          }
+        },
+        Some(Action::Stop) => return,
+        None => {}
+      };
+      if Instant::now() >= next_poll {
+        for (id, order_id, market) in self.live_orders() {
+          self.refresh(rt, exchange, id, order_id, market);
+        }
+        next_poll = Instant::now() + poll_interval;
+      }
+    }
+  }
+}
+
+fn place(order: &mut Emulated, role: Role, size: Decimal, price: Option<Decimal>, changes: &mut Changes) {
+  order.busy = true;
+  changes.actions.push(Action::Place(order.id, role, Placement {
+    market: order.market.clone(),
+    side: order.working_side(),
+    size,
+    price,
+  }));
+}
+
+// Feeds the trades of a subscription to the emulator's stops
+#[derive(Clone)]
+pub struct EmulatorSink {
+  pub emulator: SharedEmulator,
+  pub market: String,
+}
+
+impl EmulatorSink {
+  pub fn push(&self, trades: &[Trade]) {
+    self.emulator.on_trades(&self.market, trades);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::FromStr;
+  use super::*;
+  use crate::FFIExchangeKind;
+
+  fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+  }
+
+  extern fn ignore_update(_order: *const FFIEmulatedOrder, _user_data: *mut c_void) {}
+
+  // An emulator without a worker, the actions it would send are left on the returned receiver
+  fn emulator() -> (OrderEmulator, Receiver<Action>) {
+    let (actions_tx, actions_rx) = channel();
+    let emulator = OrderEmulator {
+      state: Mutex::new(EmulatorState::default()),
+      actions: Mutex::new(actions_tx),
+      callback: ignore_update,
+      user_data: UserData(std::ptr::null_mut()),
+      limiter: Arc::new(RateLimiter::new(FFIExchangeKind::Binance)),
+      dry_run: Arc::new(DryRun::default()),
+      risk: Arc::new(RiskEngine::default()),
+      market_rules: Mutex::new(MarketRules::new(FFIValidationMode::Off)),
+    };
+    emulator.watch(String::from("btcusd"));
+    (emulator, actions_rx)
+  }
+
+  fn child(id: &str, status: OrderStatus, remaining: &str) -> Order {
+    Order {
+      id: String::from(id),
+      market_pair: String::from("btcusd"),
+      client_order_id: None,
+      created_at: None,
+      order_type: OrderType::Limit,
+      side: Side::Buy,
+      status,
+      size: dec("0"),
+      price: None,
+      remaining: Some(dec(remaining)),
+    }
+  }
+
+  fn trade(price: &str) -> Trade {
+    Trade {
+      id: String::from("1"),
+      buyer_order_id: None,
+      seller_order_id: None,
+      market_pair: String::from("btcusd"),
+      price: dec(price),
+      qty: dec("1"),
+      fees: None,
+      side: Side::Buy,
+      liquidity: None,
+      created_at: 0,
+    }
+  }
+
+  // The placement of the only action sent, with its role
+  fn placed(actions: &Receiver<Action>) -> (Role, Placement) {
+    let mut sent: Vec<Action> = actions.try_iter().collect();
+    assert_eq!(sent.len(), 1);
+    match sent.remove(0) {
+      Action::Place(_, role, placement) => (role, placement),
+      _ => panic!("Expected a placement")
+    }
+  }
+
+  fn with_order<R>(emulator: &OrderEmulator, id: u64, f: impl FnOnce(&Emulated) -> R) -> R {
+    f(emulator.state.lock().unwrap().orders.get(&id).expect("Order is gone"))
+  }
+
+  #[test]
+  fn stop_fires_once_a_trade_crosses_its_price() {
+    let mut sell = Trigger::stop(Side::Sell, dec("100"));
+    assert!(!sell.update(dec("101")));
+    assert!(sell.update(dec("100")));
+    let mut buy = Trigger::stop(Side::Buy, dec("100"));
+    assert!(!buy.update(dec("99")));
+    assert!(buy.update(dec("100.5")));
+  }
+
+  #[test]
+  fn trailing_sell_stop_follows_the_high() {
+    let mut trigger = Trigger::trailing(Side::Sell, dec("5"));
+    assert!(!trigger.update(dec("100")));
+    assert_eq!(trigger.price, Some(dec("95")));
+    assert!(!trigger.update(dec("110")));
+    assert_eq!(trigger.price, Some(dec("105")));
+    // A lower trade does not move the stop back down
+    assert!(!trigger.update(dec("107")));
+    assert_eq!(trigger.price, Some(dec("105")));
+    assert!(trigger.update(dec("105")));
+  }
+
+  #[test]
+  fn trailing_buy_stop_follows_the_low() {
+    let mut trigger = Trigger::trailing(Side::Buy, dec("5"));
+    assert!(!trigger.update(dec("100")));
+    assert_eq!(trigger.price, Some(dec("105")));
+    assert!(!trigger.update(dec("90")));
+    assert_eq!(trigger.price, Some(dec("95")));
+    assert!(!trigger.update(dec("94")));
+    assert!(trigger.update(dec("95")));
+  }
+
+  #[test]
+  fn child_fills_are_counted_once_and_iceberg_clips_again() {
+    let (emulator, actions) = emulator();
+    let id = emulator.iceberg(String::from("btcusd"), Side::Buy, dec("5"), dec("2"), dec("100")).unwrap();
+    let (role, placement) = placed(&actions);
+    assert!(matches!(role, Role::Clip));
+    assert_eq!((placement.size, placement.price), (dec("2"), Some(dec("100"))));
+    emulator.on_placed(id, Role::Clip, dec("2"), Ok(child("a", OrderStatus::Open, "2")));
+
+    emulator.on_child(id, &child("a", OrderStatus::PartiallyFilled, "1.5"));
+    emulator.on_child(id, &child("a", OrderStatus::PartiallyFilled, "1.5"));
+    // Updates of another exchange order are ignored
+    emulator.on_child(id, &child("b", OrderStatus::Filled, "0"));
+    with_order(&emulator, id, |order| {
+      assert_eq!(order.filled, dec("0.5"));
+      assert!(matches!(order.status, OrderStatus::PartiallyFilled));
+    });
+    assert_eq!(actions.try_iter().count(), 0);
+
+    // A filled clip counts its whole size and the next clip is sent
+    emulator.on_child(id, &child("a", OrderStatus::Filled, "0"));
+    with_order(&emulator, id, |order| {
+      assert_eq!(order.filled, dec("2"));
+      assert!(order.live.is_none());
+      assert!(order.busy);
+    });
+    let (_, placement) = placed(&actions);
+    assert_eq!(placement.size, dec("2"));
+
+    emulator.on_placed(id, Role::Clip, dec("2"), Ok(child("c", OrderStatus::Open, "2")));
+    emulator.on_child(id, &child("c", OrderStatus::Filled, "0"));
+    // The last clip is only what is left
+    let (_, placement) = placed(&actions);
+    assert_eq!(placement.size, dec("1"));
+
+    emulator.on_placed(id, Role::Clip, dec("1"), Ok(child("d", OrderStatus::Open, "1")));
+    emulator.on_child(id, &child("d", OrderStatus::Filled, "0"));
+    assert_eq!(actions.try_iter().count(), 0);
+    // Done and without a live order, the emulator forgets it
+    assert!(emulator.state.lock().unwrap().orders.get(&id).is_none());
+  }
+
+  #[test]
+  fn bracket_exits_what_the_entry_filled() {
+    let (emulator, actions) = emulator();
+    let id = emulator.bracket(String::from("btcusd"), Side::Buy, dec("2"), dec("100"), dec("110"), dec("90")).unwrap();
+    let (role, placement) = placed(&actions);
+    assert!(matches!(role, Role::Entry));
+    assert!(matches!(placement.side, Side::Buy));
+    emulator.on_placed(id, Role::Entry, dec("2"), Ok(child("entry", OrderStatus::Open, "2")));
+
+    emulator.on_child(id, &child("entry", OrderStatus::PartiallyFilled, "1"));
+    emulator.on_child(id, &child("entry", OrderStatus::Canceled, "1"));
+    with_order(&emulator, id, |order| {
+      assert!(matches!(order.plan, Plan::Bracket { entered: true, .. }));
+      assert!(matches!(order.status, OrderStatus::Active));
+      assert_eq!((order.size, order.filled), (dec("1"), dec("0")));
+      assert!(matches!(order.working_side(), Side::Sell));
+      let trigger = order.trigger.as_ref().expect("No stop armed");
+      assert!(matches!(trigger.side, Side::Sell));
+      assert_eq!(trigger.price, Some(dec("90")));
+    });
+    let (role, placement) = placed(&actions);
+    assert!(matches!(role, Role::TakeProfit));
+    assert!(matches!(placement.side, Side::Sell));
+    assert_eq!((placement.size, placement.price), (dec("1"), Some(dec("110"))));
+  }
+
+  #[test]
+  fn bracket_entry_canceled_unfilled_is_canceled() {
+    let (emulator, actions) = emulator();
+    let id = emulator.bracket(String::from("btcusd"), Side::Sell, dec("2"), dec("100"), dec("90"), dec("110")).unwrap();
+    placed(&actions);
+    emulator.on_placed(id, Role::Entry, dec("2"), Ok(child("entry", OrderStatus::Open, "2")));
+    emulator.on_child(id, &child("entry", OrderStatus::Canceled, "2"));
+    assert_eq!(actions.try_iter().count(), 0);
+    assert!(emulator.state.lock().unwrap().orders.get(&id).is_none());
+  }
+
+  #[test]
+  fn fired_stop_sends_what_the_take_profit_left() {
+    let (emulator, actions) = emulator();
+    let id = emulator.oco(String::from("btcusd"), Side::Sell, dec("2"), dec("110"), dec("90")).unwrap();
+    placed(&actions);
+    emulator.on_placed(id, Role::TakeProfit, dec("2"), Ok(child("tp", OrderStatus::Open, "2")));
+    emulator.on_child(id, &child("tp", OrderStatus::PartiallyFilled, "1.5"));
+
+    emulator.on_trades("btcusd", &[trade("95"), trade("89")]);
+    let sent: Vec<Action> = actions.try_iter().collect();
+    assert!(matches!(sent.as_slice(), [Action::Fire(fired, Some((live, _)))] if *fired == id && live == "tp"));
+    // Busy while firing, later trades are ignored
+    emulator.on_trades("btcusd", &[trade("80")]);
+    assert_eq!(actions.try_iter().count(), 0);
+
+    // Its cancel is not reported as a user cancel
+    emulator.on_child(id, &child("tp", OrderStatus::Canceled, "1.5"));
+    with_order(&emulator, id, |order| assert!(!order.is_done()));
+    let placement = emulator.fired(id).expect("No stop order to send");
+    assert!(matches!(placement.side, Side::Sell));
+    assert_eq!((placement.size, placement.price), (dec("1.5"), None));
+  }
+
+  #[test]
+  fn fired_after_the_take_profit_filled_sends_nothing() {
+    let (emulator, actions) = emulator();
+    let id = emulator.oco(String::from("btcusd"), Side::Buy, dec("2"), dec("90"), dec("110")).unwrap();
+    placed(&actions);
+    emulator.on_placed(id, Role::TakeProfit, dec("2"), Ok(child("tp", OrderStatus::Open, "2")));
+    emulator.on_trades("btcusd", &[trade("111")]);
+    assert_eq!(actions.try_iter().count(), 1);
+    // The take profit filled before its cancel got through
+    emulator.on_child(id, &child("tp", OrderStatus::Filled, "0"));
+    assert!(emulator.fired(id).is_none());
+    assert!(emulator.state.lock().unwrap().orders.get(&id).is_none());
+  }
+}
@@ -0,0 +1,783 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{fence, AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use crossbeam_queue::ArrayQueue;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::*;
+use openlimits::model::{AskBid, Balance, Candle, Order, OrderBookResponse, Trade};
+use lazy_static::lazy_static;
+use crate::{
+  FFIAskBid, FFIBalance, FFICandle, FFIOrder, FFIDeliveryMode, FFIDeliveryPolicy, FFIStalePolicy, FFISubscription, FFISubscriptionKind, FFITicker, FFITrade,
+  FFIWsEventKind, OpenLimitsResultTag, UserData, free_c_str, order_to_ffi, string_to_c_str, to_ffi_ask_bid, to_ffi_balance, to_ffi_candle, to_ffi_trade
+};
+use crate::aggregates::{TickerState, price_change_percent};
+use crate::analytics::{BookAnalytics, FFIBookAnalytics, FFIDepthAtDistance};
+use crate::stats::{FFITradeStats, TradeStats};
+
+lazy_static! {
+  // Monotonic receive timestamps count from the first time any of them is taken
+  static ref MONOTONIC_BASE: Instant = Instant::now();
+}
+
+const LATENCY_SAMPLES: usize = 1024;
+
+// When the subscription thread received a message, before any conflation or queueing
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ReceiveTime {
+  pub monotonic_ns: u64,
+  pub wall_ms: u64,
+}
+
+impl ReceiveTime {
+  pub fn now() -> Self {
+    ReceiveTime {
+      monotonic_ns: MONOTONIC_BASE.elapsed().as_nanos() as u64,
+      wall_ms: chrono::Utc::now().timestamp_millis() as u64,
+    }
+  }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFILatencyStats {
+  samples: u64,
+  last_ms: i64,
+  min_ms: i64,
+  max_ms: i64,
+  mean_ms: f64,
+  p50_ms: i64,
+  p99_ms: i64,
+}
+
+// Exchange to local receive latency of the last LATENCY_SAMPLES messages carrying an exchange time.
+// Negative values mean the exchange clock is ahead of ours.
+#[derive(Default)]
+struct LatencyWindow {
+  samples: VecDeque<i64>,
+}
+
+impl LatencyWindow {
+  fn record(&mut self, latency_ms: i64) {
+    if self.samples.len() == LATENCY_SAMPLES {
+      self.samples.pop_front();
+    }
+    self.samples.push_back(latency_ms);
+  }
+
+  fn stats(&self) -> FFILatencyStats {
+    let last_ms = match self.samples.back() {
+      Some(last) => *last,
+      None => return FFILatencyStats::default()
+    };
+    let mut sorted: Vec<i64> = self.samples.iter().cloned().collect();
+    sorted.sort_unstable();
+    let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+    FFILatencyStats {
+      samples: sorted.len() as u64,
+      last_ms,
+      min_ms: sorted[0],
+      max_ms: sorted[sorted.len() - 1],
+      mean_ms: sorted.iter().sum::<i64>() as f64 / sorted.len() as f64,
+      p50_ms: percentile(50),
+      p99_ms: percentile(99),
+    }
+  }
+}
+
+// The subscription an event was produced for. Shared by every event of that subscription.
+pub struct EventSource {
+  pub id: u64,
+  pub kind: FFISubscriptionKind,
+  pub market: CString,
+  pub user_data: UserData,
+  pub delivery: FFIDeliveryPolicy,
+  next_seq: AtomicU64,
+  // Queued events below this sequence number were superseded under DropOldest
+  min_live_seq: AtomicU64,
+  dropped: AtomicU64,
+  conflated: AtomicU64,
+  latency: Mutex<LatencyWindow>,
+  // Bumped on every resubscribe, callbacks of older subscribe calls go quiet
+  generation: AtomicU64,
+  stale_policy: Mutex<Option<FFIStalePolicy>>,
+  // Monotonic receive time of the last market data, and of the last stale report
+  last_message_ns: AtomicU64,
+  last_stale_ns: AtomicU64,
+  stale: AtomicBool,
+  trade_stats: Mutex<Option<TradeStats>>,
+  book_analytics: Mutex<Option<BookAnalytics>>,
+}
+
+impl EventSource {
+  pub fn new(id: u64, kind: FFISubscriptionKind, market: CString, user_data: UserData, delivery: FFIDeliveryPolicy) -> Self {
+    EventSource {
+      id,
+      kind,
+      market,
+      user_data,
+      delivery,
+      next_seq: AtomicU64::new(0),
+      min_live_seq: AtomicU64::new(0),
+      dropped: AtomicU64::new(0),
+      conflated: AtomicU64::new(0),
+      latency: Mutex::new(LatencyWindow::default()),
+      generation: AtomicU64::new(0),
+      stale_policy: Mutex::new(None),
+      last_message_ns: AtomicU64::new(ReceiveTime::now().monotonic_ns),
+      last_stale_ns: AtomicU64::new(0),
+      stale: AtomicBool::new(false),
+      trade_stats: Mutex::new(None),
+      book_analytics: Mutex::new(None),
+    }
+  }
+
+  // Numbers the next event, retiring the oldest ones still queued when DropOldest is over its limit
+  pub fn admit(&self) -> u64 {
+    let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+    if let FFIDeliveryMode::DropOldest = self.delivery.mode {
+      let max_pending = std::cmp::max(self.delivery.max_pending, 1);
+      if seq >= max_pending {
+        self.min_live_seq.fetch_max(seq + 1 - max_pending, Ordering::SeqCst);
+      }
+    }
+    seq
+  }
+
+  fn is_live(&self, seq: u64) -> bool {
+    seq >= self.min_live_seq.load(Ordering::SeqCst)
+  }
+
+  pub fn add_conflated(&self, count: u64) {
+    self.conflated.fetch_add(count, Ordering::SeqCst);
+  }
+
+  pub fn record_latency(&self, received: ReceiveTime, payload: &EventPayload) {
+    let exchange_time = payload.exchange_time();
+    if exchange_time == 0 {
+      return;
+    }
+    self.latency.lock().expect("Latency lock poisoned").record(received.wall_ms as i64 - exchange_time as i64);
+  }
+
+  pub fn latency_stats(&self) -> FFILatencyStats {
+    self.latency.lock().expect("Latency lock poisoned").stats()
+  }
+
+  pub fn next_generation(&self) -> u64 {
+    self.generation.fetch_add(1, Ordering::SeqCst) + 1
+  }
+
+  pub fn is_generation(&self, generation: u64) -> bool {
+    self.generation.load(Ordering::SeqCst) == generation
+  }
+
+  pub fn set_stale_policy(&self, policy: Option<FFIStalePolicy>, now: ReceiveTime) {
+    *self.stale_policy.lock().expect("Stale policy lock poisoned") = policy;
+    self.last_message_ns.store(now.monotonic_ns, Ordering::SeqCst);
+    self.stale.store(false, Ordering::SeqCst);
+  }
+
+  // Records market data. Returns how long the feed was silent if it had been reported stale.
+  pub fn touch(&self, received: ReceiveTime) -> Option<u64> {
+    let last = self.last_message_ns.swap(received.monotonic_ns, Ordering::SeqCst);
+    match self.stale.swap(false, Ordering::SeqCst) {
+      true => Some(received.monotonic_ns.saturating_sub(last) / 1_000_000),
+      false => None
+    }
+  }
+
+  // Returns the policy and the silence so far in ms once the feed has been silent for the threshold.
+  // While it stays silent this repeats every threshold, so resubscribe and reconnect are retried.
+  pub fn check_stale(&self, now: ReceiveTime) -> Option<(FFIStalePolicy, u64)> {
+    let policy = (*self.stale_policy.lock().expect("Stale policy lock poisoned"))?;
+    let last_message = self.last_message_ns.load(Ordering::SeqCst);
+    let since = std::cmp::max(last_message, self.last_stale_ns.load(Ordering::SeqCst));
+    if now.monotonic_ns.saturating_sub(since) < policy.threshold_ms.saturating_mul(1_000_000) {
+      return None;
+    }
+    self.last_stale_ns.store(now.monotonic_ns, Ordering::SeqCst);
+    self.stale.store(true, Ordering::SeqCst);
+    Some((policy, now.monotonic_ns.saturating_sub(last_message) / 1_000_000))
+  }
+
+  pub fn set_trade_stats(&self, stats: Option<TradeStats>) {
+    *self.trade_stats.lock().expect("Trade stats lock poisoned") = stats;
+  }
+
+  pub fn record_trade_stats(&self, received: ReceiveTime, trades: &[Trade]) {
+    if let Some(stats) = self.trade_stats.lock().expect("Trade stats lock poisoned").as_mut() {
+      stats.record(received.wall_ms, trades);
+    }
+  }
+
+  // None when trade statistics are not enabled
+  pub fn trade_stats(&self, now: ReceiveTime) -> Option<Vec<FFITradeStats>> {
+    self.trade_stats.lock().expect("Trade stats lock poisoned").as_mut().map(|stats| stats.stats(now.wall_ms))
+  }
+
+  pub fn due_trade_stats(&self, now: ReceiveTime) -> Option<Vec<FFITradeStats>> {
+    self.trade_stats.lock().expect("Trade stats lock poisoned").as_mut()?.due(now.wall_ms)
+  }
+
+  pub fn set_book_analytics(&self, analytics: Option<BookAnalytics>) {
+    *self.book_analytics.lock().expect("Book analytics lock poisoned") = analytics;
+  }
+
+  // Returns the analytics to push when they are pushed on every update
+  pub fn apply_book_analytics(&self, snapshot: bool, resp: &OrderBookResponse) -> Option<(FFIBookAnalytics, Vec<FFIDepthAtDistance>)> {
+    self.book_analytics.lock().expect("Book analytics lock poisoned").as_mut()?.apply(snapshot, resp)
+  }
+
+  // None when book analytics are not enabled
+  pub fn book_analytics(&self) -> Option<(FFIBookAnalytics, Vec<FFIDepthAtDistance>)> {
+    self.book_analytics.lock().expect("Book analytics lock poisoned").as_ref().map(|analytics| (analytics.analytics(), analytics.depth()))
+  }
+
+  pub fn due_book_analytics(&self, now: ReceiveTime) -> Option<(FFIBookAnalytics, Vec<FFIDepthAtDistance>)> {
+    self.book_analytics.lock().expect("Book analytics lock poisoned").as_mut()?.due(now.wall_ms)
+  }
+}
+
+pub enum EventPayload {
+  Empty,
+  OrderBook(OrderBookResponse),
+  Trades(Vec<Trade>),
+  // Candle and the time of the last trade in it
+  Candle(Candle, u64),
+  Ticker(TickerState),
+  Orders(Vec<Order>),
+  Balances(Vec<Balance>),
+  // How long the feed has been, or was, silent in ms
+  Stale(u64),
+  // One entry per window
+  TradeStats(Vec<FFITradeStats>),
+  // Analytics and one depth entry per distance
+  BookAnalytics(FFIBookAnalytics, Vec<FFIDepthAtDistance>),
+  Error(OpenLimitsResultTag, String),
+}
+
+impl EventPayload {
+  // Exchange provided time of the latest update in the payload, 0 when the exchange sends none
+  pub fn exchange_time(&self) -> u64 {
+    match self {
+      EventPayload::Trades(trades) => trades.iter().map(|trade| trade.created_at).max().unwrap_or_default(),
+      EventPayload::Candle(_, last_trade_time) => *last_trade_time,
+      EventPayload::Ticker(ticker) => ticker.time,
+      _ => 0
+    }
+  }
+}
+
+// A stream event owned by rust until it is handed out as an FFIEvent
+pub struct Event {
+  pub kind: FFIWsEventKind,
+  pub source: Arc<EventSource>,
+  pub payload: EventPayload,
+  pub seq: u64,
+  // Number of updates merged into this one by conflation
+  pub conflated: u64,
+  pub received: ReceiveTime,
+}
+
+// Every pointer in an FFIEvent is owned by the event and released by `free_events`,
+// or right after the callback returns for events delivered through a subscription callback.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIEvent {
+  kind: FFIWsEventKind,
+  subscription: FFISubscription,
+  bids: *mut FFIAskBid,
+  bids_len: u64,
+  asks: *mut FFIAskBid,
+  asks_len: u64,
+  trades: *mut FFITrade,
+  trades_len: u64,
+  orders: *mut FFIOrder,
+  orders_len: u64,
+  balances: *mut FFIBalance,
+  balances_len: u64,
+  trade_stats: *mut FFITradeStats,
+  trade_stats_len: u64,
+  depth: *mut FFIDepthAtDistance,
+  depth_len: u64,
+  last_update_id: u64,
+  update_id: u64,
+  candle: FFICandle,
+  ticker: FFITicker,
+  book_analytics: FFIBookAnalytics,
+  error_tag: OpenLimitsResultTag,
+  error_message: *mut c_char,
+  silent_ms: u64,
+  dropped: u64,
+  conflated: u64,
+  total_dropped: u64,
+  total_conflated: u64,
+  received_monotonic_ns: u64,
+  received_at: u64,
+  exchange_time: u64,
+}
+
+pub type EventCallback = extern fn(event: *const FFIEvent);
+
+fn vec_into_raw<T>(v: Vec<T>) -> (*mut T, u64) {
+  let len = v.len() as u64;
+  (Box::into_raw(v.into_boxed_slice()) as *mut T, len)
+}
+
+unsafe fn vec_from_raw<T>(ptr: *mut T, len: u64) -> Box<[T]> {
+  Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len as usize))
+}
+
+impl Event {
+  pub fn to_ffi(&self, dropped: u64) -> FFIEvent {
+    let (bids, bids_len) = match &self.payload {
+      EventPayload::OrderBook(resp) => vec_into_raw(resp.bids.iter().map(to_ffi_ask_bid).collect()),
+      _ => vec_into_raw(Vec::new()),
+    };
+    let (asks, asks_len) = match &self.payload {
+      EventPayload::OrderBook(resp) => vec_into_raw(resp.asks.iter().map(to_ffi_ask_bid).collect()),
+      _ => vec_into_raw(Vec::new()),
+    };
+    let (trades, trades_len) = match &self.payload {
+      EventPayload::Trades(trades) => vec_into_raw(trades.iter().map(to_ffi_trade).collect()),
+      _ => vec_into_raw(Vec::new()),
+    };
+    let (orders, orders_len) = match &self.payload {
+      EventPayload::Orders(orders) => vec_into_raw(orders.iter().cloned().map(order_to_ffi).collect()),
+      _ => vec_into_raw(Vec::new()),
+    };
+    let (balances, balances_len) = match &self.payload {
+      EventPayload::Balances(balances) => vec_into_raw(balances.iter().cloned().map(to_ffi_balance).collect()),
+      _ => vec_into_raw(Vec::new()),
+    };
+    let (trade_stats, trade_stats_len) = match &self.payload {
+      EventPayload::TradeStats(stats) => vec_into_raw(stats.clone()),
+      _ => vec_into_raw(Vec::new()),
+    };
+    let (depth, depth_len) = match &self.payload {
+      EventPayload::BookAnalytics(_, depth) => vec_into_raw(depth.clone()),
+      _ => vec_into_raw(Vec::new()),
+    };
+    let book_analytics = match &self.payload {
+      EventPayload::BookAnalytics(analytics, _) => *analytics,
+      _ => FFIBookAnalytics::default()
+    };
+    let (last_update_id, update_id) = match &self.payload {
+      EventPayload::OrderBook(resp) => (resp.last_update_id.unwrap_or_default(), resp.update_id.unwrap_or_default()),
+      _ => (0, 0)
+    };
+    let candle = match &self.payload {
+      EventPayload::Candle(candle, _) => to_ffi_candle(candle),
+      _ => FFICandle::default()
+    };
+    let ticker = match &self.payload {
+      EventPayload::Ticker(ticker) => FFITicker {
+        price: ticker.price.to_f64().unwrap_or_default(),
+        qty: ticker.qty.to_f64().unwrap_or_default(),
+        time: ticker.time,
+        open_24h: ticker.open_24h.to_f64().unwrap_or_default(),
+        high_24h: ticker.high_24h.to_f64().unwrap_or_default(),
+        low_24h: ticker.low_24h.to_f64().unwrap_or_default(),
+        volume_24h: ticker.volume_24h.to_f64().unwrap_or_default(),
+        price_change_percent_24h: price_change_percent(ticker),
+      },
+      _ => FFITicker::default()
+    };
+    let (error_tag, error_message) = match &self.payload {
+      EventPayload::Error(tag, message) => (*tag, string_to_c_str(message.clone())),
+      _ => (OpenLimitsResultTag::Ok, std::ptr::null_mut())
+    };
+    let silent_ms = match &self.payload {
+      EventPayload::Stale(silent_ms) => *silent_ms,
+      _ => 0
+    };
+
+    FFIEvent {
+      kind: self.kind,
+      subscription: FFISubscription {
+        id: self.source.id,
+        kind: self.source.kind,
+        market: self.source.market.as_ptr(),
+        user_data: self.source.user_data.0,
+      },
+      bids, bids_len,
+      asks, asks_len,
+      trades, trades_len,
+      orders, orders_len,
+      balances, balances_len,
+      trade_stats, trade_stats_len,
+      depth, depth_len,
+      last_update_id,
+      update_id,
+      candle,
+      ticker,
+      book_analytics,
+      error_tag,
+      error_message,
+      silent_ms,
+      dropped,
+      conflated: self.conflated,
+      total_dropped: self.source.dropped.load(Ordering::SeqCst),
+      total_conflated: self.source.conflated.load(Ordering::SeqCst),
+      received_monotonic_ns: self.received.monotonic_ns,
+      received_at: self.received.wall_ms,
+      exchange_time: self.payload.exchange_time(),
+    }
+  }
+}
+
+impl FFIEvent {
+  pub unsafe fn free(self) {
+    free_c_str(self.error_message);
+    for bid in vec_from_raw(self.bids, self.bids_len).iter().chain(vec_from_raw(self.asks, self.asks_len).iter()) {
+      free_c_str(bid.price);
+      free_c_str(bid.qty);
+    }
+    for trade in vec_from_raw(self.trades, self.trades_len).iter() {
+      free_c_str(trade.id);
+      free_c_str(trade.buyer_order_id);
+      free_c_str(trade.seller_order_id);
+      free_c_str(trade.market_pair);
+      free_c_str(trade.price);
+      free_c_str(trade.qty);
+      free_c_str(trade.fees);
+    }
+    for order in vec_from_raw(self.orders, self.orders_len).iter() {
+      free_c_str(order.id);
+      free_c_str(order.market_pair);
+      free_c_str(order.client_order_id);
+      free_c_str(order.size);
+      free_c_str(order.price);
+      free_c_str(order.remaining);
+    }
+    for balance in vec_from_raw(self.balances, self.balances_len).iter() {
+      free_c_str(balance.asset);
+      free_c_str(balance.total);
+      free_c_str(balance.free);
+    }
+    drop(vec_from_raw(self.trade_stats, self.trade_stats_len));
+    drop(vec_from_raw(self.depth, self.depth_len));
+  }
+}
+
+// Bounded queue between the subscription thread and the consumer. When full the oldest
+// event is discarded and counted; the count is reported on the next event handed out.
+pub struct EventQueue {
+  events: ArrayQueue<Event>,
+  dropped: AtomicU64,
+  closed: AtomicBool,
+  waiters: AtomicUsize,
+  lock: Mutex<()>,
+  ready: Condvar,
+}
+
+impl EventQueue {
+  pub fn new(capacity: usize) -> Self {
+    EventQueue {
+      events: ArrayQueue::new(std::cmp::max(capacity, 1)),
+      dropped: AtomicU64::new(0),
+      closed: AtomicBool::new(false),
+      waiters: AtomicUsize::new(0),
+      lock: Mutex::new(()),
+      ready: Condvar::new(),
+    }
+  }
+
+  pub fn push(&self, event: Event) {
+    let mut event = event;
+    while let Err(rejected) = self.events.push(event) {
+      if self.events.pop().is_some() {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+      }
+      event = rejected;
+    }
+    self.wake();
+  }
+
+  pub fn close(&self) {
+    self.closed.store(true, Ordering::SeqCst);
+    self.wake();
+  }
+
+  // Only takes the lock when a consumer is blocked in `wait`
+  fn wake(&self) {
+    fence(Ordering::SeqCst);
+    if self.waiters.load(Ordering::SeqCst) > 0 {
+      let _guard = self.lock.lock().expect("Event queue lock poisoned");
+      self.ready.notify_all();
+    }
+  }
+
+  pub fn poll(&self, out: &mut [FFIEvent]) -> usize {
+    let mut len = 0;
+    while len < out.len() {
+      let event = match self.events.pop() {
+        Some(event) => event,
+        None => break
+      };
+      if !event.source.is_live(event.seq) {
+        event.source.dropped.fetch_add(1, Ordering::SeqCst);
+        continue;
+      }
+      out[len] = event.to_ffi(self.dropped.swap(0, Ordering::SeqCst));
+      len += 1;
+    }
+    len
+  }
+
+  // Blocks until at least one event is available, the queue is closed or the timeout passes
+  pub fn wait(&self, out: &mut [FFIEvent], timeout: Duration) -> usize {
+    let deadline = Instant::now() + timeout;
+    self.waiters.fetch_add(1, Ordering::SeqCst);
+    fence(Ordering::SeqCst);
+    let mut guard = self.lock.lock().expect("Event queue lock poisoned");
+    while self.events.is_empty() && !self.closed.load(Ordering::SeqCst) {
+      let now = Instant::now();
+      if now >= deadline {
+        break;
+      }
+      guard = self.ready.wait_timeout(guard, deadline - now).expect("Event queue lock poisoned").0;
+    }
+    drop(guard);
+    self.waiters.fetch_sub(1, Ordering::SeqCst);
+    self.poll(out)
+  }
+}
+
+// Order book levels merged across several updates. Zero quantities are kept for diffs,
+// since they remove the level on the consumer side, but dropped once a snapshot is pending.
+struct PendingBook {
+  kind: FFIWsEventKind,
+  bids: BTreeMap<Decimal, Decimal>,
+  asks: BTreeMap<Decimal, Decimal>,
+  last_update_id: Option<u64>,
+  update_id: Option<u64>,
+}
+
+impl PendingBook {
+  fn new(kind: FFIWsEventKind, resp: OrderBookResponse) -> Self {
+    let mut book = PendingBook {
+      kind,
+      bids: BTreeMap::new(),
+      asks: BTreeMap::new(),
+      last_update_id: resp.last_update_id,
+      update_id: resp.update_id,
+    };
+    book.apply(resp);
+    book
+  }
+
+  fn apply(&mut self, resp: OrderBookResponse) {
+    let is_snapshot = matches!(self.kind, FFIWsEventKind::OrderBookSnapshot);
+    for (levels, side) in vec![(&mut self.bids, resp.bids), (&mut self.asks, resp.asks)] {
+      for level in side {
+        if is_snapshot && level.qty.is_zero() {
+          levels.remove(&level.price);
+        } else {
+          levels.insert(level.price, level.qty);
+        }
+      }
+    }
+    self.update_id = resp.update_id;
+  }
+
+  fn into_response(self) -> OrderBookResponse {
+    OrderBookResponse {
+      bids: self.bids.into_iter().rev().map(|(price, qty)| AskBid { price, qty }).collect(),
+      asks: self.asks.into_iter().map(|(price, qty)| AskBid { price, qty }).collect(),
+      last_update_id: self.last_update_id,
+      update_id: self.update_id,
+    }
+  }
+}
+
+// Holds back order book and trade updates of a conflating subscription until the next flush
+#[derive(Default)]
+pub struct Conflator {
+  book: Option<PendingBook>,
+  book_updates: u64,
+  book_received: ReceiveTime,
+  trades: Vec<Trade>,
+  trade_updates: u64,
+  trades_received: ReceiveTime,
+}
+
+impl Conflator {
+  // Keeps the update for the next flush, or hands it back if it is not an update that can be merged
+  pub fn merge(&mut self, kind: FFIWsEventKind, payload: EventPayload, received: ReceiveTime) -> Option<EventPayload> {
+    match (kind, payload) {
+      (FFIWsEventKind::OrderBookSnapshot, EventPayload::OrderBook(resp)) => {
+        self.book = Some(PendingBook::new(kind, resp));
+        self.book_updates += 1;
+        self.book_received = received;
+      },
+      (FFIWsEventKind::OrderBookDiff, EventPayload::OrderBook(resp)) => {
+        match &mut self.book {
+          Some(book) => book.apply(resp),
+          None => self.book = Some(PendingBook::new(kind, resp))
+        };
+        self.book_updates += 1;
+        self.book_received = received;
+      },
+      (FFIWsEventKind::Trades, EventPayload::Trades(trades)) => {
+        self.trades.extend(trades);
+        self.trade_updates += 1;
+        self.trades_received = received;
+      },
+      (_, payload) => return Some(payload)
+    };
+    None
+  }
+
+  // Pending updates as (kind, payload, time the latest update was received, number of updates merged away)
+  pub fn take(&mut self) -> Vec<(FFIWsEventKind, EventPayload, ReceiveTime, u64)> {
+    let mut pending = Vec::new();
+    if let Some(book) = self.book.take() {
+      pending.push((book.kind, EventPayload::OrderBook(book.into_response()), self.book_received, self.book_updates - 1));
+      self.book_updates = 0;
+    }
+    if self.trade_updates > 0 {
+      pending.push((FFIWsEventKind::Trades, EventPayload::Trades(std::mem::take(&mut self.trades)), self.trades_received, self.trade_updates - 1));
+      self.trade_updates = 0;
+    }
+    pending
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::ffi::CStr;
+  use std::str::FromStr;
+  use super::*;
+
+  fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+  }
+
+  fn source(mode: FFIDeliveryMode, max_pending: u64) -> Arc<EventSource> {
+    let delivery = FFIDeliveryPolicy { mode, conflate_ms: 0, max_pending };
+    Arc::new(EventSource::new(1, FFISubscriptionKind::Trades, CString::new("btcusd").unwrap(), UserData(std::ptr::null_mut()), delivery))
+  }
+
+  // Events are told apart by their message
+  fn event(source: &Arc<EventSource>, number: u64) -> Event {
+    Event {
+      kind: FFIWsEventKind::Error,
+      source: source.clone(),
+      payload: EventPayload::Error(OpenLimitsResultTag::SocketError, number.to_string()),
+      seq: source.admit(),
+      conflated: 0,
+      received: ReceiveTime::default(),
+    }
+  }
+
+  // (number, dropped, total_dropped) of every event handed out
+  fn poll_all(queue: &EventQueue) -> Vec<(u64, u64, u64)> {
+    let mut out: Vec<FFIEvent> = (0..8).map(|_| unsafe { std::mem::zeroed() }).collect();
+    let len = queue.poll(&mut out);
+    out.into_iter().take(len).map(|event| {
+      let number = unsafe { CStr::from_ptr(event.error_message) }.to_str().unwrap().parse().unwrap();
+      let polled = (number, event.dropped, event.total_dropped);
+      unsafe { event.free() };
+      polled
+    }).collect()
+  }
+
+  fn book(update_id: u64, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBookResponse {
+    let levels = |levels: &[(&str, &str)]| levels.iter().map(|(price, qty)| AskBid { price: dec(price), qty: dec(qty) }).collect();
+    OrderBookResponse {
+      last_update_id: Some(update_id - 1),
+      update_id: Some(update_id),
+      bids: levels(bids),
+      asks: levels(asks),
+    }
+  }
+
+  fn levels(levels: &[AskBid]) -> Vec<(Decimal, Decimal)> {
+    levels.iter().map(|level| (level.price, level.qty)).collect()
+  }
+
+  fn received(wall_ms: u64) -> ReceiveTime {
+    ReceiveTime { monotonic_ns: 0, wall_ms }
+  }
+
+  #[test]
+  fn full_queue_drops_the_oldest_and_reports_it_once() {
+    let source = source(FFIDeliveryMode::All, 0);
+    let queue = EventQueue::new(2);
+    for number in 1..=5 {
+      queue.push(event(&source, number));
+    }
+    assert_eq!(poll_all(&queue), vec![(4, 3, 0), (5, 0, 0)]);
+    queue.push(event(&source, 6));
+    assert_eq!(poll_all(&queue), vec![(6, 0, 0)]);
+  }
+
+  #[test]
+  fn drop_oldest_skips_superseded_events_and_counts_them_on_the_source() {
+    let source = source(FFIDeliveryMode::DropOldest, 2);
+    let queue = EventQueue::new(16);
+    for number in 1..=5 {
+      queue.push(event(&source, number));
+    }
+    assert_eq!(poll_all(&queue), vec![(4, 0, 3), (5, 0, 3)]);
+    // Once the consumer caught up nothing is dropped
+    queue.push(event(&source, 6));
+    assert_eq!(poll_all(&queue), vec![(6, 0, 3)]);
+  }
+
+  #[test]
+  fn snapshot_and_diffs_conflate_into_one_snapshot() {
+    let mut conflator = Conflator::default();
+    let updates = vec![
+      (FFIWsEventKind::OrderBookSnapshot, book(1, &[("99", "1"), ("98", "2")], &[("101", "1")])),
+      (FFIWsEventKind::OrderBookDiff, book(2, &[("99", "0"), ("97", "3")], &[("101", "2"), ("102", "1")])),
+      (FFIWsEventKind::OrderBookDiff, book(3, &[], &[("102", "0")])),
+    ];
+    for (wall_ms, (kind, resp)) in updates.into_iter().enumerate() {
+      assert!(conflator.merge(kind, EventPayload::OrderBook(resp), received(wall_ms as u64)).is_none());
+    }
+    let mut pending = conflator.take();
+    assert_eq!(pending.len(), 1);
+    let (kind, payload, received, conflated) = pending.remove(0);
+    assert!(matches!(kind, FFIWsEventKind::OrderBookSnapshot));
+    assert_eq!((received.wall_ms, conflated), (2, 2));
+    let resp = match payload {
+      EventPayload::OrderBook(resp) => resp,
+      _ => panic!("Expected an order book")
+    };
+    // Removed levels are left out of a snapshot
+    assert_eq!(levels(&resp.bids), vec![(dec("98"), dec("2")), (dec("97"), dec("3"))]);
+    assert_eq!(levels(&resp.asks), vec![(dec("101"), dec("2"))]);
+    assert_eq!((resp.last_update_id, resp.update_id), (Some(0), Some(3)));
+    assert!(conflator.take().is_empty());
+  }
+
+  #[test]
+  fn diffs_conflate_into_one_diff_keeping_removals() {
+    let mut conflator = Conflator::default();
+    conflator.merge(FFIWsEventKind::OrderBookDiff, EventPayload::OrderBook(book(5, &[("99", "1")], &[])), received(1));
+    conflator.merge(FFIWsEventKind::OrderBookDiff, EventPayload::OrderBook(book(6, &[("99", "0")], &[("101", "1")])), received(2));
+    let (kind, payload, _, conflated) = conflator.take().remove(0);
+    assert!(matches!(kind, FFIWsEventKind::OrderBookDiff));
+    assert_eq!(conflated, 1);
+    let resp = match payload {
+      EventPayload::OrderBook(resp) => resp,
+      _ => panic!("Expected an order book")
+    };
+    assert_eq!(levels(&resp.bids), vec![(dec("99"), dec("0"))]);
+    assert_eq!(levels(&resp.asks), vec![(dec("101"), dec("1"))]);
+    assert_eq!((resp.last_update_id, resp.update_id), (Some(4), Some(6)));
+  }
+
+  #[test]
+  fn updates_that_cannot_be_merged_are_handed_back() {
+    let mut conflator = Conflator::default();
+    let payload = conflator.merge(FFIWsEventKind::Ping, EventPayload::Empty, received(1));
+    assert!(matches!(payload, Some(EventPayload::Empty)));
+    assert!(conflator.take().is_empty());
+  }
+}
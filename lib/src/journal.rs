@@ -0,0 +1,585 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rust_decimal::Decimal;
+use openlimits::model::{AskBid, Liquidity, OrderBookResponse, Side, Trade};
+use crate::{FFIWsEventKind, OpenLimitsResultTag};
+use crate::events::EventPayload;
+
+const INDEX_FILE: &str = "index.tsv";
+const SEGMENT_PREFIX: &str = "journal-";
+const SEGMENT_SUFFIX: &str = ".log.gz";
+// Open segments are flushed at least this often so readers can see recent records
+const FLUSH_MS: u64 = 1000;
+
+// Segments are closed and indexed once they hold `max_segment_bytes` uncompressed bytes or are
+// `max_segment_ms` old, 0 disables either limit. With `unsupported`, messages the generic stream does not
+// support are kept too, debug formatted: openlimits does not hand out their wire payload.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFIJournalConfig {
+  max_segment_bytes: u64,
+  max_segment_ms: u64,
+  unsupported: bool,
+}
+
+// Which subscription of the market a record came from
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum JournalStream {
+  OrderBook,
+  Trades,
+}
+
+// Values are written tab and comma separated, escape anything that could be mistaken for a separator
+fn escape(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '%' => escaped.push_str("%25"),
+      '\t' => escaped.push_str("%09"),
+      '\n' => escaped.push_str("%0A"),
+      '\r' => escaped.push_str("%0D"),
+      ',' => escaped.push_str("%2C"),
+      ';' => escaped.push_str("%3B"),
+      '|' => escaped.push_str("%7C"),
+      c => escaped.push(c)
+    }
+  }
+  escaped
+}
+
+fn unescape(s: &str) -> String {
+  let mut unescaped = String::with_capacity(s.len());
+  let mut rest = s;
+  while let Some(pos) = rest.find('%') {
+    unescaped.push_str(&rest[..pos]);
+    let code = rest.get(pos + 1..pos + 3).and_then(|code| u8::from_str_radix(code, 16).ok());
+    match code {
+      Some(code) => {
+        unescaped.push(code as char);
+        rest = &rest[pos + 3..];
+      },
+      None => {
+        unescaped.push('%');
+        rest = &rest[pos + 1..];
+      }
+    }
+  }
+  unescaped.push_str(rest);
+  unescaped
+}
+
+fn optional(s: &Option<String>) -> String {
+  s.as_ref().map(|s| escape(s)).unwrap_or_default()
+}
+
+fn parse_optional(s: &str) -> Option<String> {
+  match s {
+    "" => None,
+    s => Some(unescape(s))
+  }
+}
+
+fn parse_decimal(s: &str) -> Option<Decimal> {
+  Decimal::from_str(s).ok()
+}
+
+fn format_levels(levels: &[AskBid]) -> String {
+  levels.iter().map(|level| format!("{};{}", level.price, level.qty)).collect::<Vec<_>>().join(",")
+}
+
+fn parse_levels(s: &str) -> Option<Vec<AskBid>> {
+  if s.is_empty() {
+    return Some(Vec::new());
+  }
+  s.split(',').map(|level| {
+    let mut fields = level.split(';');
+    Some(AskBid {
+      price: parse_decimal(fields.next()?)?,
+      qty: parse_decimal(fields.next()?)?,
+    })
+  }).collect()
+}
+
+fn format_book(resp: &OrderBookResponse) -> String {
+  format!(
+    "{}|{}|{}|{}",
+    resp.last_update_id.map(|id| id.to_string()).unwrap_or_default(),
+    resp.update_id.map(|id| id.to_string()).unwrap_or_default(),
+    format_levels(&resp.bids),
+    format_levels(&resp.asks)
+  )
+}
+
+fn parse_book(s: &str) -> Option<OrderBookResponse> {
+  let fields: Vec<&str> = s.split('|').collect();
+  if fields.len() != 4 {
+    return None;
+  }
+  Some(OrderBookResponse {
+    last_update_id: fields[0].parse().ok(),
+    update_id: fields[1].parse().ok(),
+    bids: parse_levels(fields[2])?,
+    asks: parse_levels(fields[3])?,
+  })
+}
+
+fn format_trade(trade: &Trade) -> String {
+  format!(
+    "{};{};{};{};{};{};{};{};{};{}",
+    escape(&trade.id),
+    optional(&trade.buyer_order_id),
+    optional(&trade.seller_order_id),
+    escape(&trade.market_pair),
+    trade.price,
+    trade.qty,
+    trade.fees.map(|fees| fees.to_string()).unwrap_or_default(),
+    match trade.side {
+      Side::Buy => "B",
+      Side::Sell => "S",
+    },
+    match trade.liquidity {
+      Some(Liquidity::Maker) => "M",
+      Some(Liquidity::Taker) => "T",
+      None => "",
+    },
+    trade.created_at
+  )
+}
+
+fn parse_trade(s: &str) -> Option<Trade> {
+  let fields: Vec<&str> = s.split(';').collect();
+  if fields.len() != 10 {
+    return None;
+  }
+  Some(Trade {
+    id: unescape(fields[0]),
+    buyer_order_id: parse_optional(fields[1]),
+    seller_order_id: parse_optional(fields[2]),
+    market_pair: unescape(fields[3]),
+    price: parse_decimal(fields[4])?,
+    qty: parse_decimal(fields[5])?,
+    fees: parse_decimal(fields[6]),
+    side: match fields[7] {
+      "B" => Side::Buy,
+      "S" => Side::Sell,
+      _ => return None
+    },
+    liquidity: match fields[8] {
+      "M" => Some(Liquidity::Maker),
+      "T" => Some(Liquidity::Taker),
+      _ => None
+    },
+    created_at: fields[9].parse().ok()?,
+  })
+}
+
+// First and last record time and number of records of one market in a segment
+#[derive(Debug, Copy, Clone)]
+struct MarketRange {
+  first_ms: u64,
+  last_ms: u64,
+  count: u64,
+}
+
+struct Segment {
+  name: String,
+  encoder: GzEncoder<File>,
+  started_ms: u64,
+  bytes: u64,
+  last_flush_ms: u64,
+  markets: HashMap<String, MarketRange>,
+}
+
+// Appends order book and trade messages of the subscription thread to rolling gzip segments.
+// A segment is listed in the index once it is closed, the open one is found by listing the directory.
+pub struct JournalWriter {
+  dir: PathBuf,
+  config: FFIJournalConfig,
+  segment: Option<Segment>,
+  next_segment: u64,
+}
+
+impl JournalWriter {
+  pub fn open(dir: PathBuf, config: FFIJournalConfig) -> io::Result<Self> {
+    fs::create_dir_all(&dir)?;
+    Ok(JournalWriter {
+      dir,
+      config,
+      segment: None,
+      next_segment: 0,
+    })
+  }
+
+  pub fn keeps_unsupported(&self) -> bool {
+    self.config.unsupported
+  }
+
+  pub fn record_book(&mut self, received_ms: u64, market: &str, snapshot: bool, resp: &OrderBookResponse) -> io::Result<()> {
+    let kind = if snapshot { "S" } else { "D" };
+    self.append(received_ms, market, JournalStream::OrderBook, kind, format_book(resp))
+  }
+
+  pub fn record_trades(&mut self, received_ms: u64, market: &str, trades: &[Trade]) -> io::Result<()> {
+    let payload = trades.iter().map(format_trade).collect::<Vec<_>>().join(",");
+    self.append(received_ms, market, JournalStream::Trades, "T", payload)
+  }
+
+  pub fn record_unsupported(&mut self, received_ms: u64, market: &str, stream: JournalStream, message: &str) -> io::Result<()> {
+    if !self.config.unsupported {
+      return Ok(());
+    }
+    self.append(received_ms, market, stream, "U", escape(message))
+  }
+
+  fn append(&mut self, received_ms: u64, market: &str, stream: JournalStream, kind: &str, payload: String) -> io::Result<()> {
+    if self.should_roll(received_ms) {
+      self.close_segment()?;
+    }
+    if self.segment.is_none() {
+      self.segment = Some(self.open_segment(received_ms)?);
+    }
+    let segment = self.segment.as_mut().expect("Segment was just opened");
+    let stream = match stream {
+      JournalStream::OrderBook => "B",
+      JournalStream::Trades => "T",
+    };
+    let line = format!("{}\t{}\t{}\t{}\t{}\n", received_ms, escape(market), stream, kind, payload);
+    segment.encoder.write_all(line.as_bytes())?;
+    segment.bytes += line.len() as u64;
+    let range = segment.markets.entry(String::from(market)).or_insert(MarketRange {
+      first_ms: received_ms,
+      last_ms: received_ms,
+      count: 0
+    });
+    range.first_ms = std::cmp::min(range.first_ms, received_ms);
+    range.last_ms = std::cmp::max(range.last_ms, received_ms);
+    range.count += 1;
+    if received_ms.saturating_sub(segment.last_flush_ms) >= FLUSH_MS {
+      segment.encoder.flush()?;
+      segment.last_flush_ms = received_ms;
+    }
+    Ok(())
+  }
+
+  fn should_roll(&self, now_ms: u64) -> bool {
+    match &self.segment {
+      Some(segment) => {
+        (self.config.max_segment_bytes != 0 && segment.bytes >= self.config.max_segment_bytes)
+          || (self.config.max_segment_ms != 0 && now_ms.saturating_sub(segment.started_ms) >= self.config.max_segment_ms)
+      },
+      None => false
+    }
+  }
+
+  fn open_segment(&mut self, started_ms: u64) -> io::Result<Segment> {
+    loop {
+      let name = format!("{}{:013}-{}{}", SEGMENT_PREFIX, started_ms, self.next_segment, SEGMENT_SUFFIX);
+      self.next_segment += 1;
+      let file = match OpenOptions::new().write(true).create_new(true).open(self.dir.join(&name)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+        Err(e) => return Err(e)
+      };
+      return Ok(Segment {
+        name,
+        encoder: GzEncoder::new(file, Compression::default()),
+        started_ms,
+        bytes: 0,
+        last_flush_ms: started_ms,
+        markets: HashMap::new(),
+      });
+    }
+  }
+
+  // Finishes the open segment and lists each of its markets in the index
+  pub fn close_segment(&mut self) -> io::Result<()> {
+    let segment = match self.segment.take() {
+      Some(segment) => segment,
+      None => return Ok(())
+    };
+    segment.encoder.finish()?.sync_all()?;
+    let mut index = OpenOptions::new().append(true).create(true).open(self.dir.join(INDEX_FILE))?;
+    let mut lines = String::new();
+    for (market, range) in segment.markets.iter() {
+      lines.push_str(&format!("{}\t{}\t{}\t{}\t{}\n", segment.name, escape(market), range.first_ms, range.last_ms, range.count));
+    }
+    index.write_all(lines.as_bytes())?;
+    index.sync_all()
+  }
+}
+
+impl Drop for JournalWriter {
+  fn drop(&mut self) {
+    self.close_segment().ok();
+  }
+}
+
+pub struct JournalRecord {
+  pub received_ms: u64,
+  pub stream: JournalStream,
+  pub kind: FFIWsEventKind,
+  pub payload: EventPayload,
+}
+
+fn parse_record(line: &str, market: &str) -> Option<JournalRecord> {
+  let fields: Vec<&str> = line.splitn(5, '\t').collect();
+  if fields.len() != 5 || unescape(fields[1]) != market {
+    return None;
+  }
+  let stream = match fields[2] {
+    "B" => JournalStream::OrderBook,
+    "T" => JournalStream::Trades,
+    _ => return None
+  };
+  let (kind, payload) = match fields[3] {
+    "S" => (FFIWsEventKind::OrderBookSnapshot, EventPayload::OrderBook(parse_book(fields[4])?)),
+    "D" => (FFIWsEventKind::OrderBookDiff, EventPayload::OrderBook(parse_book(fields[4])?)),
+    "T" => {
+      let trades = match fields[4] {
+        "" => Vec::new(),
+        trades => trades.split(',').map(parse_trade).collect::<Option<Vec<_>>>()?
+      };
+      (FFIWsEventKind::Trades, EventPayload::Trades(trades))
+    },
+    "U" => (
+      FFIWsEventKind::Unsupported,
+      EventPayload::Error(OpenLimitsResultTag::WebSocketMessageNotSupported, unescape(fields[4]))
+    ),
+    _ => return None
+  };
+  Some(JournalRecord {
+    received_ms: fields[0].parse().ok()?,
+    stream,
+    kind,
+    payload,
+  })
+}
+
+// Segment names start with their first record time, so they sort chronologically
+fn segments_to_read(dir: &PathBuf, market: &str, from_ms: u64, to_ms: u64) -> io::Result<VecDeque<PathBuf>> {
+  let mut indexed = HashSet::new();
+  let mut selected = HashSet::new();
+  if let Ok(index) = File::open(dir.join(INDEX_FILE)) {
+    for line in BufReader::new(index).lines() {
+      let line = line?;
+      let fields: Vec<&str> = line.split('\t').collect();
+      if fields.len() != 5 {
+        continue;
+      }
+      indexed.insert(String::from(fields[0]));
+      let first_ms: u64 = fields[2].parse().unwrap_or(0);
+      let last_ms: u64 = fields[3].parse().unwrap_or(u64::MAX);
+      if unescape(fields[1]) == market && first_ms <= to_ms && last_ms >= from_ms {
+        selected.insert(String::from(fields[0]));
+      }
+    }
+  }
+
+  let mut names = Vec::new();
+  for entry in fs::read_dir(dir)? {
+    let name = entry?.file_name().to_string_lossy().into_owned();
+    if !name.starts_with(SEGMENT_PREFIX) || !name.ends_with(SEGMENT_SUFFIX) {
+      continue;
+    }
+    // Segments missing from the index are still open or were never closed, they have to be scanned
+    if selected.contains(&name) || !indexed.contains(&name) {
+      names.push(name);
+    }
+  }
+  names.sort();
+  Ok(names.into_iter().map(|name| dir.join(name)).collect())
+}
+
+// Iterates the journaled records of one market received within [from_ms, to_ms], oldest first.
+// A segment cut short by a crash is read up to the last complete record.
+pub struct JournalReader {
+  market: String,
+  from_ms: u64,
+  to_ms: u64,
+  segments: VecDeque<PathBuf>,
+  lines: Option<io::Lines<BufReader<GzDecoder<File>>>>,
+}
+
+impl JournalReader {
+  pub fn open(dir: PathBuf, market: String, from_ms: u64, to_ms: u64) -> io::Result<Self> {
+    let segments = segments_to_read(&dir, &market, from_ms, to_ms)?;
+    Ok(JournalReader {
+      market,
+      from_ms,
+      to_ms,
+      segments,
+      lines: None,
+    })
+  }
+
+  fn next_line(&mut self) -> Option<String> {
+    loop {
+      if let Some(lines) = &mut self.lines {
+        match lines.next() {
+          Some(Ok(line)) => return Some(line),
+          _ => self.lines = None
+        }
+      }
+      let path = self.segments.pop_front()?;
+      if let Ok(file) = File::open(path) {
+        self.lines = Some(BufReader::new(GzDecoder::new(file)).lines());
+      }
+    }
+  }
+}
+
+impl Iterator for JournalReader {
+  type Item = JournalRecord;
+
+  fn next(&mut self) -> Option<JournalRecord> {
+    loop {
+      let line = self.next_line()?;
+      let record = match parse_record(&line, &self.market) {
+        Some(record) => record,
+        None => continue
+      };
+      if record.received_ms >= self.from_ms && record.received_ms <= self.to_ms {
+        return Some(record);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+  }
+
+  fn levels(levels: &[AskBid]) -> Vec<(Decimal, Decimal)> {
+    levels.iter().map(|level| (level.price, level.qty)).collect()
+  }
+
+  // A journal directory of its own per test, removed when dropped
+  struct TempDir(PathBuf);
+
+  impl TempDir {
+    fn new(name: &str) -> Self {
+      let dir = std::env::temp_dir().join(format!("openlimits-journal-{}-{}", std::process::id(), name));
+      fs::remove_dir_all(&dir).ok();
+      TempDir(dir)
+    }
+  }
+
+  impl Drop for TempDir {
+    fn drop(&mut self) {
+      fs::remove_dir_all(&self.0).ok();
+    }
+  }
+
+  fn names(segments: &VecDeque<PathBuf>) -> Vec<String> {
+    segments.iter().map(|path| path.file_name().unwrap().to_string_lossy().into_owned()).collect()
+  }
+
+  #[test]
+  fn escape_round_trips_separators_and_percent() {
+    let value = "a\tb\nc\rd,e;f|g%h%25";
+    let escaped = escape(value);
+    assert_eq!(escaped, "a%09b%0Ac%0Dd%2Ce%3Bf%7Cg%25h%2525");
+    assert_eq!(unescape(&escaped), value);
+    // A stray percent that is not an escape is kept as is
+    assert_eq!(unescape("100%"), "100%");
+    assert_eq!(unescape("%zz"), "%zz");
+  }
+
+  #[test]
+  fn book_round_trips() {
+    let resp = OrderBookResponse {
+      last_update_id: Some(7),
+      update_id: Some(9),
+      bids: vec![AskBid { price: dec("99.5"), qty: dec("1.25") }, AskBid { price: dec("99"), qty: dec("0") }],
+      asks: vec![AskBid { price: dec("100.5"), qty: dec("3") }],
+    };
+    let parsed = parse_book(&format_book(&resp)).unwrap();
+    assert_eq!((parsed.last_update_id, parsed.update_id), (Some(7), Some(9)));
+    assert_eq!(levels(&parsed.bids), levels(&resp.bids));
+    assert_eq!(levels(&parsed.asks), levels(&resp.asks));
+
+    let empty = OrderBookResponse {
+      last_update_id: None,
+      update_id: None,
+      bids: Vec::new(),
+      asks: Vec::new(),
+    };
+    let parsed = parse_book(&format_book(&empty)).unwrap();
+    assert_eq!((parsed.last_update_id, parsed.update_id), (None, None));
+    assert!(parsed.bids.is_empty() && parsed.asks.is_empty());
+    assert!(parse_book("1|2|99;1").is_none());
+  }
+
+  #[test]
+  fn trade_round_trips() {
+    let trade = Trade {
+      id: String::from("1;2,3|4%"),
+      buyer_order_id: Some(String::from("buyer\t1")),
+      seller_order_id: None,
+      market_pair: String::from("btc,usd"),
+      price: dec("100.5"),
+      qty: dec("0.001"),
+      fees: Some(dec("0.1")),
+      side: Side::Sell,
+      liquidity: Some(Liquidity::Maker),
+      created_at: 1600000000123,
+    };
+    let formatted = format_trade(&trade);
+    // Trades of a record are comma separated
+    assert!(!formatted.contains(','));
+    let parsed = parse_trade(&formatted).unwrap();
+    assert_eq!(parsed.id, trade.id);
+    assert_eq!(parsed.buyer_order_id, trade.buyer_order_id);
+    assert_eq!(parsed.seller_order_id, None);
+    assert_eq!(parsed.market_pair, trade.market_pair);
+    assert_eq!((parsed.price, parsed.qty, parsed.fees), (trade.price, trade.qty, trade.fees));
+    assert!(matches!(parsed.side, Side::Sell));
+    assert!(matches!(parsed.liquidity, Some(Liquidity::Maker)));
+    assert_eq!(parsed.created_at, trade.created_at);
+
+    let parsed = parse_trade(&format_trade(&Trade { fees: None, side: Side::Buy, liquidity: None, ..trade })).unwrap();
+    assert_eq!(parsed.fees, None);
+    assert!(matches!(parsed.side, Side::Buy));
+    assert!(parsed.liquidity.is_none());
+  }
+
+  #[test]
+  fn segments_to_read_uses_the_index_and_scans_unindexed_segments() {
+    let dir = TempDir::new("segments");
+    let book = OrderBookResponse {
+      last_update_id: None,
+      update_id: Some(1),
+      bids: Vec::new(),
+      asks: Vec::new(),
+    };
+    let config = FFIJournalConfig { max_segment_ms: 1000, ..FFIJournalConfig::default() };
+    let mut writer = JournalWriter::open(dir.0.clone(), config).unwrap();
+    writer.record_book(1000, "btcusd", true, &book).unwrap();
+    writer.record_book(1500, "ethusd", true, &book).unwrap();
+    writer.record_book(2500, "btcusd", false, &book).unwrap();
+    // Closes the second segment, the third stays open and out of the index
+    writer.record_book(3600, "ethusd", false, &book).unwrap();
+    let first = "journal-0000000001000-0.log.gz";
+    let second = "journal-0000000002500-1.log.gz";
+    let open = "journal-0000000003600-2.log.gz";
+
+    assert_eq!(names(&segments_to_read(&dir.0, "btcusd", 0, 2000).unwrap()), vec![first, open]);
+    assert_eq!(names(&segments_to_read(&dir.0, "btcusd", 2000, 5000).unwrap()), vec![second, open]);
+    assert_eq!(names(&segments_to_read(&dir.0, "ethusd", 1000, 1200).unwrap()), vec![open]);
+    assert_eq!(names(&segments_to_read(&dir.0, "ethusd", 1500, 1500).unwrap()), vec![first, open]);
+
+    drop(writer);
+    assert_eq!(names(&segments_to_read(&dir.0, "ethusd", 2000, 5000).unwrap()), vec![open]);
+    assert_eq!(names(&segments_to_read(&dir.0, "btcusd", 3000, 5000).unwrap()), Vec::<String>::new());
+  }
+}
@@ -4,10 +4,10 @@ use rust_decimal::Decimal;
 use chrono::Duration;
 use openlimits::{
   exchange::{OpenLimits, ExchangeAccount, ExchangeMarketData}, 
-  exchange_ws::OpenLimitsWs, 
+  exchange_ws::OpenLimitsWs,
   exchange_info::{MarketPair, ExchangeInfoRetrieval},
   errors::OpenLimitsError,
-  any_exchange::{AnyExchange, InitAnyExchange, AnyWsExchange},
+  any_exchange::{AnyExchange, InitAnyExchange, AnyWsExchange, AnyWsResponse},
   nash::{
     NashCredentials,
     NashParameters,
@@ -22,7 +22,7 @@ use openlimits::{
     CoinbaseParameters,
   },
   model::{      
-    OrderBookRequest, 
+    OrderBookRequest, OrderBookResponse, 
     GetOrderRequest,
     Liquidity,
     Side,
@@ -49,9 +49,45 @@ use openlimits::{
   }
 };
 use tokio::stream::StreamExt;
-use std::{ffi::CStr, ffi::CString, os::raw::c_char};
+use std::{ffi::CStr, ffi::CString, ffi::c_void, os::raw::c_char};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}};
 use thiserror::Error;
 
+mod account;
+mod aggregates;
+mod analytics;
+mod book;
+mod dryrun;
+mod emulation;
+mod events;
+mod journal;
+mod orders;
+mod ratelimit;
+mod retry;
+mod risk;
+mod stats;
+mod tape;
+mod validation;
+use account::{AccountPoller, AccountStream};
+use aggregates::Aggregate;
+use analytics::{BookAnalytics, FFIBookAnalytics, FFIBookAnalyticsConfig, FFIDepthAtDistance};
+use emulation::{EmulatedOrderCallback, EmulatorSink, OrderEmulator, SharedEmulator};
+use dryrun::{DryRun, DryRunCallback, is_simulated};
+use book::{BookReplay, BookSink, ConsolidatedBook, FFIBookReplayState, SharedBook, VenueLevel};
+use orders::{
+  AmendRequest, ClientOrderIds, FFICancelRequest, FFIOrderRequest, FFITriggerDirection, NewOrder, StopOrderRequest,
+  amend_native_order, cancel_replace, check_native_stop_order, ffi_side, is_terminal, place_native_stop_order, side_from_ffi
+};
+use journal::{FFIJournalConfig, JournalReader, JournalStream, JournalWriter};
+use ratelimit::{FFIEndpoint, FFIRateLimits, FFIRateLimitUsage, RateLimiter};
+use retry::{FFIRetryPolicy, RetryPolicy, with_retry};
+use risk::{FFIRiskLimits, RegisteredClient, RiskEngine, RiskInputs, kill_switch_engaged, register_client, registered_clients, set_kill_switch};
+use stats::{FFITradeStats, TradeStats};
+use tape::{FFITapeStats, SharedTape, TapeSink, TapeTrade, TradeTape};
+use validation::{FFIValidationMode, MarketRules, normalize, normalize_trigger};
+use events::{Conflator, Event, EventCallback, EventPayload, EventQueue, EventSource, FFIEvent, FFILatencyStats, ReceiveTime};
+
 #[repr(u32)]
 #[derive(Debug, Copy, Clone)]
 pub enum FFIInterval {
@@ -110,7 +146,7 @@ fn interval_from_ffi_interval(
 
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct FFICandle {
   time: u64,
   low: f64,
@@ -120,6 +156,20 @@ pub struct FFICandle {
   volume: f64,
 }
 
+// Ticker synthesized from the trade stream, with statistics over the last 24 hours
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFITicker {
+  price: f64,
+  qty: f64,
+  time: u64,
+  open_24h: f64,
+  high_24h: f64,
+  low_24h: f64,
+  volume_24h: f64,
+  price_change_percent_24h: f64,
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct FFIPaginator {
@@ -139,6 +189,13 @@ fn string_to_c_str(s: String) -> *mut c_char {
   raw
 }
 
+// Takes back a string handed out by string_to_c_str, null is ignored
+unsafe fn free_c_str(s: *mut c_char) {
+  if !s.is_null() {
+    drop(CString::from_raw(s));
+  }
+}
+
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -183,6 +240,18 @@ fn nullable_cstr(s: *mut c_char) -> Result<Option<String>, std::str::Utf8Error>
   }
 }
 
+fn decimal_from_c_str(s: *mut c_char, name: &str) -> Result<Decimal, OpenlimitsSharpError> {
+  if s.is_null() {
+    return Err(OpenlimitsSharpError::InvalidArgument(format!("{} is null", name)));
+  }
+  let s = c_str_to_string(s).map_err(|e|
+    OpenlimitsSharpError::InvalidArgument(format!("Failed to parse {} string. Invalid character on pos {}", name, e.valid_up_to()))
+  )?;
+  Decimal::from_str(s.as_str()).map_err(|e|
+    OpenlimitsSharpError::InvalidArgument(format!("Failed to parse {} string: {}", name, e))
+  )
+}
+
 
 impl TryInto<Paginator> for FFIPaginator {
   type Error = std::str::Utf8Error;
@@ -208,6 +277,10 @@ pub enum OpenlimitsSharpError {
   InitializeException(String),
   #[error("Failed to subscribe: {0}")]
   SubscribeException(String),
+  #[error("Risk rejected: {0}")]
+  RiskRejected(String),
+  #[error("Rate limited: {0}")]
+  RateLimited(String),
   #[error("{0}")]
   OpenLimitsError(#[from] OpenLimitsError)
 }
@@ -247,7 +320,9 @@ pub enum OpenLimitsResultTag {
 
   InitializeException,
   SubscribeException,
-  NoMarketPair
+  NoMarketPair,
+  RiskRejected,
+  RateLimited
 }
 
 #[repr(C)]
@@ -265,72 +340,104 @@ fn result_to_ffi(r: Result<(), OpenlimitsSharpError>) -> OpenLimitsResult {
         OpenlimitsSharpError::InvalidArgument(msg) => OpenLimitsResult { tag: OpenLimitsResultTag::InvalidArgument, message: string_to_c_str(msg) },
         OpenlimitsSharpError::InitializeException(msg) => OpenLimitsResult { tag: OpenLimitsResultTag::InitializeException, message: string_to_c_str(msg) },
         OpenlimitsSharpError::SubscribeException(msg) => OpenLimitsResult { tag: OpenLimitsResultTag::SubscribeException, message: string_to_c_str(msg) },
-        OpenlimitsSharpError::OpenLimitsError(e) => {
-          let message = match &e {
-            OpenLimitsError::BinanceError(e) => e.msg.clone(),
-            OpenLimitsError::CoinbaseError(e) => e.message.clone(),
-            OpenLimitsError::NashProtocolError(e) => e.0.to_string(),
-            OpenLimitsError::MissingImplementation(e) => e.message.clone(),
-            OpenLimitsError::AssetNotFound() => String::from("Asset not found"),
-            OpenLimitsError::NoApiKeySet() => String::from("No api key set"),
-            OpenLimitsError::InternalServerError() => String::from("Internal server error"),
-            OpenLimitsError::ServiceUnavailable() => String::from("Service unavailable"),
-            OpenLimitsError::Unauthorized() => String::from("Unauthorized"),
-            OpenLimitsError::SymbolNotFound() => String::from("Symbol not found"),
-            OpenLimitsError::SocketError() => String::from("Socket error"),
-            OpenLimitsError::GetTimestampFailed() => String::from("Get timestamp failed"),
-            OpenLimitsError::ReqError(e) => e.to_string(),
-            OpenLimitsError::InvalidHeaderError(e) => e.to_string(),
-            OpenLimitsError::InvalidPayloadSignature(e) => e.to_string(),
-            OpenLimitsError::IoError(e) => e.to_string(),
-            OpenLimitsError::PoisonError() => String::from("Poison error"),
-            OpenLimitsError::JsonError(e) => e.to_string(),
-            OpenLimitsError::ParseFloatError(e) => e.to_string(),
-            OpenLimitsError::UrlParserError(e) => e.to_string(),
-            OpenLimitsError::Tungstenite(e) => e.to_string(),
-            OpenLimitsError::TimestampError(e) => e.to_string(),
-            OpenLimitsError::UnkownResponse(e) => e.clone(),
-            OpenLimitsError::NotParsableResponse(e) => e.clone(),
-            OpenLimitsError::MissingParameter(e) => e.clone(),
-            OpenLimitsError::WebSocketMessageNotSupported() => String::from("WebSocket message not supported"),
-            OpenLimitsError::NoMarketPair => String::from("No market pair")
-          };
-          let tag = match &e {
-            OpenLimitsError::BinanceError(_) => OpenLimitsResultTag::BinanceError,
-            OpenLimitsError::CoinbaseError(_) => OpenLimitsResultTag::CoinbaseError,
-            OpenLimitsError::NashProtocolError(_) => OpenLimitsResultTag::NashProtocolError,
-            OpenLimitsError::MissingImplementation(_) => OpenLimitsResultTag::MissingImplementation,
-            OpenLimitsError::AssetNotFound() => OpenLimitsResultTag::AssetNotFound,
-            OpenLimitsError::NoApiKeySet() => OpenLimitsResultTag::NoApiKeySet,
-            OpenLimitsError::InternalServerError() => OpenLimitsResultTag::InternalServerError,
-            OpenLimitsError::ServiceUnavailable() => OpenLimitsResultTag::ServiceUnavailable,
-            OpenLimitsError::Unauthorized() => OpenLimitsResultTag::Unauthorized,
-            OpenLimitsError::SymbolNotFound() => OpenLimitsResultTag::SymbolNotFound,
-            OpenLimitsError::SocketError() => OpenLimitsResultTag::SocketError,
-            OpenLimitsError::GetTimestampFailed() => OpenLimitsResultTag::GetTimestampFailed,
-            OpenLimitsError::ReqError(_) => OpenLimitsResultTag::ReqError,
-            OpenLimitsError::InvalidHeaderError(_) => OpenLimitsResultTag::InvalidHeaderError,
-            OpenLimitsError::InvalidPayloadSignature(_) => OpenLimitsResultTag::InvalidPayloadSignature,
-            OpenLimitsError::IoError(_) => OpenLimitsResultTag::IoError,
-            OpenLimitsError::PoisonError() => OpenLimitsResultTag::PoisonError,
-            OpenLimitsError::JsonError(_) => OpenLimitsResultTag::JsonError,
-            OpenLimitsError::ParseFloatError(_) => OpenLimitsResultTag::ParseFloatError,
-            OpenLimitsError::UrlParserError(_) => OpenLimitsResultTag::UrlParserError,
-            OpenLimitsError::Tungstenite(_) => OpenLimitsResultTag::Tungstenite,
-            OpenLimitsError::TimestampError(_) => OpenLimitsResultTag::TimestampError,
-            OpenLimitsError::UnkownResponse(_) => OpenLimitsResultTag::UnkownResponse,
-            OpenLimitsError::NotParsableResponse(_) => OpenLimitsResultTag::NotParsableResponse,
-            OpenLimitsError::MissingParameter(_) => OpenLimitsResultTag::MissingParameter,
-            OpenLimitsError::WebSocketMessageNotSupported() => OpenLimitsResultTag::WebSocketMessageNotSupported,
-            OpenLimitsError::NoMarketPair => OpenLimitsResultTag::NoMarketPair,
-          };
-          OpenLimitsResult { tag, message: string_to_c_str(message) }
-        },
+        OpenlimitsSharpError::RiskRejected(msg) => OpenLimitsResult { tag: OpenLimitsResultTag::RiskRejected, message: string_to_c_str(msg) },
+        OpenlimitsSharpError::RateLimited(msg) => OpenLimitsResult { tag: OpenLimitsResultTag::RateLimited, message: string_to_c_str(msg) },
+        OpenlimitsSharpError::OpenLimitsError(e) => openlimits_error_to_ffi(&e),
       }
     }
   }
 }
 
+fn openlimits_error_to_ffi(e: &OpenLimitsError) -> OpenLimitsResult {
+  let (tag, message) = openlimits_error_parts(e);
+  OpenLimitsResult { tag, message: string_to_c_str(message) }
+}
+
+fn openlimits_error_parts(e: &OpenLimitsError) -> (OpenLimitsResultTag, String) {
+  let message = match e {
+    OpenLimitsError::BinanceError(e) => e.msg.clone(),
+    OpenLimitsError::CoinbaseError(e) => e.message.clone(),
+    OpenLimitsError::NashProtocolError(e) => e.0.to_string(),
+    OpenLimitsError::MissingImplementation(e) => e.message.clone(),
+    OpenLimitsError::AssetNotFound() => String::from("Asset not found"),
+    OpenLimitsError::NoApiKeySet() => String::from("No api key set"),
+    OpenLimitsError::InternalServerError() => String::from("Internal server error"),
+    OpenLimitsError::ServiceUnavailable() => String::from("Service unavailable"),
+    OpenLimitsError::Unauthorized() => String::from("Unauthorized"),
+    OpenLimitsError::SymbolNotFound() => String::from("Symbol not found"),
+    OpenLimitsError::SocketError() => String::from("Socket error"),
+    OpenLimitsError::GetTimestampFailed() => String::from("Get timestamp failed"),
+    OpenLimitsError::ReqError(e) => e.to_string(),
+    OpenLimitsError::InvalidHeaderError(e) => e.to_string(),
+    OpenLimitsError::InvalidPayloadSignature(e) => e.to_string(),
+    OpenLimitsError::IoError(e) => e.to_string(),
+    OpenLimitsError::PoisonError() => String::from("Poison error"),
+    OpenLimitsError::JsonError(e) => e.to_string(),
+    OpenLimitsError::ParseFloatError(e) => e.to_string(),
+    OpenLimitsError::UrlParserError(e) => e.to_string(),
+    OpenLimitsError::Tungstenite(e) => e.to_string(),
+    OpenLimitsError::TimestampError(e) => e.to_string(),
+    OpenLimitsError::UnkownResponse(e) => e.clone(),
+    OpenLimitsError::NotParsableResponse(e) => e.clone(),
+    OpenLimitsError::MissingParameter(e) => e.clone(),
+    OpenLimitsError::WebSocketMessageNotSupported() => String::from("WebSocket message not supported"),
+    OpenLimitsError::NoMarketPair => String::from("No market pair")
+  };
+  (openlimits_error_tag(e), message)
+}
+
+fn openlimits_error_tag(e: &OpenLimitsError) -> OpenLimitsResultTag {
+  match e {
+    OpenLimitsError::BinanceError(_) => OpenLimitsResultTag::BinanceError,
+    OpenLimitsError::CoinbaseError(_) => OpenLimitsResultTag::CoinbaseError,
+    OpenLimitsError::NashProtocolError(_) => OpenLimitsResultTag::NashProtocolError,
+    OpenLimitsError::MissingImplementation(_) => OpenLimitsResultTag::MissingImplementation,
+    OpenLimitsError::AssetNotFound() => OpenLimitsResultTag::AssetNotFound,
+    OpenLimitsError::NoApiKeySet() => OpenLimitsResultTag::NoApiKeySet,
+    OpenLimitsError::InternalServerError() => OpenLimitsResultTag::InternalServerError,
+    OpenLimitsError::ServiceUnavailable() => OpenLimitsResultTag::ServiceUnavailable,
+    OpenLimitsError::Unauthorized() => OpenLimitsResultTag::Unauthorized,
+    OpenLimitsError::SymbolNotFound() => OpenLimitsResultTag::SymbolNotFound,
+    OpenLimitsError::SocketError() => OpenLimitsResultTag::SocketError,
+    OpenLimitsError::GetTimestampFailed() => OpenLimitsResultTag::GetTimestampFailed,
+    OpenLimitsError::ReqError(_) => OpenLimitsResultTag::ReqError,
+    OpenLimitsError::InvalidHeaderError(_) => OpenLimitsResultTag::InvalidHeaderError,
+    OpenLimitsError::InvalidPayloadSignature(_) => OpenLimitsResultTag::InvalidPayloadSignature,
+    OpenLimitsError::IoError(_) => OpenLimitsResultTag::IoError,
+    OpenLimitsError::PoisonError() => OpenLimitsResultTag::PoisonError,
+    OpenLimitsError::JsonError(_) => OpenLimitsResultTag::JsonError,
+    OpenLimitsError::ParseFloatError(_) => OpenLimitsResultTag::ParseFloatError,
+    OpenLimitsError::UrlParserError(_) => OpenLimitsResultTag::UrlParserError,
+    OpenLimitsError::Tungstenite(_) => OpenLimitsResultTag::Tungstenite,
+    OpenLimitsError::TimestampError(_) => OpenLimitsResultTag::TimestampError,
+    OpenLimitsError::UnkownResponse(_) => OpenLimitsResultTag::UnkownResponse,
+    OpenLimitsError::NotParsableResponse(_) => OpenLimitsResultTag::NotParsableResponse,
+    OpenLimitsError::MissingParameter(_) => OpenLimitsResultTag::MissingParameter,
+    OpenLimitsError::WebSocketMessageNotSupported() => OpenLimitsResultTag::WebSocketMessageNotSupported,
+    OpenLimitsError::NoMarketPair => OpenLimitsResultTag::NoMarketPair,
+  }
+}
+
+fn error_tag(e: &OpenlimitsSharpError) -> OpenLimitsResultTag {
+  match e {
+    OpenlimitsSharpError::InvalidArgument(_) => OpenLimitsResultTag::InvalidArgument,
+    OpenlimitsSharpError::InitializeException(_) => OpenLimitsResultTag::InitializeException,
+    OpenlimitsSharpError::SubscribeException(_) => OpenLimitsResultTag::SubscribeException,
+    OpenlimitsSharpError::RiskRejected(_) => OpenLimitsResultTag::RiskRejected,
+    OpenlimitsSharpError::RateLimited(_) => OpenLimitsResultTag::RateLimited,
+    OpenlimitsSharpError::OpenLimitsError(e) => openlimits_error_tag(e),
+  }
+}
+
+fn error_parts(e: &OpenlimitsSharpError) -> (OpenLimitsResultTag, String) {
+  match e {
+    OpenlimitsSharpError::OpenLimitsError(e) => openlimits_error_parts(e),
+    OpenlimitsSharpError::InvalidArgument(message) | OpenlimitsSharpError::InitializeException(message)
+      | OpenlimitsSharpError::SubscribeException(message) | OpenlimitsSharpError::RiskRejected(message)
+      | OpenlimitsSharpError::RateLimited(message) => (error_tag(e), message.clone()),
+  }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct FFIAskBid {
@@ -408,7 +515,7 @@ pub enum FFIOrderStatus {
 }
 
 
-fn order_status_to_ffi(t: OrderStatus) -> FFIOrderStatus {
+pub(crate) fn order_status_to_ffi(t: OrderStatus) -> FFIOrderStatus {
   match t {
     OrderStatus::New => FFIOrderStatus::New,
     OrderStatus::PartiallyFilled => FFIOrderStatus::PartiallyFilled,
@@ -451,9 +558,12 @@ pub struct FFIOrder {
   pub size: *mut c_char,
   pub price: *mut c_char,
   pub remaining: *mut c_char,
+  // Returned by a dry run instead of the exchange
+  pub simulated: bool,
 }
 
 fn order_to_ffi(t: Order) -> FFIOrder {
+  let simulated = is_simulated(&t.id);
   FFIOrder {
     id: string_to_c_str(t.id),
     market_pair: string_to_c_str(t.market_pair),
@@ -479,7 +589,8 @@ fn order_to_ffi(t: Order) -> FFIOrder {
     remaining: match t.remaining {
       Some(rem) =>  string_to_c_str(rem.to_string()),
       None => std::ptr::null_mut()
-    }
+    },
+    simulated
   }
 }
 
@@ -589,12 +700,146 @@ pub enum FFINashEnv {
   Production
 }
 
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFISubscriptionKind {
+  OrderBook,
+  Trades,
+  Candles,
+  Ticker,
+  AccountOrders,
+  AccountTrades,
+  AccountBalances
+}
+
+// Describes the subscription an event belongs to. `market` is owned by the subscription and
+// only valid for the duration of the callback.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFISubscription {
+  id: u64,
+  kind: FFISubscriptionKind,
+  market: *const c_char,
+  user_data: *mut c_void,
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFIWsEventKind {
+  Ping,
+  OrderBookSnapshot,
+  OrderBookDiff,
+  Trades,
+  Error,
+  Unsupported,
+  Candle,
+  CandleClosed,
+  Ticker,
+  OrderUpdate,
+  Fill,
+  BalanceUpdate,
+  Stale,
+  Resumed,
+  TradeStats,
+  BookAnalytics
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFIConnectionState {
+  Connecting,
+  Connected,
+  Reconnecting,
+  GaveUp
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFIDeliveryMode {
+  All,
+  Conflate,
+  DropOldest
+}
+
+// How events of a single subscription reach the consumer. `conflate_ms` applies to Conflate,
+// `max_pending` to DropOldest.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIDeliveryPolicy {
+  mode: FFIDeliveryMode,
+  conflate_ms: u64,
+  max_pending: u64,
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFIExchangeKind {
+  Binance,
+  Coinbase,
+  Nash
+}
+
+// A price level of a consolidated book, tagged with the venue it is on. Price and qty are null for an empty side.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIVenueLevel {
+  venue: u32,
+  exchange: FFIExchangeKind,
+  price: *mut c_char,
+  qty: *mut c_char,
+}
+
+// A trade of a merged trade tape. The caller frees the symbol and trade strings.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFITapeTrade {
+  venue: u32,
+  exchange: FFIExchangeKind,
+  symbol: *mut c_char,
+  late: bool,
+  trade: FFITrade,
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFIStaleAction {
+  Notify,
+  Resubscribe,
+  Reconnect
+}
+
+// A subscription is stale when no market data arrived for `threshold_ms`, 0 disables the check.
+// Pings do not count, the socket can stay alive while a market stops updating.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIStalePolicy {
+  threshold_ms: u64,
+  action: FFIStaleAction,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIReconnectPolicy {
+  max_attempts: u32,
+  initial_backoff_ms: u64,
+  max_backoff_ms: u64,
+}
+
 #[repr(C)]
 pub struct ExchangeClient {
   client: AnyExchange,
   init_params: InitAnyExchange,
   channel: Option<tokio::sync::mpsc::UnboundedSender<SubthreadCmd>>,
-  runtime: tokio::runtime::Runtime
+  runtime: tokio::runtime::Runtime,
+  client_orders: ClientOrderIds,
+  market_rules: MarketRules,
+  // Shared with the order emulator, whose orders count against the same limits
+  risk: Arc<RiskEngine>,
+  // Shared with the order emulator and the account streams, which use the same account
+  rate_limiter: Arc<RateLimiter>,
+  retry_policy: RetryPolicy,
+  // Shared with the order emulator, whose placements are simulated too
+  dry_run: Arc<DryRun>
 }
 
 #[repr(C)]
@@ -602,10 +847,66 @@ pub struct ExchangeClient {
 pub struct InitResult {
   client: *mut ExchangeClient,
 }
-type SubResult = std::result::Result<openlimits::exchange_ws::CallbackHandle, openlimits::errors::OpenLimitsError>;
+type SubResult = std::result::Result<u64, OpenlimitsSharpError>;
 type SubChannel = tokio::sync::oneshot::Sender<SubResult>;
+
+#[derive(Copy, Clone)]
+pub struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+// What the consumer asked for. Candles and tickers are built from the underlying trade subscription,
+// account streams are polled over REST every given number of milliseconds.
+#[derive(Copy, Clone)]
+pub enum StreamKind {
+  OrderBook,
+  Trades,
+  Candles(FFIInterval),
+  Ticker,
+  Account(AccountStream, u64)
+}
+
+#[derive(Clone)]
+pub struct SubscriptionRequest {
+  market: String,
+  stream: StreamKind,
+  callback: Option<EventCallback>,
+  user_data: UserData,
+  delivery: FFIDeliveryPolicy,
+  sink: Option<Sink>,
+}
+
+// Cross client structures fed straight from the subscription thread instead of the event queue
+#[derive(Clone)]
+pub enum Sink {
+  Book(BookSink),
+  Tape(TapeSink),
+  Emulator(EmulatorSink),
+}
+
+impl SubscriptionRequest {
+  // Journal the messages of this request are recorded to, candles and tickers record their trades
+  fn journal_stream(&self) -> JournalStream {
+    match self.stream {
+      StreamKind::OrderBook => JournalStream::OrderBook,
+      _ => JournalStream::Trades
+    }
+  }
+
+  // The websocket subscription feeding this request, None for streams that are polled
+  fn ws_subscription(&self) -> Option<Subscription> {
+    match self.stream {
+      StreamKind::OrderBook => Some(Subscription::OrderBookUpdates(self.market.clone())),
+      StreamKind::Trades | StreamKind::Candles(_) | StreamKind::Ticker => Some(Subscription::Trades(self.market.clone())),
+      StreamKind::Account(_, _) => None
+    }
+  }
+}
+
 pub enum SubthreadCmd {
-  Sub(Subscription, SubChannel),
+  Sub(SubscriptionRequest, SubChannel),
+  Resubscribe(u64),
+  Reconnect(u64),
   Disconnect
 }
 
@@ -622,14 +923,22 @@ pub  extern "cdecl" fn init_binance(
     let client: AnyExchange = runtime.block_on(client_future)?;
 
 
+    let rate_limiter = Arc::new(RateLimiter::new(exchange_kind(&init_params)));
     let b = Box::new(ExchangeClient{
       client,
       init_params,
       channel: None,
-      runtime
+      runtime,
+      client_orders: ClientOrderIds::default(),
+      market_rules: MarketRules::new(FFIValidationMode::Reject),
+      risk: Arc::new(RiskEngine::default()),
+      rate_limiter,
+      retry_policy: RetryPolicy::default(),
+      dry_run: Arc::new(DryRun::default())
     });
     unsafe {
       *out_client = Box::into_raw(b);
+      register_client(&**out_client);
       Ok(())
     }
   };
@@ -683,14 +992,22 @@ pub  extern "cdecl" fn init_coinbase(
     let client: AnyExchange = runtime.block_on(client_future)?;
 
 
+    let rate_limiter = Arc::new(RateLimiter::new(exchange_kind(&init_params)));
     let b = Box::new(ExchangeClient{
       client,
       init_params,
       channel: None,
-      runtime
+      runtime,
+      client_orders: ClientOrderIds::default(),
+      market_rules: MarketRules::new(FFIValidationMode::Reject),
+      risk: Arc::new(RiskEngine::default()),
+      rate_limiter,
+      retry_policy: RetryPolicy::default(),
+      dry_run: Arc::new(DryRun::default())
     });
     unsafe {
       *out_client = Box::into_raw(b);
+      register_client(&**out_client);
       Ok(())
     }
   };
@@ -751,14 +1068,22 @@ pub  extern "cdecl" fn init_nash(
     let client_future = OpenLimits::instantiate(init_params.clone());
     let client: AnyExchange = runtime.block_on(client_future)?;
 
+    let rate_limiter = Arc::new(RateLimiter::new(exchange_kind(&init_params)));
     let b = Box::new(ExchangeClient{
       client,
       init_params,
       channel: None,
-      runtime
+      runtime,
+      client_orders: ClientOrderIds::default(),
+      market_rules: MarketRules::new(FFIValidationMode::Reject),
+      risk: Arc::new(RiskEngine::default()),
+      rate_limiter,
+      retry_policy: RetryPolicy::default(),
+      dry_run: Arc::new(DryRun::default())
     });
     unsafe {
       *out_client = Box::into_raw(b);
+      register_client(&**out_client);
       Ok(())
     }
   };
@@ -791,9 +1116,10 @@ pub  extern "cdecl" fn order_book(
       market_pair
     };
     unsafe {
-      let resp = (*client).runtime.block_on(
-        (*client).client.order_book(&req)
-      )?;
+      let resp = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::OrderBook, 1)?;
+        Ok((*client).runtime.block_on((*client).client.order_book(&req))?)
+      })?;
   
       let bids = std::slice::from_raw_parts_mut::<FFIAskBid>(bids_buff, bids_buff_len as usize);
       let ffi_bids: Vec<FFIAskBid> = resp.bids.iter().map(to_ffi_ask_bid).collect();
@@ -839,9 +1165,10 @@ pub  extern "cdecl" fn get_price_ticker(
       market_pair
     };
     unsafe {
-      let resp = (*client).runtime.block_on(
-        (*client).client.get_price_ticker(&req)
-      )?;
+      let resp = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::PriceTicker, 1)?;
+        Ok((*client).runtime.block_on((*client).client.get_price_ticker(&req))?)
+      })?;
       let price_opt = resp.price;
       let price_opt = price_opt.map(|f| f.to_f64()).flatten();
       (*price) = price_opt.unwrap_or(std::f64::NAN);
@@ -885,9 +1212,10 @@ pub  extern "cdecl" fn get_historic_rates(
       interval
     };
     unsafe {
-      let resp = (*client).runtime.block_on(
-        (*client).client.get_historic_rates(&req)
-      )?;
+      let resp = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::HistoricRates, 1)?;
+        Ok((*client).runtime.block_on((*client).client.get_historic_rates(&req))?)
+      })?;
 
 
       let canles = std::slice::from_raw_parts_mut::<FFICandle>(candles_buff, candles_buff_len);
@@ -931,9 +1259,10 @@ pub  extern "cdecl" fn get_historic_trades(
       market_pair,
     };
     unsafe {
-      let resp = (*client).runtime.block_on(
-        (*client).client.get_historic_trades(&req)
-      )?;
+      let resp = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::HistoricTrades, 1)?;
+        Ok((*client).runtime.block_on((*client).client.get_historic_trades(&req))?)
+      })?;
 
       let trades = std::slice::from_raw_parts_mut::<FFITrade>(buff, buff_len);
       let ffi_trades: Vec<FFITrade> = resp.iter().map(to_ffi_trade).collect();
@@ -945,10 +1274,37 @@ pub  extern "cdecl" fn get_historic_trades(
   };
   result_to_ffi(call())
 }
-#[no_mangle]
+// Claims the client order id for a placement. When an order was already placed with it, that order
+// is looked up and written to `result` instead, and true is returned.
+unsafe fn begin_placement(client: *mut ExchangeClient, client_order_id: &str, result: Out<FFIOrder>) -> Result<bool, OpenlimitsSharpError> {
+  let (id, market_pair) = match (*client).client_orders.begin(client_order_id)? {
+    Some(placed) => placed,
+    None => return Ok(false)
+  };
+  let req = GetOrderRequest {
+    id,
+    market_pair: Some(market_pair)
+  };
+  let mut order = fetch_order(&mut *client, &req)?;
+  order.client_order_id = Some(String::from(client_order_id));
+  (*result) = order_to_ffi(order);
+  Ok(true)
+}
 
-pub extern "cdecl" fn place_order(
+unsafe fn finish_placement(
   client: *mut ExchangeClient,
+  client_order_id: &str,
+  placed: Result<Order, OpenLimitsError>,
+  result: Out<FFIOrder>
+) -> Result<(), OpenlimitsSharpError> {
+  (*client).client_orders.finish(client_order_id, placed.as_ref().map(|order| (order.id.as_str(), order.market_pair.as_str())));
+  let mut order = placed?;
+  order.client_order_id = Some(String::from(client_order_id));
+  (*result) = order_to_ffi(order);
+  Ok(())
+}
+
+fn parse_new_order(
   market: *mut c_char,
   qty: *mut c_char,
   limit: bool,
@@ -956,87 +1312,414 @@ pub extern "cdecl" fn place_order(
   side: FFISide,
   tif: FFITIF,
   tif_duration: u64,
-  _post_only: bool,
+  post_only: bool,
+) -> Result<NewOrder, OpenlimitsSharpError> {
+  if market.is_null() {
+    return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+  }
+  let market_pair = c_str_to_string(market).map_err(|e|
+    OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+  )?;
+  let size = decimal_from_c_str(qty, "size")?;
+  if limit == false {
+    return Ok(NewOrder::Market(side, OpenMarketOrderRequest {
+      market_pair,
+      size
+    }));
+  }
+  let price = decimal_from_c_str(price, "price")?;
+  Ok(NewOrder::Limit(side, OpenLimitOrderRequest {
+    market_pair,
+    price,
+    time_in_force: ffitif_to_tif(tif, tif_duration),
+    size,
+    post_only
+  }))
+}
+
+// Waits for the rate limit budget of `count` calls, failing with RateLimited when that takes longer
+// than the client allows
+fn throttle(limiter: &RateLimiter, endpoint: FFIEndpoint, count: u32) -> Result<(), OpenlimitsSharpError> {
+  limiter.acquire(endpoint, count, limiter.deadline()).map_err(OpenlimitsSharpError::RateLimited)
+}
+
+// Looks an order up on the exchange, or among the simulated orders of the client's dry runs
+fn fetch_order(client: &mut ExchangeClient, req: &GetOrderRequest) -> Result<Order, OpenlimitsSharpError> {
+  if is_simulated(&req.id) {
+    return client.dry_run.get(&req.id).ok_or_else(|| OpenlimitsSharpError::InvalidArgument(format!("Unknown simulated order {}", req.id)));
+  }
+  with_retry(client.retry_policy, || {
+    throttle(&client.rate_limiter, FFIEndpoint::GetOrder, 1)?;
+    Ok(client.runtime.block_on(client.client.get_order(req))?)
+  })
+}
+
+// Market pair of `market` if the exchange lists it, fetching the pairs on first use
+fn market_pair<'a>(client: &'a mut ExchangeClient, market: &str) -> Result<Option<&'a MarketPair>, OpenlimitsSharpError> {
+  if client.market_rules.needs_refresh(market) {
+    let pairs = with_retry(client.retry_policy, || {
+      throttle(&client.rate_limiter, FFIEndpoint::MarketPairs, 1)?;
+      Ok(client.runtime.block_on(client.client.retrieve_pairs())?)
+    })?;
+    client.market_rules.load(pairs);
+  }
+  Ok(client.market_rules.pair(market))
+}
+
+// Checks or rounds size and price with the client's validation mode. Markets the exchange doesn't
+// list are left for the exchange to reject.
+fn validate_order(
+  client: &mut ExchangeClient,
+  market: &str,
+  side: FFISide,
+  size: Decimal,
+  price: Option<Decimal>
+) -> Result<(Decimal, Option<Decimal>), OpenlimitsSharpError> {
+  let mode = client.market_rules.mode;
+  if mode == FFIValidationMode::Off {
+    return Ok((size, price));
+  }
+  match market_pair(client, market)? {
+    Some(pair) => normalize(pair, mode, side, size, price).map_err(OpenlimitsSharpError::InvalidArgument),
+    None => Ok((size, price))
+  }
+}
+
+fn validate_trigger(client: &mut ExchangeClient, market: &str, trigger_price: Decimal) -> Result<Decimal, OpenlimitsSharpError> {
+  let mode = client.market_rules.mode;
+  if mode == FFIValidationMode::Off {
+    return Ok(trigger_price);
+  }
+  match market_pair(client, market)? {
+    Some(pair) => normalize_trigger(pair, mode, trigger_price).map_err(OpenlimitsSharpError::InvalidArgument),
+    None => Ok(trigger_price)
+  }
+}
+
+fn mid_price(client: &mut ExchangeClient, market: &str) -> Result<Option<Decimal>, OpenlimitsSharpError> {
+  let req = OrderBookRequest {
+    market_pair: String::from(market)
+  };
+  let book = with_retry(client.retry_policy, || {
+    throttle(&client.rate_limiter, FFIEndpoint::OrderBook, 1)?;
+    Ok(client.runtime.block_on(client.client.order_book(&req))?)
+  })?;
+  Ok(book_mid(&book))
+}
+
+fn book_mid(book: &OrderBookResponse) -> Option<Decimal> {
+  let best_bid = book.bids.iter().map(|bid| bid.price).max();
+  let best_ask = book.asks.iter().map(|ask| ask.price).min();
+  match (best_bid, best_ask) {
+    (Some(bid), Some(ask)) => Some((bid + ask) / Decimal::new(2, 0)),
+    _ => None
+  }
+}
+
+// Runs the client's risk checks on an order about to be sent and reserves it under `key` until
+// RiskEngine::sent is called with the key. `replaces` is the order an amend takes the place of.
+fn risk_check(
+  client: &mut ExchangeClient,
+  key: &str,
+  market: &str,
+  side: FFISide,
+  size: Decimal,
+  price: Option<Decimal>,
+  replaces: Option<&str>
+) -> Result<(), OpenlimitsSharpError> {
+  let risk = client.risk.clone();
+  risk.check(client, key, market, side, size, price, replaces)
+}
+
+impl RiskInputs for ExchangeClient {
+  fn open_orders(&mut self) -> Result<Vec<Order>, OpenlimitsSharpError> {
+    with_retry(self.retry_policy, || {
+      throttle(&self.rate_limiter, FFIEndpoint::OpenOrders, 1)?;
+      Ok(self.runtime.block_on(self.client.get_all_open_orders())?)
+    })
+  }
+
+  fn balances(&mut self) -> Result<Vec<Balance>, OpenlimitsSharpError> {
+    with_retry(self.retry_policy, || {
+      throttle(&self.rate_limiter, FFIEndpoint::AccountBalances, 1)?;
+      Ok(self.runtime.block_on(self.client.get_account_balances(None))?)
+    })
+  }
+
+  fn mid_price(&mut self, market: &str) -> Result<Option<Decimal>, OpenlimitsSharpError> {
+    mid_price(self, market)
+  }
+
+  fn base_asset(&mut self, market: &str) -> Result<Option<String>, OpenlimitsSharpError> {
+    Ok(market_pair(self, market)?.map(|pair| pair.base.clone()))
+  }
+}
+
+fn validate_new_order(client: &mut ExchangeClient, new_order: NewOrder) -> Result<NewOrder, OpenlimitsSharpError> {
+  Ok(match new_order {
+    NewOrder::Market(side, req) => {
+      let (size, _) = validate_order(client, &req.market_pair, side, req.size, None)?;
+      NewOrder::Market(side, OpenMarketOrderRequest { size, ..req })
+    },
+    NewOrder::Limit(side, req) => {
+      let (size, price) = validate_order(client, &req.market_pair, side, req.size, Some(req.price))?;
+      NewOrder::Limit(side, OpenLimitOrderRequest { size, price: price.unwrap_or(req.price), ..req })
+    }
+  })
+}
+
+fn client_order_id_or_generate(client_orders: &ClientOrderIds, client_order_id: *mut c_char) -> Result<String, OpenlimitsSharpError> {
+  let client_order_id = nullable_cstr(client_order_id).map_err(|e|
+    OpenlimitsSharpError::InvalidArgument(format!("Failed to parse client_order_id string. Invalid character on pos {}", e.valid_up_to()))
+  )?;
+  Ok(match client_order_id {
+    Some(client_order_id) => client_order_id,
+    None => client_orders.generate()
+  })
+}
 
+// Places a limit or market order. Without a client order id one is generated; placing again with
+// the id of an order already placed returns that order instead of sending a new one.
+#[no_mangle]
+pub extern "cdecl" fn place_order(
+  client: *mut ExchangeClient,
+  market: *mut c_char,
+  qty: *mut c_char,
+  limit: bool,
+  price: *mut c_char,
+  side: FFISide,
+  tif: FFITIF,
+  tif_duration: u64,
+  post_only: bool,
+  client_order_id: *mut c_char,
   result: Out<FFIOrder>
 ) -> OpenLimitsResult {
   let call = move|| -> Result<(), OpenlimitsSharpError> {
     if client.is_null() {
       return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
     }
-    let market_pair = c_str_to_string(market).map_err(|e|
-      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
-    )?;
-    let size = c_str_to_string(qty).map(|q| Decimal::from_str(q.as_str()));
-    let size = size.map_err(|e|
-      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse size string. Invalid character on pos {}", e.valid_up_to()))
-    )?;
-    let size = size.map_err(|e|
-      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse size string: {}", e))
-    )?;
+    let new_order = parse_new_order(market, qty, limit, price, side, tif, tif_duration, post_only)?;
+    unsafe {
+      let new_order = validate_new_order(&mut *client, new_order)?;
+      let client_order_id = client_order_id_or_generate(&(*client).client_orders, client_order_id)?;
+      if begin_placement(client, &client_order_id, result)? {
+        return Ok(());
+      }
+      let dry_run = (*client).dry_run.enabled();
+      let (market, side, size, price) = new_order.terms();
+      let sendable = risk_check(&mut *client, &client_order_id, market, side, size, price, None)
+        .and_then(|_| match dry_run {
+          true => Ok(()),
+          false => throttle(&(*client).rate_limiter, FFIEndpoint::PlaceOrder, 1)
+        });
+      if let Err(e) = sendable {
+        (*client).risk.sent(&client_order_id, None);
+        (*client).client_orders.release(&client_order_id);
+        return Err(e);
+      }
+      let order = if dry_run {
+        Ok((*client).dry_run.place(&new_order, Some(&client_order_id)))
+      } else {
+        (*client).runtime.block_on(new_order.send(&(*client).client))
+      };
+      (*client).risk.sent(&client_order_id, order.as_ref().ok());
+      finish_placement(client, &client_order_id, order, result)
+    }
+  };
 
+  result_to_ffi(call())
+}
 
-    if limit == false {
-      let  req = OpenMarketOrderRequest {
-        market_pair,
-        size
-      };
+enum BatchPlacement {
+  Send(String, NewOrder),
+  // Client order id, exchange order id and market of an order already placed with the client order id
+  Placed(String, String, String),
+}
 
-      unsafe {
-        #[allow(unreachable_patterns)]
-        match side {
-          FFISide::Buy => {
-            let order = (*client).runtime.block_on(
-              (*client).client.market_buy(&req)
-            )?;
-            (*result) = order_to_ffi(order);
-            return Ok(());
-          },
-          FFISide::Sell => {
-            let order = (*client).runtime.block_on(
-              (*client).client.market_sell(&req)
-            )?;
-            (*result) = order_to_ffi(order);
-            return Ok(());
+// Places every order of `requests` concurrently. openlimits exposes no batch order endpoint for
+// any exchange, so each order is sent as its own request. `results` and `orders` must hold
+// `requests_len` items; `orders[i]` is only written when `results[i]` is Ok, and the call itself
+// only fails when its arguments are unusable.
+#[no_mangle]
+pub extern "cdecl" fn place_orders(
+  client: *mut ExchangeClient,
+  requests: *const FFIOrderRequest,
+  requests_len: u64,
+  results: *mut OpenLimitsResult,
+  orders: *mut FFIOrder
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    if requests_len > 0 && (requests.is_null() || results.is_null() || orders.is_null()) {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("requests, results or orders is null")));
+    }
+    unsafe {
+      let client = &mut *client;
+      let requests = match requests_len {
+        0 => &[],
+        len => std::slice::from_raw_parts(requests, len as usize)
+      };
+      // Client order ids are claimed in order, so a repeated id fails on its later items
+      let placements: Vec<Result<BatchPlacement, OpenlimitsSharpError>> = requests.iter().map(|req| {
+        let new_order = parse_new_order(req.market, req.qty, req.limit, req.price, req.side, req.tif, req.tif_duration, req.post_only)?;
+        let new_order = validate_new_order(client, new_order)?;
+        let client_order_id = client_order_id_or_generate(&client.client_orders, req.client_order_id)?;
+        if let Some((id, market_pair)) = client.client_orders.begin(&client_order_id)? {
+          if !is_simulated(&id) {
+            throttle(&client.rate_limiter, FFIEndpoint::GetOrder, 1)?;
+          }
+          return Ok(BatchPlacement::Placed(client_order_id, id, market_pair));
+        }
+        let (market, side, size, price) = new_order.terms();
+        let sendable = risk_check(client, &client_order_id, market, side, size, price, None)
+          .and_then(|_| match client.dry_run.enabled() {
+            true => Ok(()),
+            false => throttle(&client.rate_limiter, FFIEndpoint::PlaceOrder, 1)
+          });
+        if let Err(e) = sendable {
+          client.risk.sent(&client_order_id, None);
+          client.client_orders.release(&client_order_id);
+          return Err(e);
+        }
+        Ok(BatchPlacement::Send(client_order_id, new_order))
+      }).collect();
+      // Orders that passed the risk checks, to release their reservations once sent
+      let reserved: Vec<Option<String>> = placements.iter().map(|placement| match placement {
+        Ok(BatchPlacement::Send(client_order_id, _)) => Some(client_order_id.clone()),
+        _ => None
+      }).collect();
+
+      let (runtime, exchange, client_orders, dry_run) = (&mut client.runtime, &client.client, &client.client_orders, &client.dry_run);
+
+      let placed = runtime.block_on(futures_util::future::join_all(placements.into_iter().map(|placement| async move {
+        let (client_order_id, order) = match placement? {
+          BatchPlacement::Send(client_order_id, new_order) => {
+            let order = match dry_run.enabled() {
+              true => Ok(dry_run.place(&new_order, Some(&client_order_id))),
+              false => new_order.send(exchange).await
+            };
+            client_orders.finish(&client_order_id, order.as_ref().map(|order| (order.id.as_str(), order.market_pair.as_str())));
+            (client_order_id, order?)
           },
-          e => return Err(OpenlimitsSharpError::InvalidArgument(format!("Invalid side size string: {:?}", e)))
+          BatchPlacement::Placed(client_order_id, id, market_pair) => {
+            let order = match dry_run.get(&id) {
+              Some(order) => order,
+              None => exchange.get_order(&GetOrderRequest {
+                id,
+                market_pair: Some(market_pair)
+              }).await?
+            };
+            (client_order_id, order)
+          }
+        };
+        Ok(Order {
+          client_order_id: Some(client_order_id),
+          ..order
+        })
+      })));
+
+      for (client_order_id, order) in reserved.iter().zip(placed.iter()) {
+        if let Some(client_order_id) = client_order_id {
+          client.risk.sent(client_order_id, order.as_ref().ok());
         }
       }
+      for (i, order) in placed.into_iter().enumerate() {
+        let order: Result<Order, OpenlimitsSharpError> = order;
+        *results.add(i) = match order {
+          Ok(order) => {
+            *orders.add(i) = order_to_ffi(order);
+            result_to_ffi(Ok(()))
+          },
+          Err(e) => result_to_ffi(Err(e))
+        };
+      }
+    }
+    Ok(())
+  };
+
+  result_to_ffi(call())
+}
+
+// Places a stop order that becomes a limit order at `limit_price`, or a market order when it is null,
+// once the price moves to `trigger_price` in `direction`. Exchanges without a native stop order
+// endpoint fail with MissingImplementation before any risk or rate limit budget is reserved.
+#[no_mangle]
+pub extern "cdecl" fn place_stop_order(
+  client: *mut ExchangeClient,
+  market: *mut c_char,
+  qty: *mut c_char,
+  side: FFISide,
+  trigger_price: *mut c_char,
+  direction: FFITriggerDirection,
+  limit_price: *mut c_char,
+  tif: FFITIF,
+  tif_duration: u64,
+  client_order_id: *mut c_char,
+  result: Out<FFIOrder>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
     }
-    let price = c_str_to_string(price).map(|q| Decimal::from_str(q.as_str())).map_err(|e|
-      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse price string. Invalid character on pos {}", e.valid_up_to()))
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
     )?;
-    let price = price.map_err(|e|
-      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse price string: {}", e))
+    let size = decimal_from_c_str(qty, "qty")?;
+    let trigger_price = decimal_from_c_str(trigger_price, "trigger_price")?;
+    let limit_price = match limit_price.is_null() {
+      true => None,
+      false => Some(decimal_from_c_str(limit_price, "limit_price")?)
+    };
+    let client_order_id = nullable_cstr(client_order_id).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse client_order_id string. Invalid character on pos {}", e.valid_up_to()))
     )?;
-
-    let time_in_force = ffitif_to_tif(tif, tif_duration);
-    let req = OpenLimitOrderRequest {
+    let (size, limit_price) = unsafe { validate_order(&mut *client, &market_pair, side, size, limit_price)? };
+    let trigger_price = unsafe { validate_trigger(&mut *client, &market_pair, trigger_price)? };
+    let req = StopOrderRequest {
       market_pair,
-      price,
-      time_in_force,
+      side,
       size,
-      post_only: _post_only
+      trigger_price,
+      direction,
+      limit_price,
+      time_in_force: ffitif_to_tif(tif, tif_duration),
     };
+
     unsafe {
-      #[allow(unreachable_patterns)]
-      match side {
-        FFISide::Buy => {
-          let order = (*client).runtime.block_on(
-            (*client).client.limit_buy(&req)
-          )?;
-          (*result) = order_to_ffi(order);
-          return Ok(());
-        },
-        FFISide::Sell => {
-          let order = (*client).runtime.block_on(
-            (*client).client.limit_sell(&req)
-          )?;
-          (*result) = order_to_ffi(order);
-          return Ok(());
-        },
-        e => return Err(OpenlimitsSharpError::InvalidArgument(format!("Invalid side size string: {:?}", e)))
+      // Dry runs fail like the real call would, so a strategy relying on stop orders is caught early
+      let exchange = exchange_kind(&(*client).init_params);
+      check_native_stop_order(exchange, &req)?;
+      let dry_run = (*client).dry_run.enabled();
+      let client_order_id = match client_order_id {
+        Some(client_order_id) => client_order_id,
+        None => (*client).client_orders.generate()
+      };
+      if begin_placement(client, &client_order_id, result)? {
+        return Ok(());
       }
+      // Stop orders are checked at their limit price, or the trigger price for stop market orders
+      let price = req.limit_price.or(Some(req.trigger_price));
+      let sendable = risk_check(&mut *client, &client_order_id, &req.market_pair, side, req.size, price, None)
+        .and_then(|_| match dry_run {
+          true => Ok(()),
+          false => throttle(&(*client).rate_limiter, FFIEndpoint::PlaceOrder, 1)
+        });
+      if let Err(e) = sendable {
+        (*client).risk.sent(&client_order_id, None);
+        (*client).client_orders.release(&client_order_id);
+        return Err(e);
+      }
+      let order = match dry_run {
+        true => Ok((*client).dry_run.place_stop(&req, Some(&client_order_id))),
+        false => (*client).runtime.block_on(place_native_stop_order(exchange, &req))
+      };
+      (*client).risk.sent(&client_order_id, order.as_ref().ok());
+      finish_placement(client, &client_order_id, order, result)
     }
   };
 
@@ -1054,14 +1737,15 @@ pub  extern "cdecl" fn get_all_open_orders(
     }
 
     unsafe {
-      let resp = (*client).runtime.block_on(
-        (*client).client.get_all_open_orders()
-      )?;
+      let resp = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::OpenOrders, 1)?;
+        Ok((*client).runtime.block_on((*client).client.get_all_open_orders())?)
+      })?;
 
       let orders = std::slice::from_raw_parts_mut::<FFIOrder>(buff, buff_len);
       let ffi_orders: Vec<FFIOrder> = resp.into_iter().map(order_to_ffi).collect();
       let l = std::cmp::min(buff_len, ffi_orders.len());
-      orders[0..ffi_orders.len()].copy_from_slice(&ffi_orders[0..l]);
+      orders[0..l].copy_from_slice(&ffi_orders[0..l]);
       (*actual_buff_len) = l;
     };
     Ok(())
@@ -1100,9 +1784,10 @@ pub  extern "cdecl" fn get_order_history(
       market_pair,
     };
     unsafe {
-      let resp = (*client).runtime.block_on(
-        (*client).client.get_order_history(&req)
-      )?;
+      let resp = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::OrderHistory, 1)?;
+        Ok((*client).runtime.block_on((*client).client.get_order_history(&req))?)
+      })?;
 
       let orders = std::slice::from_raw_parts_mut::<FFIOrder>(buff, buff_len);
       let ffi_orders: Vec<FFIOrder> = resp.into_iter().map(order_to_ffi).collect();
@@ -1154,15 +1839,16 @@ pub  extern "cdecl" fn get_trade_history(
       market_pair,
     };
     unsafe {
-      let resp = (*client).runtime.block_on(
-        (*client).client.get_trade_history(&req)
-      )?;
+      let resp = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::TradeHistory, 1)?;
+        Ok((*client).runtime.block_on((*client).client.get_trade_history(&req))?)
+      })?;
 
       let trades = std::slice::from_raw_parts_mut::<FFITrade>(buff, buff_len);
       let ffi_trades: Vec<FFITrade> = resp.iter().map(to_ffi_trade).collect();
       let l = std::cmp::min(buff_len, ffi_trades.len());
 
-      trades[0..ffi_trades.len()].copy_from_slice(&ffi_trades[0..l]);
+      trades[0..l].copy_from_slice(&ffi_trades[0..l]);
       (*actual_buff_len) = l;
     }
     Ok(())
@@ -1195,9 +1881,10 @@ pub  extern "cdecl" fn get_account_balances(
 
 
     unsafe {
-      let resp = (*client).runtime.block_on(
-        (*client).client.get_account_balances(paginator)
-      )?;
+      let resp = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::AccountBalances, 1)?;
+        Ok((*client).runtime.block_on((*client).client.get_account_balances(paginator.clone()))?)
+      })?;
 
       let balances = std::slice::from_raw_parts_mut::<FFIBalance>(buff, buff_len);
       let ffi_balances: Vec<FFIBalance> = resp.into_iter().map(to_ffi_balance).collect();
@@ -1229,12 +1916,26 @@ pub  extern "cdecl" fn cancel_all_orders(
 
   
     unsafe {
+      if (*client).dry_run.enabled() {
+        let ids = dry_run_cancel_all(&*client, market_pair);
+        let buff = std::slice::from_raw_parts_mut::<*mut c_char>(buff, buff_len);
+        let l = std::cmp::min(buff_len, ids.len());
+        for (slot, id) in buff.iter_mut().zip(ids.into_iter()) {
+          *slot = string_to_c_str(id);
+        }
+        (*actual_buff_len) = l;
+        return Ok(());
+      }
+      throttle(&(*client).rate_limiter, FFIEndpoint::CancelAllOrders, 1)?;
       let resp = (*client).runtime.block_on(
         (*client).client.cancel_all_orders(&CancelAllOrdersRequest {
           market_pair
         })
       )?;
 
+      for canceled in resp.iter() {
+        (*client).risk.canceled(&canceled.id);
+      }
       let ids = std::slice::from_raw_parts_mut::<*mut c_char>(buff, buff_len);
       let ffi_ids: Vec<*mut c_char> = resp.into_iter().map(|c|string_to_c_str(c.id)).collect();
       let l = std::cmp::min(buff_len, ffi_ids.len());
@@ -1247,7 +1948,20 @@ pub  extern "cdecl" fn cancel_all_orders(
   result_to_ffi(call())
 }
 
-#[no_mangle]
+// Cancels the open simulated orders and returns their ids, the exchange is not read
+fn dry_run_cancel_all(client: &ExchangeClient, market_pair: Option<String>) -> Vec<String> {
+  client.dry_run.log(match &market_pair {
+    Some(market_pair) => format!("would cancel all on {}", market_pair),
+    None => String::from("would cancel all on every market")
+  });
+  let ids: Vec<String> = client.dry_run.open(market_pair.as_deref()).into_iter().map(|order| order.id).collect();
+  for id in ids.iter() {
+    client.dry_run.cancel(id);
+  }
+  ids
+}
+
+#[no_mangle]
 pub extern "cdecl" fn get_order(
   client: *mut ExchangeClient,
   order_id: *mut c_char,
@@ -1267,12 +1981,14 @@ pub extern "cdecl" fn get_order(
     )?;
 
     unsafe {
-      let order = (*client).runtime.block_on(
-        (*client).client.get_order( &GetOrderRequest {
-          id,
-          market_pair
-        })
-      )?;
+      let req = GetOrderRequest {
+        id,
+        market_pair
+      };
+      let mut order = fetch_order(&mut *client, &req)?;
+      if order.client_order_id.is_none() {
+        order.client_order_id = (*client).client_orders.client_order_id(&order.id);
+      }
       (*result) = order_to_ffi(order);
     }
 
@@ -1300,283 +2016,2490 @@ pub  extern "cdecl" fn cancel_order(
     )?;
 
     unsafe {
+      if (*client).dry_run.simulates_cancel(&id) {
+        (*client).dry_run.cancel(&id);
+        return Ok(());
+      }
+      throttle(&(*client).rate_limiter, FFIEndpoint::CancelOrder, 1)?;
       (*client).runtime.block_on(
         (*client).client.cancel_order(&CancelOrderRequest {
-          id,
+          id: id.clone(),
           market_pair
         })
       )?;
+      (*client).risk.canceled(&id);
     }
     Ok(())
   };  
   result_to_ffi(call())
 }
 
-
+// Cancels every order of `requests` concurrently, each with its own request like place_orders.
+// `results` must hold `requests_len` items.
 #[no_mangle]
-pub  extern "cdecl" fn receive_pairs(
+pub extern "cdecl" fn cancel_orders(
   client: *mut ExchangeClient,
-  buff: *mut FFIMarketPair, buff_len: usize, actual_buff_len: Out<usize>,
+  requests: *const FFICancelRequest,
+  requests_len: u64,
+  results: *mut OpenLimitsResult
 ) -> OpenLimitsResult {
   let call = move|| -> Result<(), OpenlimitsSharpError> {
     if client.is_null() {
       return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
     }
+    if requests_len > 0 && (requests.is_null() || results.is_null()) {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("requests or results is null")));
+    }
     unsafe {
-      let pairs = (*client).runtime.block_on(
-        (*client).client.retrieve_pairs()
-      )?;
-
-      let pairs_buff = std::slice::from_raw_parts_mut::<FFIMarketPair>(buff, buff_len);
-      let pairs_ffi: Vec<FFIMarketPair> = pairs.into_iter().map(market_pair_to_ffi).collect();
-      let l = std::cmp::min(buff_len, pairs_ffi.len());
+      let client = &mut *client;
+      let (runtime, exchange, limiter, dry_run) = (&mut client.runtime, &client.client, &client.rate_limiter, &client.dry_run);
+      let requests = match requests_len {
+        0 => &[],
+        len => std::slice::from_raw_parts(requests, len as usize)
+      };
+      let cancels: Vec<Result<CancelOrderRequest, OpenlimitsSharpError>> = requests.iter().map(|req| {
+        if req.order_id.is_null() {
+          return Err(OpenlimitsSharpError::InvalidArgument(String::from("order_id is null")));
+        }
+        let id = c_str_to_string(req.order_id).map_err(|e|
+          OpenlimitsSharpError::InvalidArgument(format!("Failed to parse order_id string. Invalid character on pos {}", e.valid_up_to()))
+        )?;
+        let market_pair = nullable_cstr(req.market).map_err(|e|
+          OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+        )?;
+        if !dry_run.simulates_cancel(&id) {
+          throttle(limiter, FFIEndpoint::CancelOrder, 1)?;
+        }
+        Ok(CancelOrderRequest {
+          id,
+          market_pair
+        })
+      }).collect();
 
-      pairs_buff[0..l].copy_from_slice(&pairs_ffi[0..l]);
-      (*actual_buff_len) = l;
+      let canceled = runtime.block_on(futures_util::future::join_all(cancels.into_iter().map(|cancel| async move {
+        let cancel = cancel?;
+        if dry_run.simulates_cancel(&cancel.id) {
+          dry_run.cancel(&cancel.id);
+          return Ok((cancel.id, false));
+        }
+        exchange.cancel_order(&cancel).await?;
+        Ok((cancel.id, true))
+      })));
+
+      for (i, canceled) in canceled.into_iter().enumerate() {
+        let canceled: Result<(String, bool), OpenlimitsSharpError> = canceled;
+        if let Ok((id, true)) = &canceled {
+          client.risk.canceled(id);
+        }
+        *results.add(i) = result_to_ffi(canceled.map(|_| ()));
+      }
     }
     Ok(())
   };
   result_to_ffi(call())
 }
 
+// Outcome of every step of an amend. Steps that were not run are left Ok.
 #[repr(C)]
-#[derive(Copy, Clone)]
-pub struct FFITradeBox(*mut FFITrade);
-unsafe impl Send for FFITradeBox {}
-unsafe impl Sync for FFITradeBox {}
-#[repr(C)]
-#[derive(Copy, Clone)]
-pub struct FFIAskBidBox(*mut FFIAskBid);
-unsafe impl Send for FFIAskBidBox {}
-unsafe impl Sync for FFIAskBidBox {}
+#[derive(Debug)]
+pub struct FFIAmendResult {
+  // The exchange amended the order itself and `replacement` is the amended order
+  native: bool,
+  cancel: OpenLimitsResult,
+  // False when the cancel failed or the order filled before it was canceled
+  replacement_sent: bool,
+  replacement: OpenLimitsResult,
+  // True when the replacement was rejected and the rest of the original order was placed again
+  restore_sent: bool,
+  restore: OpenLimitsResult,
+}
 
+// Changes the price and/or size of an open limit order; a null price or size keeps the current one
+// and the size is what is left to work afterwards. Exchanges without a native amend get the order
+// canceled and replaced by a good till cancelled limit order, with the original put back if the
+// replacement is rejected. `replacement_order` and `restored_order` are only written when their
+// step succeeded.
 #[no_mangle]
-#[allow(unsafe_code)]
-pub  extern "cdecl" fn init_subscriptions(
+pub extern "cdecl" fn amend_order(
   client: *mut ExchangeClient,
-  on_error: extern fn(),
-  on_ping: extern fn(),
-  on_orderbook: extern fn(bids_len: u64, asks_len: u64, market: *mut c_char, last_update_id: u64, update_id: u64),
-  on_trades: extern fn(buff_len: u64, market: *mut c_char),
-  on_disconnet: extern fn(),
-  bids_buff: FFIAskBidBox, bids_buff_len: usize,
-  asks_buff: FFIAskBidBox, asks_buff_len: usize,
-  trades_buff: FFITradeBox, trades_buff_len: usize,
-  sub_handle: Out<*mut tokio::sync::mpsc::UnboundedSender<SubthreadCmd>>
-) ->  OpenLimitsResult {
-  let (sub_request_tx, mut sub_rx) = tokio::sync::mpsc::unbounded_channel::<SubthreadCmd>();
-
-  let init_params = unsafe {
-    (*client).init_params.clone()
-  };
-  let (finish_tx, finish_rx) = tokio::sync::oneshot::channel::<Result<(), OpenlimitsSharpError>>();
-  
-  std::thread::spawn(move || {
-    let call = move|| -> Result<(tokio::runtime::Runtime, OpenLimitsWs<AnyWsExchange>), OpenlimitsSharpError> {
-      let mut rt = tokio::runtime::Builder::new()
-                .basic_scheduler()
-                .enable_all()
-                .build()
-                .map_err(|_| OpenlimitsSharpError::InitializeException(String::from("Failed to start tokio runtime")))?;
-      let client: OpenLimitsWs<AnyWsExchange> = rt.block_on(OpenLimitsWs::instantiate(init_params))?;
-
-      Ok((rt, client))
+  order_id: *mut c_char,
+  market: *mut c_char,
+  new_price: *mut c_char,
+  new_size: *mut c_char,
+  client_order_id: *mut c_char,
+  result: Out<FFIAmendResult>,
+  replacement_order: Out<FFIOrder>,
+  restored_order: Out<FFIOrder>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    if order_id.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("order_id is null")));
+    }
+    let id = c_str_to_string(order_id).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse order_id string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let market_pair = nullable_cstr(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let price = match new_price.is_null() {
+      true => None,
+      false => Some(decimal_from_c_str(new_price, "new_price")?)
     };
+    let size = match new_size.is_null() {
+      true => None,
+      false => Some(decimal_from_c_str(new_size, "new_size")?)
+    };
+    if price.is_none() && size.is_none() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("Either new_price or new_size must be set")));
+    }
 
-    let (mut rt, client) = match call() {
-      Ok(e) => e,
-      Err(e) => {
-        finish_tx.send(Err(e)).expect("Failed to communicate result back to main thread");
-        return;
+    unsafe {
+      let client = &mut *client;
+      // Replacements would need their cancel and placement simulated step by step
+      if client.dry_run.enabled() || is_simulated(&id) {
+        return Err(OpenlimitsSharpError::InvalidArgument(String::from("Orders can't be amended in dry run")));
+      }
+      let req = GetOrderRequest {
+        id,
+        market_pair
+      };
+      let original = fetch_order(client, &req)?;
+      if is_terminal(&original.status) {
+        return Err(OpenlimitsSharpError::InvalidArgument(format!("Order {} is no longer open", original.id)));
+      }
+      if !matches!(original.order_type, OrderType::Limit) || original.price.is_none() {
+        return Err(OpenlimitsSharpError::InvalidArgument(format!("Order {} is not a limit order", original.id)));
+      }
+      let side = ffi_side(&original.side);
+      let remaining = original.remaining.unwrap_or(original.size);
+      let (checked_size, checked_price) = validate_order(client, &original.market_pair, side, size.unwrap_or(remaining), price.or(original.price))?;
+      let req = AmendRequest {
+        id: original.id.clone(),
+        market_pair: original.market_pair.clone(),
+        price: price.and(checked_price),
+        size: size.map(|_| checked_size),
+      };
+      let client_order_id = client_order_id_or_generate(&client.client_orders, client_order_id)?;
+      if client.client_orders.begin(&client_order_id)?.is_some() {
+        return Err(OpenlimitsSharpError::InvalidArgument(format!("An order was already placed with client order id {}", client_order_id)));
+      }
+      if let Err(e) = risk_check(client, &client_order_id, &original.market_pair, side, checked_size, checked_price, Some(&original.id)) {
+        client.client_orders.release(&client_order_id);
+        return Err(e);
       }
-    };
-    finish_tx.send(Ok(())).expect("Failed to communicate result back to main thread");
 
-    loop { 
-      let subcmd = sub_rx.next();
-      let thread_cmd = rt.block_on(subcmd);
-      match thread_cmd {
-        Some(SubthreadCmd::Disconnect) => {
-          break;
-        },
-        Some(SubthreadCmd::Sub(sub, writer)) => {
-          let result = rt.block_on(client.subscribe(sub.clone(), move |resp| {
-            let out_asks = unsafe { std::slice::from_raw_parts_mut::<FFIAskBid>(asks_buff.0, asks_buff_len) };
-            let out_bids = unsafe { std::slice::from_raw_parts_mut::<FFIAskBid>(bids_buff.0, bids_buff_len) };
-            let resp = match resp {
-              Ok(e) => e,
-              Err(_) => {
-                on_error();
-                return
-              }
-            };
-            let resp = match resp {
-              WebSocketResponse::Generic(msg) => msg,
-              _ => {
-                return;
-              }
-            };
+      let ok = || result_to_ffi(Ok(()));
+      match client.runtime.block_on(amend_native_order(exchange_kind(&client.init_params), &req)) {
+        Err(OpenLimitsError::MissingImplementation(_)) => {},
+        amended => {
+          client.client_orders.finish(&client_order_id, amended.as_ref().map(|order| (order.id.as_str(), order.market_pair.as_str())));
+          if amended.is_ok() {
+            client.risk.canceled(&original.id);
+          }
+          client.risk.sent(&client_order_id, amended.as_ref().ok());
+          (*replacement_order) = order_to_ffi(Order {
+            client_order_id: Some(client_order_id),
+            ..amended?
+          });
+          (*result) = FFIAmendResult {
+            native: true,
+            cancel: ok(),
+            replacement_sent: true,
+            replacement: ok(),
+            restore_sent: false,
+            restore: ok(),
+          };
+          return Ok(());
+        }
+      };
 
-            match resp {
-              OpenLimitsWebSocketMessage::Ping => {
-                on_ping();
-              },
-              OpenLimitsWebSocketMessage::Trades(trades) => {
-                let out_trades = unsafe { std::slice::from_raw_parts_mut::<FFITrade>(trades_buff.0, trades_buff_len) };
-                let market = match sub.clone() {
-                  Subscription::Trades(market) => market,
-                  _ => panic!("Unreachable")
-                };
-                for (i, trade) in trades.iter().enumerate() {
-                  out_trades[i] = to_ffi_trade(trade);
-                }
-                on_trades(trades.len() as u64, string_to_c_str(market));
-              },
-              OpenLimitsWebSocketMessage::OrderBook(resp) => {
-                let market = match sub.clone() {
-                  Subscription::OrderBookUpdates(market) => market,
-                  _ => panic!("Unreachable")
-                };
-                for (i, bid) in resp.bids.iter().enumerate() {
-                  out_bids[i] = to_ffi_ask_bid(bid);
-                }
-                for (i, ask) in resp.asks.iter().enumerate() {
-                  out_asks[i] = to_ffi_ask_bid(ask);
-                }
-                on_orderbook(
-                  resp.bids.len() as u64,
-                  resp.asks.len() as u64,
-                  string_to_c_str(market.clone()),
-                  resp.last_update_id.unwrap_or_default(),
-                  resp.update_id.unwrap_or_default()
-                );
-              },
-              OpenLimitsWebSocketMessage::OrderBookDiff(resp) => {
-                let market = match sub.clone() {
-                  Subscription::OrderBookUpdates(market) => market,
-                  _ => panic!("Unreachable")
-                };
-                for (i, bid) in resp.bids.iter().enumerate() {
-                  out_bids[i] = to_ffi_ask_bid(bid);
-                }
-                for (i, ask) in resp.asks.iter().enumerate() {
-                  out_asks[i] = to_ffi_ask_bid(ask);
-                }
-                on_orderbook(
-                  resp.bids.len() as u64,
-                  resp.asks.len() as u64,
-                  string_to_c_str(market.clone()),
-                  resp.last_update_id.unwrap_or_default(),
-                  resp.update_id.unwrap_or_default()
-                );
-              }
-            };
-          }));
-          writer.send(result).expect("Failed to send result back to subcribe call");
-        },
-        None => {}
+      if let Err(e) = throttle(&client.rate_limiter, FFIEndpoint::CancelOrder, 1) {
+        client.risk.sent(&client_order_id, None);
+        client.client_orders.release(&client_order_id);
+        return Err(e);
+      }
+      let (runtime, exchange, client_orders, limiter) = (&mut client.runtime, &client.client, &client.client_orders, &client.rate_limiter);
+      let outcome = runtime.block_on(cancel_replace(exchange, client_orders, limiter, &original, &req, &client_order_id));
+      if outcome.cancel.is_ok() {
+        client.risk.canceled(&original.id);
       }
+      client.risk.sent(&client_order_id, outcome.replacement.as_ref().and_then(|placed| placed.as_ref().ok()));
+      if let Some(Ok(restored)) = &outcome.restore {
+        client.risk.track(restored);
+      }
+      let leg = |order: Option<Result<Order, OpenlimitsSharpError>>, out: Out<FFIOrder>| match order {
+        None => (false, ok()),
+        Some(Ok(order)) => {
+          (*out) = order_to_ffi(order);
+          (true, ok())
+        },
+        Some(Err(e)) => (true, result_to_ffi(Err(e)))
+      };
+      let (replacement_sent, replacement) = leg(outcome.replacement, replacement_order);
+      let (restore_sent, restore) = leg(outcome.restore, restored_order);
+      (*result) = FFIAmendResult {
+        native: false,
+        cancel: match outcome.cancel {
+          Ok(()) => ok(),
+          Err(e) => openlimits_error_to_ffi(&e)
+        },
+        replacement_sent,
+        replacement,
+        restore_sent,
+        restore,
+      };
     }
-    on_disconnet();
-  });
-  
-  unsafe {
-    let r = match  (*client).runtime.block_on(
-      finish_rx
-    ) {
-      Err(error) => Err(OpenlimitsSharpError::InitializeException(format!("Failed while waiting for subscription thread to intialize: {}", error.to_string()))),
-      Ok(e) => e
-    };
-
-    *sub_handle = Box::into_raw(Box::new(sub_request_tx));
-    
-    result_to_ffi(r)
-  }
+    Ok(())
+  };
+  result_to_ffi(call())
 }
 
+#[no_mangle]
+pub extern "cdecl" fn get_order_by_client_id(
+  client: *mut ExchangeClient,
+  client_order_id: *mut c_char,
+  result: Out<FFIOrder>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    let client_order_id = c_str_to_string(client_order_id).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse client_order_id string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    unsafe {
+      let (id, market_pair) = (*client).client_orders.resolve(&client_order_id)?;
+      let req = GetOrderRequest {
+        id,
+        market_pair: Some(market_pair)
+      };
+      let mut order = fetch_order(&mut *client, &req)?;
+      order.client_order_id = Some(client_order_id);
+      (*result) = order_to_ffi(order);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
 
 #[no_mangle]
-pub extern fn free_string(s: *mut c_char) {
+pub  extern "cdecl" fn cancel_order_by_client_id(
+  client: *mut ExchangeClient,
+  client_order_id: *mut c_char,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    let client_order_id = c_str_to_string(client_order_id).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse client_order_id string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
     unsafe {
-        if s.is_null() { return }
-        CString::from_raw(s)
-    };
+      let (id, market_pair) = (*client).client_orders.resolve(&client_order_id)?;
+      if (*client).dry_run.simulates_cancel(&id) {
+        (*client).dry_run.cancel(&id);
+        return Ok(());
+      }
+      throttle(&(*client).rate_limiter, FFIEndpoint::CancelOrder, 1)?;
+      (*client).runtime.block_on(
+        (*client).client.cancel_order(&CancelOrderRequest {
+          id: id.clone(),
+          market_pair: Some(market_pair)
+        })
+      )?;
+      (*client).risk.canceled(&id);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
 }
 
+// Releases a client order id, e.g. after checking open orders once a placement failed without telling
+// whether the exchange accepted it. The next place_order with the id sends a new order.
 #[no_mangle]
-pub  extern "cdecl" fn subscribe_orderbook(
+pub  extern "cdecl" fn forget_client_order_id(
   client: *mut ExchangeClient,
-  channel: *mut tokio::sync::mpsc::UnboundedSender<SubthreadCmd>,
-  market: *mut c_char,
+  client_order_id: *mut c_char,
 ) -> OpenLimitsResult {
   let call = move|| -> Result<(), OpenlimitsSharpError> {
-    if channel.is_null() {
-      return Err(OpenlimitsSharpError::InvalidArgument(String::from("channel is null")));
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
     }
-    let market_pair = c_str_to_string(market).map_err(|e|
-      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    let client_order_id = c_str_to_string(client_order_id).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse client_order_id string. Invalid character on pos {}", e.valid_up_to()))
     )?;
+    unsafe {
+      (*client).client_orders.forget(&client_order_id)
+    }
+  };
+  result_to_ffi(call())
+}
 
-    let (finish_tx, finish_rx) = tokio::sync::oneshot::channel::<SubResult>();
+#[no_mangle]
+pub  extern "cdecl" fn receive_pairs(
+  client: *mut ExchangeClient,
+  buff: *mut FFIMarketPair, buff_len: usize, actual_buff_len: Out<usize>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
     unsafe {
-      (*channel).send(
-        SubthreadCmd::Sub(Subscription::OrderBookUpdates(
-          market_pair,
-        ), finish_tx)
-      ).map_err(|_| OpenlimitsSharpError::SubscribeException(String::from("failed to send subscription to handler")))?;
+      let pairs = with_retry((*client).retry_policy, || {
+        throttle(&(*client).rate_limiter, FFIEndpoint::MarketPairs, 1)?;
+        Ok((*client).runtime.block_on((*client).client.retrieve_pairs())?)
+      })?;
 
-      let result = (*client).runtime.block_on(finish_rx).map_err(|_| OpenlimitsSharpError::SubscribeException(String::from("failed to get subscription result from handler")))?;
+      let pairs_buff = std::slice::from_raw_parts_mut::<FFIMarketPair>(buff, buff_len);
+      let pairs_ffi: Vec<FFIMarketPair> = pairs.into_iter().map(market_pair_to_ffi).collect();
+      let l = std::cmp::min(buff_len, pairs_ffi.len());
 
-      match result {
-        Ok(_) => Ok(()),
-        Err(e) => Err(OpenlimitsSharpError::OpenLimitsError(e))
-      }
+      pairs_buff[0..l].copy_from_slice(&pairs_ffi[0..l]);
+      (*actual_buff_len) = l;
     }
+    Ok(())
   };
   result_to_ffi(call())
 }
 
+// How place_order, place_orders, place_stop_order and amend_order check prices and sizes against
+// the market's increments and minimums before sending. Reject is the default.
 #[no_mangle]
-pub  extern "cdecl" fn subscribe_trades(
+pub extern "cdecl" fn set_order_validation(client: *mut ExchangeClient, mode: FFIValidationMode) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    unsafe {
+      (*client).market_rules.mode = mode;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Checks or rounds an order's size and price like placing it with `mode` would, without sending it.
+// `price` is null for market orders, in which case `normalized_price` is set to null.
+#[no_mangle]
+pub extern "cdecl" fn normalize_order(
   client: *mut ExchangeClient,
-  channel: *mut tokio::sync::mpsc::UnboundedSender<SubthreadCmd>,
-  market: *mut c_char
+  market: *mut c_char,
+  side: FFISide,
+  qty: *mut c_char,
+  price: *mut c_char,
+  mode: FFIValidationMode,
+  normalized_qty: Out<*mut c_char>,
+  normalized_price: Out<*mut c_char>
 ) -> OpenLimitsResult {
   let call = move|| -> Result<(), OpenlimitsSharpError> {
-    if channel.is_null() {
-      return Err(OpenlimitsSharpError::InvalidArgument(String::from("channel is null")));
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
     }
-    let market_pair = c_str_to_string(market).map_err(|e|
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let market = c_str_to_string(market).map_err(|e|
       OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
     )?;
-    let (finish_tx, finish_rx) = tokio::sync::oneshot::channel::<SubResult>();
+    let size = decimal_from_c_str(qty, "size")?;
+    let price = match price.is_null() {
+      true => None,
+      false => Some(decimal_from_c_str(price, "price")?)
+    };
+    unsafe {
+      let pair = match market_pair(&mut *client, &market)? {
+        Some(pair) => pair,
+        None => return Err(OpenlimitsSharpError::InvalidArgument(format!("Unknown market {}", market)))
+      };
+      let (size, price) = normalize(pair, mode, side, size, price).map_err(OpenlimitsSharpError::InvalidArgument)?;
+      (*normalized_qty) = string_to_c_str(size.to_string());
+      (*normalized_price) = match price {
+        Some(price) => string_to_c_str(price.to_string()),
+        None => std::ptr::null_mut()
+      };
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Replaces the client's risk limits, every limit is off at 0
+#[no_mangle]
+pub extern "cdecl" fn set_risk_limits(client: *mut ExchangeClient, limits: FFIRiskLimits) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    unsafe {
+      (*client).risk.set_limits(limits);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
 
+// Limits the absolute balance of `asset` that orders of the client may lead to, counting its working
+// orders. A null `max_position` removes the limit.
+#[no_mangle]
+pub extern "cdecl" fn set_position_limit(client: *mut ExchangeClient, asset: *mut c_char, max_position: *mut c_char) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    if asset.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("asset is null")));
+    }
+    let asset = c_str_to_string(asset).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse asset string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let max_position = match max_position.is_null() {
+      true => None,
+      false => Some(decimal_from_c_str(max_position, "max_position")?)
+    };
     unsafe {
-      (*channel).send(
-        SubthreadCmd::Sub(Subscription::Trades(
-          market_pair,
-        ), finish_tx)
-      ).map_err(|_| OpenlimitsSharpError::SubscribeException(String::from("failed to send subscription to handler")))?;
-      
-      let result = (*client).runtime.block_on(finish_rx).map_err(|_| OpenlimitsSharpError::SubscribeException(String::from("failed to get subscription result from handler")))?;
+      (*client).risk.set_position_limit(&asset, max_position);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Cancels every open order of a registered client's account with a REST client and runtime of its own
+fn kill_switch_cancel(registered: RegisteredClient) -> Result<(), OpenlimitsSharpError> {
+  let mut runtime = tokio::runtime::Builder::new().basic_scheduler().enable_all().build().map_err(|_| OpenlimitsSharpError::InitializeException(String::from("Failed to start tokio runtime")))?;
+  let exchange = runtime.block_on(OpenLimits::instantiate(registered.init_params))?;
+  throttle(&registered.limiter, FFIEndpoint::CancelAllOrders, 1)?;
+  runtime.block_on(exchange.cancel_all_orders(&CancelAllOrdersRequest {
+    market_pair: None
+  }))?;
+  Ok(())
+}
 
-      match result {
-        Ok(_) => Ok(()),
-        Err(e) => Err(OpenlimitsSharpError::OpenLimitsError(e))
+// Blocks new orders on every client and cancels all open orders of every client. `results` gets the
+// outcome of cancel_all_orders per client in the order the clients were initialized. The clients
+// themselves are left alone, so they may be in use on other threads: every account is reached from
+// a thread of its own with a new REST client, and risk engines reload their open orders afterwards.
+#[no_mangle]
+pub extern "cdecl" fn engage_kill_switch(
+  results: *mut OpenLimitsResult, buff_len: usize, actual_buff_len: Out<usize>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    set_kill_switch(true);
+    let cancels: Vec<_> = registered_clients().into_iter()
+      .map(|registered| std::thread::spawn(move || kill_switch_cancel(registered)))
+      .collect();
+    unsafe {
+      (*actual_buff_len) = 0;
+      for (i, cancel) in cancels.into_iter().enumerate() {
+        let canceled = cancel.join().unwrap_or_else(|_|
+          Err(OpenlimitsSharpError::InitializeException(String::from("Kill switch thread panicked")))
+        );
+        if i < buff_len && !results.is_null() {
+          *results.add(i) = result_to_ffi(canceled);
+          (*actual_buff_len) = i + 1;
+        }
       }
     }
+    Ok(())
   };
   result_to_ffi(call())
 }
 
+// Lets clients place orders again, orders canceled by the kill switch stay canceled
 #[no_mangle]
-pub  extern "cdecl" fn disconnect(
-  channel: *mut tokio::sync::mpsc::UnboundedSender<SubthreadCmd>,
-) {
-  unsafe {
-    let res = (*channel).send(
-      SubthreadCmd::Disconnect
+pub extern "cdecl" fn release_kill_switch() -> OpenLimitsResult {
+  set_kill_switch(false);
+  result_to_ffi(Ok(()))
+}
+
+#[no_mangle]
+pub extern "cdecl" fn get_kill_switch(engaged: Out<bool>) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    unsafe {
+      (*engaged) = kill_switch_engaged();
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Replaces the client's retry policy. `retryable` has bit `1 << tag` set for every OpenLimitsResultTag
+// to retry. Only read-only calls are retried, placements are sent once.
+#[no_mangle]
+pub extern "cdecl" fn set_retry_policy(client: *mut ExchangeClient, policy: FFIRetryPolicy, retryable: u64) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    unsafe {
+      (*client).retry_policy = RetryPolicy::new(policy, retryable);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Turns dry run on or off. In dry run placements and cancels are validated and risk checked but
+// not sent; they are reported to `on_simulated`, which may only be null when turning dry run off,
+// and placements return orders marked as simulated. Simulated orders can still be looked up and canceled afterwards.
+#[no_mangle]
+pub extern "cdecl" fn set_dry_run(
+  client: *mut ExchangeClient,
+  enabled: bool,
+  on_simulated: Option<DryRunCallback>,
+  user_data: *mut c_void
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    if enabled && on_simulated.is_none() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("on_simulated is null")));
+    }
+    unsafe {
+      (*client).dry_run.set(enabled, on_simulated.map(|callback| (callback, UserData(user_data))));
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Replaces the request and order budgets the client starts with, those of its exchange
+#[no_mangle]
+pub extern "cdecl" fn set_rate_limits(client: *mut ExchangeClient, limits: FFIRateLimits) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    unsafe {
+      (*client).rate_limiter.set_limits(limits);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub extern "cdecl" fn get_rate_limits(client: *mut ExchangeClient, limits: Out<FFIRateLimits>) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    unsafe {
+      (*limits) = (*client).rate_limiter.limits();
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Overrides how much of the request budget a call to `endpoint` takes
+#[no_mangle]
+pub extern "cdecl" fn set_request_weight(client: *mut ExchangeClient, endpoint: FFIEndpoint, weight: u32) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    unsafe {
+      (*client).rate_limiter.set_weight(endpoint, weight);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub extern "cdecl" fn get_rate_limit_usage(client: *mut ExchangeClient, usage: Out<FFIRateLimitUsage>) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    unsafe {
+      (*usage) = (*client).rate_limiter.usage();
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[derive(Copy, Clone)]
+struct SubthreadCallbacks {
+  on_disconnet: extern fn(),
+  on_connection_state: extern fn(state: FFIConnectionState, attempt: u32),
+}
+
+type SharedJournal = Arc<Mutex<Option<JournalWriter>>>;
+
+// Returned by init_subscriptions. Subscriptions are sent through `channel`, events without a callback end up in `events`.
+pub struct SubthreadHandle {
+  channel: tokio::sync::mpsc::UnboundedSender<SubthreadCmd>,
+  events: Arc<EventQueue>,
+  sources: Arc<Mutex<HashMap<u64, Arc<EventSource>>>>,
+  journal: SharedJournal,
+}
+
+// A subscription registered on the subscription thread, replayed after every reconnect
+#[derive(Clone)]
+struct ActiveSubscription {
+  request: SubscriptionRequest,
+  source: Arc<EventSource>,
+  conflator: Option<Arc<Mutex<Conflator>>>,
+  aggregate: Option<Arc<Mutex<Aggregate>>>,
+}
+
+impl ActiveSubscription {
+  fn new(id: u64, request: SubscriptionRequest) -> Self {
+    let market = request.market.clone();
+    let kind = match request.stream {
+      StreamKind::OrderBook => FFISubscriptionKind::OrderBook,
+      StreamKind::Trades => FFISubscriptionKind::Trades,
+      StreamKind::Candles(_) => FFISubscriptionKind::Candles,
+      StreamKind::Ticker => FFISubscriptionKind::Ticker,
+      StreamKind::Account(AccountStream::Orders, _) => FFISubscriptionKind::AccountOrders,
+      StreamKind::Account(AccountStream::Trades, _) => FFISubscriptionKind::AccountTrades,
+      StreamKind::Account(AccountStream::Balances, _) => FFISubscriptionKind::AccountBalances,
+    };
+    let aggregate = match request.stream {
+      StreamKind::Candles(interval) => Some(Arc::new(Mutex::new(Aggregate::candles(interval)))),
+      StreamKind::Ticker => Some(Arc::new(Mutex::new(Aggregate::ticker()))),
+      _ => None
+    };
+    let source = Arc::new(EventSource::new(
+      id,
+      kind,
+      CString::new(market).expect("Failed to create CString!"),
+      request.user_data,
+      request.delivery
+    ));
+    let conflator = match request.delivery.mode {
+      FFIDeliveryMode::Conflate => Some(Arc::new(Mutex::new(Conflator::default()))),
+      _ => None
+    };
+    ActiveSubscription {
+      request,
+      source,
+      conflator,
+      aggregate,
+    }
+  }
+
+  fn id(&self) -> u64 {
+    self.source.id
+  }
+
+  fn deliver(&self, events: &EventQueue, kind: FFIWsEventKind, payload: EventPayload, received: ReceiveTime) {
+    self.source.record_latency(received, &payload);
+    let payload = match &self.conflator {
+      Some(conflator) => match conflator.lock().expect("Conflator lock poisoned").merge(kind, payload, received) {
+        Some(payload) => payload,
+        None => return
+      },
+      None => payload
+    };
+    self.emit(events, kind, payload, received, 0);
+  }
+
+  // Market data arrived, reports the feed as resumed if it was stale
+  fn heartbeat(&self, events: &EventQueue, received: ReceiveTime) {
+    if let Some(silent_ms) = self.source.touch(received) {
+      self.emit(events, FFIWsEventKind::Resumed, EventPayload::Stale(silent_ms), received, 0);
+    }
+  }
+
+  fn flush(&self, events: &EventQueue) {
+    let pending = match &self.conflator {
+      Some(conflator) => conflator.lock().expect("Conflator lock poisoned").take(),
+      None => return
+    };
+    for (kind, payload, received, conflated) in pending {
+      self.emit(events, kind, payload, received, conflated);
+    }
+  }
+
+  // Hands the event to the subscription callback, or queues it when the subscription has none
+  fn emit(&self, events: &EventQueue, kind: FFIWsEventKind, payload: EventPayload, received: ReceiveTime, conflated: u64) {
+    self.source.add_conflated(conflated);
+    let event = Event {
+      kind,
+      source: self.source.clone(),
+      payload,
+      seq: self.source.admit(),
+      conflated,
+      received,
+    };
+    match self.request.callback {
+      Some(callback) => {
+        let ffi_event = event.to_ffi(0);
+        callback(&ffi_event);
+        unsafe { ffi_event.free() };
+      },
+      None => events.push(event)
+    }
+  }
+}
+
+// Errors that mean the socket itself is gone, as opposed to a single message failing to parse
+fn is_connection_error(e: &OpenLimitsError) -> bool {
+  match e {
+    OpenLimitsError::Tungstenite(_) => true,
+    OpenLimitsError::SocketError() => true,
+    OpenLimitsError::IoError(_) => true,
+    _ => false
+  }
+}
+
+const STALE_CHECK_MS: u64 = 100;
+
+// Hands a message to the journal if one is running, write failures are reported on the subscription
+fn journal_message<F>(journal: &SharedJournal, active: &ActiveSubscription, events: &EventQueue, received: ReceiveTime, record: F)
+  where F: FnOnce(&mut JournalWriter, &str) -> std::io::Result<()> {
+  let result = match journal.lock().expect("Journal lock poisoned").as_mut() {
+    Some(writer) => record(writer, &active.request.market),
+    None => return
+  };
+  if let Err(e) = result {
+    active.deliver(
+      events,
+      FFIWsEventKind::Error,
+      EventPayload::Error(OpenLimitsResultTag::IoError, format!("Failed to write journal: {}", e)),
+      received
     );
-    res.map_err(|_| "Send error").expect("Failed to disconnect");
   }
-}
\ No newline at end of file
+}
+
+fn next_backoff(policy: &FFIReconnectPolicy, backoff: u64) -> u64 {
+  let max_backoff = std::cmp::max(policy.initial_backoff_ms, policy.max_backoff_ms);
+  std::cmp::min(backoff.saturating_mul(2), max_backoff)
+}
+
+struct Subthread {
+  rt: tokio::runtime::Runtime,
+  client: OpenLimitsWs<AnyWsExchange>,
+  init_params: InitAnyExchange,
+  policy: FFIReconnectPolicy,
+  callbacks: SubthreadCallbacks,
+  // REST client for polled account streams, created with the first one
+  exchange: Option<Arc<AnyExchange>>,
+  rate_limiter: Arc<RateLimiter>,
+  events: Arc<EventQueue>,
+  sources: Arc<Mutex<HashMap<u64, Arc<EventSource>>>>,
+  journal: SharedJournal,
+  commands: tokio::sync::mpsc::UnboundedSender<SubthreadCmd>,
+  // Bumped on every reconnect attempt so callbacks still registered on an old socket go quiet
+  connection: Arc<AtomicU64>,
+  subscriptions: Vec<ActiveSubscription>,
+  next_subscription_id: u64,
+}
+
+impl Subthread {
+  fn subscribe(&mut self, active: &ActiveSubscription) -> Result<(), OpenLimitsError> {
+    let sub = match active.request.ws_subscription() {
+      Some(sub) => sub,
+      None => return Ok(())
+    };
+    let events = self.events.clone();
+    let commands = self.commands.clone();
+    let active_connection = self.connection.clone();
+    let journal = self.journal.clone();
+    let connection = self.connection.load(Ordering::SeqCst);
+    // Resubscribing leaves the previous stream running, only the latest one delivers
+    let generation = active.source.next_generation();
+    let active = active.clone();
+    let (ended, ended_commands, ended_connection) = (active.clone(), commands.clone(), active_connection.clone());
+
+    let handle = move |resp: &Result<WebSocketResponse<AnyWsResponse>, OpenLimitsError>| {
+      if active_connection.load(Ordering::SeqCst) != connection || !active.source.is_generation(generation) {
+        return;
+      }
+      let received = ReceiveTime::now();
+      let resp = match resp {
+        Ok(e) => e,
+        Err(e) => {
+          let (tag, message) = openlimits_error_parts(e);
+          active.deliver(&events, FFIWsEventKind::Error, EventPayload::Error(tag, message), received);
+          if is_connection_error(e) {
+            commands.send(SubthreadCmd::Reconnect(connection)).ok();
+          }
+          return
+        }
+      };
+      let resp = match resp {
+        WebSocketResponse::Generic(msg) => msg,
+        raw => {
+          journal_message(&journal, &active, &events, received, |writer, market| {
+            match writer.keeps_unsupported() {
+              true => writer.record_unsupported(received.wall_ms, market, active.request.journal_stream(), &format!("{:?}", raw)),
+              false => Ok(())
+            }
+          });
+          active.deliver(
+            &events,
+            FFIWsEventKind::Unsupported,
+            EventPayload::Error(
+              OpenLimitsResultTag::WebSocketMessageNotSupported,
+              String::from("Exchange specific message is not supported by the generic stream")
+            ),
+            received
+          );
+          return;
+        }
+      };
+
+      match resp {
+        OpenLimitsWebSocketMessage::Ping => {
+          active.deliver(&events, FFIWsEventKind::Ping, EventPayload::Empty, received);
+        },
+        OpenLimitsWebSocketMessage::Trades(trades) => {
+          active.heartbeat(&events, received);
+          journal_message(&journal, &active, &events, received, |writer, market| writer.record_trades(received.wall_ms, market, trades));
+          active.source.record_trade_stats(received, trades);
+          match &active.request.sink {
+            Some(Sink::Tape(tape)) => {
+              tape.push(trades);
+              return;
+            },
+            Some(Sink::Emulator(emulator)) => {
+              emulator.push(trades);
+              return;
+            },
+            _ => {}
+          };
+          match &active.aggregate {
+            Some(aggregate) => {
+              let updates = aggregate.lock().expect("Aggregate lock poisoned").update(trades);
+              for (kind, payload) in updates {
+                active.deliver(&events, kind, payload, received);
+              }
+            },
+            None => active.deliver(&events, FFIWsEventKind::Trades, EventPayload::Trades(trades.clone()), received)
+          };
+        },
+        OpenLimitsWebSocketMessage::OrderBook(resp) => {
+          active.heartbeat(&events, received);
+          journal_message(&journal, &active, &events, received, |writer, market| writer.record_book(received.wall_ms, market, true, resp));
+          if let Some((analytics, depth)) = active.source.apply_book_analytics(true, resp) {
+            active.emit(&events, FFIWsEventKind::BookAnalytics, EventPayload::BookAnalytics(analytics, depth), received, 0);
+          }
+          match &active.request.sink {
+            Some(Sink::Book(book)) => book.apply(true, resp),
+            _ => active.deliver(&events, FFIWsEventKind::OrderBookSnapshot, EventPayload::OrderBook(resp.clone()), received)
+          };
+        },
+        OpenLimitsWebSocketMessage::OrderBookDiff(resp) => {
+          active.heartbeat(&events, received);
+          journal_message(&journal, &active, &events, received, |writer, market| writer.record_book(received.wall_ms, market, false, resp));
+          if let Some((analytics, depth)) = active.source.apply_book_analytics(false, resp) {
+            active.emit(&events, FFIWsEventKind::BookAnalytics, EventPayload::BookAnalytics(analytics, depth), received, 0);
+          }
+          match &active.request.sink {
+            Some(Sink::Book(book)) => book.apply(false, resp),
+            _ => active.deliver(&events, FFIWsEventKind::OrderBookDiff, EventPayload::OrderBook(resp.clone()), received)
+          };
+        }
+      };
+    };
+
+    let mut stream = self.rt.block_on(self.client.create_stream(&[sub]))?;
+    self.rt.spawn(async move {
+      while let Some(resp) = stream.next().await {
+        handle(&resp);
+      }
+      // An exchange closing the socket cleanly ends the stream without an error
+      if ended_connection.load(Ordering::SeqCst) == connection && ended.source.is_generation(generation) {
+        ended_commands.send(SubthreadCmd::Reconnect(connection)).ok();
+      }
+    });
+    Ok(())
+  }
+
+  // Polls an account stream until the subscription thread stops. The first poll runs right away
+  // so missing credentials fail the subscribe call instead of showing up as error events.
+  fn start_polling(&mut self, active: &ActiveSubscription) -> Result<(), OpenlimitsSharpError> {
+    let (stream, poll_interval_ms) = match active.request.stream {
+      StreamKind::Account(stream, poll_interval_ms) => (stream, poll_interval_ms),
+      _ => return Ok(())
+    };
+    let exchange = match &self.exchange {
+      Some(exchange) => exchange.clone(),
+      None => {
+        let exchange = Arc::new(self.rt.block_on(OpenLimits::instantiate(self.init_params.clone()))?);
+        self.exchange = Some(exchange.clone());
+        exchange
+      }
+    };
+    let market = match active.request.market.as_str() {
+      "" => None,
+      market => Some(String::from(market))
+    };
+    let mut poller = AccountPoller::new(stream, market, self.rate_limiter.clone());
+    let received = ReceiveTime::now();
+    for (kind, payload) in self.rt.block_on(poller.poll(&exchange))? {
+      active.deliver(&self.events, kind, payload, received);
+    }
+
+    let events = self.events.clone();
+    let active = active.clone();
+    let period = std::time::Duration::from_millis(std::cmp::max(poll_interval_ms, 1));
+    self.rt.spawn(async move {
+      let mut interval = tokio::time::interval(period);
+      interval.tick().await;
+      loop {
+        interval.tick().await;
+        let received = ReceiveTime::now();
+        match poller.poll(&exchange).await {
+          Ok(updates) => {
+            active.heartbeat(&events, received);
+            for (kind, payload) in updates {
+              active.deliver(&events, kind, payload, received);
+            }
+          },
+          Err(e) => {
+            let (tag, message) = error_parts(&e);
+            active.deliver(&events, FFIWsEventKind::Error, EventPayload::Error(tag, message), received);
+          }
+        }
+      }
+    });
+    Ok(())
+  }
+
+  // Flushes a conflating subscription every `conflate_ms` for as long as the subscription thread runs
+  fn start_flushing(&mut self, active: &ActiveSubscription) {
+    if active.conflator.is_none() {
+      return;
+    }
+    let events = self.events.clone();
+    let active = active.clone();
+    let period = std::time::Duration::from_millis(std::cmp::max(active.request.delivery.conflate_ms, 1));
+    self.rt.spawn(async move {
+      let mut interval = tokio::time::interval(period);
+      loop {
+        interval.tick().await;
+        active.flush(&events);
+      }
+    });
+  }
+
+  // Checks the subscription for staleness and pushes due trade statistics and book analytics every STALE_CHECK_MS
+  // for as long as the subscription thread runs
+  fn start_watchdog(&mut self, active: &ActiveSubscription) {
+    let events = self.events.clone();
+    let commands = self.commands.clone();
+    let connection = self.connection.clone();
+    let active = active.clone();
+    self.rt.spawn(async move {
+      let mut interval = tokio::time::interval(std::time::Duration::from_millis(STALE_CHECK_MS));
+      loop {
+        interval.tick().await;
+        let now = ReceiveTime::now();
+        if let Some(stats) = active.source.due_trade_stats(now) {
+          active.emit(&events, FFIWsEventKind::TradeStats, EventPayload::TradeStats(stats), now, 0);
+        }
+        if let Some((analytics, depth)) = active.source.due_book_analytics(now) {
+          active.emit(&events, FFIWsEventKind::BookAnalytics, EventPayload::BookAnalytics(analytics, depth), now, 0);
+        }
+        let (policy, silent_ms) = match active.source.check_stale(now) {
+          Some(stale) => stale,
+          None => continue
+        };
+        active.emit(&events, FFIWsEventKind::Stale, EventPayload::Stale(silent_ms), now, 0);
+        match policy.action {
+          FFIStaleAction::Notify => {},
+          FFIStaleAction::Resubscribe => {
+            commands.send(SubthreadCmd::Resubscribe(active.id())).ok();
+          },
+          FFIStaleAction::Reconnect => {
+            commands.send(SubthreadCmd::Reconnect(connection.load(Ordering::SeqCst))).ok();
+          }
+        }
+      }
+    });
+  }
+
+  fn resubscribe(&mut self, id: u64) {
+    let active = match self.subscriptions.iter().find(|active| active.id() == id) {
+      Some(active) => active.clone(),
+      None => return
+    };
+    if let Err(e) = self.subscribe(&active) {
+      let (tag, message) = openlimits_error_parts(&e);
+      active.deliver(&self.events, FFIWsEventKind::Error, EventPayload::Error(tag, message), ReceiveTime::now());
+      if is_connection_error(&e) {
+        self.commands.send(SubthreadCmd::Reconnect(self.connection.load(Ordering::SeqCst))).ok();
+      }
+    }
+  }
+
+  // Waits out the backoff, keeping any commands that arrive meanwhile. Returns true if a disconnect was requested.
+  fn wait_backoff(
+    &mut self,
+    backoff: u64,
+    sub_rx: &mut tokio::sync::mpsc::UnboundedReceiver<SubthreadCmd>,
+    deferred: &mut VecDeque<SubthreadCmd>
+  ) -> bool {
+    self.rt.block_on(async {
+      let delay = tokio::time::delay_for(std::time::Duration::from_millis(backoff));
+      tokio::pin!(delay);
+      loop {
+        tokio::select! {
+          _ = &mut delay => return false,
+          cmd = sub_rx.recv() => match cmd {
+            Some(SubthreadCmd::Disconnect) => return true,
+            Some(cmd) => deferred.push_back(cmd),
+            None => return false
+          }
+        }
+      }
+    })
+  }
+
+  // Reconnects with exponential backoff and replays every active subscription.
+  // Returns false if the policy gave up or a disconnect was requested while waiting.
+  fn reconnect(
+    &mut self,
+    sub_rx: &mut tokio::sync::mpsc::UnboundedReceiver<SubthreadCmd>,
+    deferred: &mut VecDeque<SubthreadCmd>
+  ) -> bool {
+    let mut attempt: u32 = 0;
+    let mut backoff = self.policy.initial_backoff_ms;
+    loop {
+      if self.policy.max_attempts != 0 && attempt >= self.policy.max_attempts {
+        (self.callbacks.on_connection_state)(FFIConnectionState::GaveUp, attempt);
+        return false;
+      }
+      attempt += 1;
+      self.connection.fetch_add(1, Ordering::SeqCst);
+      (self.callbacks.on_connection_state)(FFIConnectionState::Reconnecting, attempt);
+
+      if self.wait_backoff(backoff, sub_rx, deferred) {
+        return false;
+      }
+      backoff = next_backoff(&self.policy, backoff);
+
+      self.client = match self.rt.block_on(OpenLimitsWs::instantiate(self.init_params.clone())) {
+        Ok(client) => client,
+        Err(_) => continue
+      };
+
+      let subscriptions = self.subscriptions.clone();
+      if subscriptions.iter().all(|active| self.subscribe(active).is_ok()) {
+        (self.callbacks.on_connection_state)(FFIConnectionState::Connected, attempt);
+        return true;
+      }
+    }
+  }
+}
+
+#[no_mangle]
+#[allow(unsafe_code)]
+pub  extern "cdecl" fn init_subscriptions(
+  client: *mut ExchangeClient,
+  on_disconnet: extern fn(),
+  on_connection_state: extern fn(state: FFIConnectionState, attempt: u32),
+  reconnect_policy: FFIReconnectPolicy,
+  event_queue_capacity: usize,
+  sub_handle: Out<*mut SubthreadHandle>
+) ->  OpenLimitsResult {
+  let (sub_request_tx, mut sub_rx) = tokio::sync::mpsc::unbounded_channel::<SubthreadCmd>();
+
+  let (init_params, rate_limiter) = unsafe {
+    ((*client).init_params.clone(), (*client).rate_limiter.clone())
+  };
+  let (finish_tx, finish_rx) = tokio::sync::oneshot::channel::<Result<(), OpenlimitsSharpError>>();
+  let callbacks = SubthreadCallbacks {
+    on_disconnet,
+    on_connection_state,
+  };
+  let events = Arc::new(EventQueue::new(event_queue_capacity));
+  let thread_events = events.clone();
+  let sources = Arc::new(Mutex::new(HashMap::new()));
+  let thread_sources = sources.clone();
+  let journal: SharedJournal = Arc::new(Mutex::new(None));
+  let thread_journal = journal.clone();
+  let commands = sub_request_tx.clone();
+
+  std::thread::spawn(move || {
+    let call = move|| -> Result<Subthread, OpenlimitsSharpError> {
+      let mut rt = tokio::runtime::Builder::new()
+                .basic_scheduler()
+                .enable_all()
+                .build()
+                .map_err(|_| OpenlimitsSharpError::InitializeException(String::from("Failed to start tokio runtime")))?;
+      (callbacks.on_connection_state)(FFIConnectionState::Connecting, 0);
+      let client: OpenLimitsWs<AnyWsExchange> = rt.block_on(OpenLimitsWs::instantiate(init_params.clone()))?;
+
+      Ok(Subthread {
+        rt,
+        client,
+        init_params,
+        policy: reconnect_policy,
+        callbacks,
+        exchange: None,
+        rate_limiter,
+        events: thread_events,
+        sources: thread_sources,
+        journal: thread_journal,
+        commands,
+        connection: Arc::new(AtomicU64::new(0)),
+        subscriptions: Vec::new(),
+        next_subscription_id: 0,
+      })
+    };
+
+    let mut subthread = match call() {
+      Ok(e) => e,
+      Err(e) => {
+        finish_tx.send(Err(e)).expect("Failed to communicate result back to main thread");
+        return;
+      }
+    };
+    finish_tx.send(Ok(())).expect("Failed to communicate result back to main thread");
+    (callbacks.on_connection_state)(FFIConnectionState::Connected, 0);
+
+    // Commands received while waiting out a reconnect backoff
+    let mut deferred: VecDeque<SubthreadCmd> = VecDeque::new();
+    loop { 
+      let thread_cmd = match deferred.pop_front() {
+        Some(cmd) => Some(cmd),
+        None => subthread.rt.block_on(sub_rx.next())
+      };
+      match thread_cmd {
+        Some(SubthreadCmd::Disconnect) => {
+          break;
+        },
+        Some(SubthreadCmd::Sub(request, writer)) => {
+          subthread.next_subscription_id += 1;
+          let active = ActiveSubscription::new(subthread.next_subscription_id, request);
+          let result = match subthread.subscribe(&active) {
+            Ok(_) => subthread.start_polling(&active).map(|_| active.id()),
+            Err(e) => Err(OpenlimitsSharpError::from(e))
+          };
+          if result.is_ok() {
+            subthread.start_flushing(&active);
+            subthread.start_watchdog(&active);
+            subthread.sources.lock().expect("Subscription sources lock poisoned").insert(active.id(), active.source.clone());
+            subthread.subscriptions.push(active);
+          }
+          writer.send(result).expect("Failed to send result back to subcribe call");
+        },
+        Some(SubthreadCmd::Resubscribe(id)) => {
+          subthread.resubscribe(id);
+        },
+        Some(SubthreadCmd::Reconnect(connection)) => {
+          if connection != subthread.connection.load(Ordering::SeqCst) {
+            continue;
+          }
+          if !subthread.reconnect(&mut sub_rx, &mut deferred) {
+            break;
+          }
+        },
+        None => {}
+      }
+    }
+    subthread.journal.lock().expect("Journal lock poisoned").take();
+    subthread.events.close();
+    (callbacks.on_disconnet)();
+  });
+  
+  unsafe {
+    let r = match  (*client).runtime.block_on(
+      finish_rx
+    ) {
+      Err(error) => Err(OpenlimitsSharpError::InitializeException(format!("Failed while waiting for subscription thread to intialize: {}", error.to_string()))),
+      Ok(e) => e
+    };
+
+    *sub_handle = Box::into_raw(Box::new(SubthreadHandle {
+      channel: sub_request_tx,
+      events,
+      sources,
+      journal,
+    }));
+    
+    result_to_ffi(r)
+  }
+}
+
+
+#[no_mangle]
+pub extern fn free_string(s: *mut c_char) {
+    unsafe {
+        if s.is_null() { return }
+        CString::from_raw(s)
+    };
+}
+
+fn send_subscription(
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: String,
+  stream: StreamKind,
+  callback: Option<EventCallback>,
+  user_data: *mut c_void,
+  delivery: FFIDeliveryPolicy,
+  sink: Option<Sink>,
+  subscription_id: Out<u64>,
+) -> Result<(), OpenlimitsSharpError> {
+  let (finish_tx, finish_rx) = tokio::sync::oneshot::channel::<SubResult>();
+  unsafe {
+    (*sub_handle).channel.send(
+      SubthreadCmd::Sub(SubscriptionRequest {
+        market,
+        stream,
+        callback,
+        user_data: UserData(user_data),
+        delivery,
+        sink,
+      }, finish_tx)
+    ).map_err(|_| OpenlimitsSharpError::SubscribeException(String::from("failed to send subscription to handler")))?;
+
+    let result = (*client).runtime.block_on(finish_rx).map_err(|_| OpenlimitsSharpError::SubscribeException(String::from("failed to get subscription result from handler")))?;
+
+    match result {
+      Ok(id) => {
+        (*subscription_id) = id;
+        Ok(())
+      },
+      Err(e) => Err(e)
+    }
+  }
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn subscribe_orderbook(
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  on_event: Option<EventCallback>,
+  user_data: *mut c_void,
+  delivery: FFIDeliveryPolicy,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    send_subscription(client, sub_handle, market_pair, StreamKind::OrderBook, on_event, user_data, delivery, None, subscription_id)
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn subscribe_trades(
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  on_event: Option<EventCallback>,
+  user_data: *mut c_void,
+  delivery: FFIDeliveryPolicy,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    send_subscription(client, sub_handle, market_pair, StreamKind::Trades, on_event, user_data, delivery, None, subscription_id)
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn subscribe_candles(
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  interval: FFIInterval,
+  on_event: Option<EventCallback>,
+  user_data: *mut c_void,
+  delivery: FFIDeliveryPolicy,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    send_subscription(client, sub_handle, market_pair, StreamKind::Candles(interval), on_event, user_data, delivery, None, subscription_id)
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn subscribe_ticker(
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  on_event: Option<EventCallback>,
+  user_data: *mut c_void,
+  delivery: FFIDeliveryPolicy,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    send_subscription(client, sub_handle, market_pair, StreamKind::Ticker, on_event, user_data, delivery, None, subscription_id)
+  };
+  result_to_ffi(call())
+}
+
+// Own order updates for one market, or every market when `market` is null
+#[no_mangle]
+pub  extern "cdecl" fn subscribe_account_orders(
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  poll_interval_ms: u64,
+  on_event: Option<EventCallback>,
+  user_data: *mut c_void,
+  delivery: FFIDeliveryPolicy,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    let market_pair = nullable_cstr(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    send_subscription(client, sub_handle, market_pair.unwrap_or_default(), StreamKind::Account(AccountStream::Orders, poll_interval_ms), on_event, user_data, delivery, None, subscription_id)
+  };
+  result_to_ffi(call())
+}
+
+// Own fills for one market, or every market when `market` is null
+#[no_mangle]
+pub  extern "cdecl" fn subscribe_account_trades(
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  poll_interval_ms: u64,
+  on_event: Option<EventCallback>,
+  user_data: *mut c_void,
+  delivery: FFIDeliveryPolicy,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    let market_pair = nullable_cstr(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    send_subscription(client, sub_handle, market_pair.unwrap_or_default(), StreamKind::Account(AccountStream::Trades, poll_interval_ms), on_event, user_data, delivery, None, subscription_id)
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn subscribe_account_balances(
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  poll_interval_ms: u64,
+  on_event: Option<EventCallback>,
+  user_data: *mut c_void,
+  delivery: FFIDeliveryPolicy,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+
+    send_subscription(client, sub_handle, String::new(), StreamKind::Account(AccountStream::Balances, poll_interval_ms), on_event, user_data, delivery, None, subscription_id)
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn poll_events(
+  sub_handle: *mut SubthreadHandle,
+  events_buff: *mut FFIEvent, events_buff_len: usize,
+  actual_len: Out<usize>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      let out_events = std::slice::from_raw_parts_mut::<FFIEvent>(events_buff, events_buff_len);
+      (*actual_len) = (*sub_handle).events.poll(out_events);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn wait_events(
+  sub_handle: *mut SubthreadHandle,
+  events_buff: *mut FFIEvent, events_buff_len: usize,
+  timeout_ms: u64,
+  actual_len: Out<usize>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      let out_events = std::slice::from_raw_parts_mut::<FFIEvent>(events_buff, events_buff_len);
+      (*actual_len) = (*sub_handle).events.wait(out_events, std::time::Duration::from_millis(timeout_ms));
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub extern "cdecl" fn free_events(events_buff: *mut FFIEvent, len: usize) {
+  if events_buff.is_null() {
+    return;
+  }
+  unsafe {
+    for event in std::slice::from_raw_parts(events_buff, len) {
+      event.free();
+    }
+  }
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn get_latency_stats(
+  sub_handle: *mut SubthreadHandle,
+  subscription_id: u64,
+  out_stats: Out<FFILatencyStats>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      let sources = (*sub_handle).sources.lock().expect("Subscription sources lock poisoned");
+      let source = sources.get(&subscription_id).ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Unknown subscription id {}", subscription_id))
+      )?;
+      (*out_stats) = source.latency_stats();
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Replaces the staleness check of a subscription, the silence window starts over from now
+#[no_mangle]
+pub  extern "cdecl" fn set_stale_policy(
+  sub_handle: *mut SubthreadHandle,
+  subscription_id: u64,
+  policy: FFIStalePolicy
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      let sources = (*sub_handle).sources.lock().expect("Subscription sources lock poisoned");
+      let source = sources.get(&subscription_id).ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Unknown subscription id {}", subscription_id))
+      )?;
+      let policy = match policy.threshold_ms {
+        0 => None,
+        _ => Some(policy)
+      };
+      source.set_stale_policy(policy, ReceiveTime::now());
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Maintains rolling trade statistics for the subscription over each window in `windows_ms`, replacing
+// any earlier windows. They are pushed as TradeStats events every `push_interval_ms` unless it is 0.
+// An empty window list turns the statistics off.
+#[no_mangle]
+pub  extern "cdecl" fn enable_trade_stats(
+  sub_handle: *mut SubthreadHandle,
+  subscription_id: u64,
+  windows_ms: *const u64,
+  windows_len: u64,
+  push_interval_ms: u64
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    if windows_ms.is_null() && windows_len > 0 {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("windows_ms is null")));
+    }
+    unsafe {
+      let windows = match windows_len {
+        0 => Vec::new(),
+        _ => std::slice::from_raw_parts(windows_ms, windows_len as usize).to_vec()
+      };
+      if windows.contains(&0) {
+        return Err(OpenlimitsSharpError::InvalidArgument(String::from("Trade stats windows must be longer than 0 ms")));
+      }
+      let sources = (*sub_handle).sources.lock().expect("Subscription sources lock poisoned");
+      let source = sources.get(&subscription_id).ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Unknown subscription id {}", subscription_id))
+      )?;
+      let stats = match windows.is_empty() {
+        true => None,
+        false => Some(TradeStats::new(windows, push_interval_ms, ReceiveTime::now().wall_ms))
+      };
+      source.set_trade_stats(stats);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Current trade statistics of the subscription, one entry per window in the order they were enabled
+#[no_mangle]
+pub  extern "cdecl" fn get_trade_stats(
+  sub_handle: *mut SubthreadHandle,
+  subscription_id: u64,
+  buff: *mut FFITradeStats, buff_len: u64, actual_buff_len: Out<u64>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      let sources = (*sub_handle).sources.lock().expect("Subscription sources lock poisoned");
+      let source = sources.get(&subscription_id).ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Unknown subscription id {}", subscription_id))
+      )?;
+      let stats = source.trade_stats(ReceiveTime::now()).ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Trade stats are not enabled for subscription {}", subscription_id))
+      )?;
+      let len = std::cmp::min(stats.len(), buff_len as usize);
+      let out = std::slice::from_raw_parts_mut::<FFITradeStats>(buff, buff_len as usize);
+      out[0..len].copy_from_slice(&stats[0..len]);
+      (*actual_buff_len) = len as u64;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Keeps a local order book for the subscription and computes analytics on it, replacing any earlier
+// configuration. Depth is reported within each distance in `distances_bps` of mid. The book starts
+// empty, so enable analytics before the subscription's next snapshot.
+#[no_mangle]
+pub  extern "cdecl" fn enable_book_analytics(
+  sub_handle: *mut SubthreadHandle,
+  subscription_id: u64,
+  config: FFIBookAnalyticsConfig,
+  distances_bps: *const f64,
+  distances_len: u64
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    if distances_bps.is_null() && distances_len > 0 {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("distances_bps is null")));
+    }
+    unsafe {
+      let distances = match distances_len {
+        0 => Vec::new(),
+        _ => std::slice::from_raw_parts(distances_bps, distances_len as usize).to_vec()
+      };
+      if distances.iter().any(|distance| distance.is_nan() || *distance <= 0.0) {
+        return Err(OpenlimitsSharpError::InvalidArgument(String::from("Depth distances must be greater than 0 bps")));
+      }
+      let sources = (*sub_handle).sources.lock().expect("Subscription sources lock poisoned");
+      let source = sources.get(&subscription_id).ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Unknown subscription id {}", subscription_id))
+      )?;
+      if !matches!(source.kind, FFISubscriptionKind::OrderBook) {
+        return Err(OpenlimitsSharpError::InvalidArgument(format!("Subscription {} is not an order book subscription", subscription_id)));
+      }
+      source.set_book_analytics(Some(BookAnalytics::new(config, distances, ReceiveTime::now().wall_ms)));
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn disable_book_analytics(
+  sub_handle: *mut SubthreadHandle,
+  subscription_id: u64
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      let sources = (*sub_handle).sources.lock().expect("Subscription sources lock poisoned");
+      let source = sources.get(&subscription_id).ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Unknown subscription id {}", subscription_id))
+      )?;
+      source.set_book_analytics(None);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Current analytics of the subscription's book, with one depth entry per configured distance
+#[no_mangle]
+pub  extern "cdecl" fn get_book_analytics(
+  sub_handle: *mut SubthreadHandle,
+  subscription_id: u64,
+  out_analytics: Out<FFIBookAnalytics>,
+  depth_buff: *mut FFIDepthAtDistance, depth_buff_len: u64, actual_depth_buff_len: Out<u64>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      let sources = (*sub_handle).sources.lock().expect("Subscription sources lock poisoned");
+      let source = sources.get(&subscription_id).ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Unknown subscription id {}", subscription_id))
+      )?;
+      let (analytics, depth) = source.book_analytics().ok_or_else(||
+        OpenlimitsSharpError::InvalidArgument(format!("Book analytics are not enabled for subscription {}", subscription_id))
+      )?;
+      (*out_analytics) = analytics;
+      let len = std::cmp::min(depth.len(), depth_buff_len as usize);
+      let out = std::slice::from_raw_parts_mut::<FFIDepthAtDistance>(depth_buff, depth_buff_len as usize);
+      out[0..len].copy_from_slice(&depth[0..len]);
+      (*actual_depth_buff_len) = len as u64;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+fn exchange_kind(init_params: &InitAnyExchange) -> FFIExchangeKind {
+  match init_params {
+    InitAnyExchange::Binance(_) => FFIExchangeKind::Binance,
+    InitAnyExchange::Coinbase(_) => FFIExchangeKind::Coinbase,
+    InitAnyExchange::Nash(_) => FFIExchangeKind::Nash,
+  }
+}
+
+fn venue_level_to_ffi(level: Option<VenueLevel>) -> FFIVenueLevel {
+  match level {
+    Some(level) => FFIVenueLevel {
+      venue: level.venue,
+      exchange: level.exchange,
+      price: string_to_c_str(level.price.to_string()),
+      qty: string_to_c_str(level.qty.to_string()),
+    },
+    None => FFIVenueLevel {
+      venue: 0,
+      exchange: FFIExchangeKind::Binance,
+      price: std::ptr::null_mut(),
+      qty: std::ptr::null_mut(),
+    }
+  }
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn create_consolidated_book(book: Out<*mut SharedBook>) -> OpenLimitsResult {
+  unsafe {
+    *book = Box::into_raw(Box::new(Arc::new(Mutex::new(ConsolidatedBook::default()))));
+  }
+  result_to_ffi(Ok(()))
+}
+
+// Venues keep feeding the book until their subscription thread disconnects, freeing it only drops this handle
+#[no_mangle]
+pub  extern "cdecl" fn free_consolidated_book(book: *mut SharedBook) {
+  if book.is_null() {
+    return;
+  }
+  unsafe {
+    drop(Box::from_raw(book));
+  }
+}
+
+// Subscribes to the order book of `market` on the client and adds it to the book as a new venue
+#[no_mangle]
+pub  extern "cdecl" fn consolidated_book_add_venue(
+  book: *mut SharedBook,
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  venue: Out<u32>,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if book.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("book is null")));
+    }
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    unsafe {
+      let shared = (*book).clone();
+      let added = shared.lock().expect("Consolidated book lock poisoned").add_venue(exchange_kind(&(*client).init_params));
+      let sink = BookSink {
+        book: shared,
+        venue: added
+      };
+      let delivery = FFIDeliveryPolicy {
+        mode: FFIDeliveryMode::All,
+        conflate_ms: 0,
+        max_pending: 0
+      };
+      send_subscription(client, sub_handle, market_pair, StreamKind::OrderBook, None, std::ptr::null_mut(), delivery, Some(Sink::Book(sink)), subscription_id)?;
+      (*venue) = added;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Best bid and ask across all venues. The caller frees the level strings.
+#[no_mangle]
+pub  extern "cdecl" fn consolidated_book_best(
+  book: *mut SharedBook,
+  bid: Out<FFIVenueLevel>,
+  ask: Out<FFIVenueLevel>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if book.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("book is null")));
+    }
+    unsafe {
+      let book = (*book).lock().expect("Consolidated book lock poisoned");
+      (*bid) = venue_level_to_ffi(book.best_bid());
+      (*ask) = venue_level_to_ffi(book.best_ask());
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Best bid and ask of a single venue. The caller frees the level strings.
+#[no_mangle]
+pub  extern "cdecl" fn consolidated_book_venue_best(
+  book: *mut SharedBook,
+  venue: u32,
+  bid: Out<FFIVenueLevel>,
+  ask: Out<FFIVenueLevel>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if book.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("book is null")));
+    }
+    unsafe {
+      let book = (*book).lock().expect("Consolidated book lock poisoned");
+      if !book.has_venue(venue) {
+        return Err(OpenlimitsSharpError::InvalidArgument(format!("Unknown venue {}", venue)));
+      }
+      (*bid) = venue_level_to_ffi(book.venue_best_bid(venue));
+      (*ask) = venue_level_to_ffi(book.venue_best_ask(venue));
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Fills the buffers with the best levels across all venues, best first. The caller frees the level strings.
+#[no_mangle]
+pub  extern "cdecl" fn consolidated_book_depth(
+  book: *mut SharedBook,
+  bids_buff: *mut FFIVenueLevel, bids_buff_len: u64, actual_bids_buff_len: Out<u64>,
+  asks_buff: *mut FFIVenueLevel, asks_buff_len: u64, actual_asks_buff_len: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if book.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("book is null")));
+    }
+    unsafe {
+      let book = (*book).lock().expect("Consolidated book lock poisoned");
+
+      let bids = std::slice::from_raw_parts_mut::<FFIVenueLevel>(bids_buff, bids_buff_len as usize);
+      let ffi_bids: Vec<FFIVenueLevel> = book.bids(bids_buff_len as usize).into_iter().map(|level| venue_level_to_ffi(Some(level))).collect();
+      bids[0..ffi_bids.len()].copy_from_slice(&ffi_bids);
+      (*actual_bids_buff_len) = ffi_bids.len() as u64;
+
+      let asks = std::slice::from_raw_parts_mut::<FFIVenueLevel>(asks_buff, asks_buff_len as usize);
+      let ffi_asks: Vec<FFIVenueLevel> = book.asks(asks_buff_len as usize).into_iter().map(|level| venue_level_to_ffi(Some(level))).collect();
+      asks[0..ffi_asks.len()].copy_from_slice(&ffi_asks);
+      (*actual_asks_buff_len) = ffi_asks.len() as u64;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+fn tape_trade_to_ffi(t: TapeTrade) -> FFITapeTrade {
+  FFITapeTrade {
+    venue: t.venue,
+    exchange: t.exchange,
+    symbol: string_to_c_str(t.symbol),
+    late: t.late,
+    trade: to_ffi_trade(&t.trade),
+  }
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn create_trade_tape(window_ms: u64, capacity: usize, tape: Out<*mut SharedTape>) -> OpenLimitsResult {
+  unsafe {
+    *tape = Box::into_raw(Box::new(Arc::new(TradeTape::new(window_ms, capacity))));
+  }
+  result_to_ffi(Ok(()))
+}
+
+// Venues keep feeding the tape until their subscription thread disconnects, freeing it only drops this handle
+#[no_mangle]
+pub  extern "cdecl" fn free_trade_tape(tape: *mut SharedTape) {
+  if tape.is_null() {
+    return;
+  }
+  unsafe {
+    drop(Box::from_raw(tape));
+  }
+}
+
+// Subscribes to the trades of `market` on the client and merges them into the tape under `symbol`
+#[no_mangle]
+pub  extern "cdecl" fn trade_tape_add_venue(
+  tape: *mut SharedTape,
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  symbol: *mut c_char,
+  venue: Out<u32>,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if tape.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("tape is null")));
+    }
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    if symbol.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("symbol is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let symbol = c_str_to_string(symbol).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse symbol string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    unsafe {
+      let shared = (*tape).clone();
+      let added = shared.add_venue(exchange_kind(&(*client).init_params), symbol);
+      let sink = TapeSink {
+        tape: shared,
+        venue: added
+      };
+      let delivery = FFIDeliveryPolicy {
+        mode: FFIDeliveryMode::All,
+        conflate_ms: 0,
+        max_pending: 0
+      };
+      send_subscription(client, sub_handle, market_pair, StreamKind::Trades, None, std::ptr::null_mut(), delivery, Some(Sink::Tape(sink)), subscription_id)?;
+      (*venue) = added;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Waits up to `timeout_ms` for trades that left the reordering window, in exchange time order
+#[no_mangle]
+pub  extern "cdecl" fn trade_tape_wait(
+  tape: *mut SharedTape,
+  buff: *mut FFITapeTrade, buff_len: usize,
+  timeout_ms: u64,
+  actual_len: Out<usize>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if tape.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("tape is null")));
+    }
+    unsafe {
+      let trades = (*tape).wait(buff_len, std::time::Duration::from_millis(timeout_ms));
+      let out = std::slice::from_raw_parts_mut::<FFITapeTrade>(buff, buff_len);
+      let ffi_trades: Vec<FFITapeTrade> = trades.into_iter().map(tape_trade_to_ffi).collect();
+      out[0..ffi_trades.len()].copy_from_slice(&ffi_trades);
+      (*actual_len) = ffi_trades.len();
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn trade_tape_stats(tape: *mut SharedTape, out_stats: Out<FFITapeStats>) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if tape.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("tape is null")));
+    }
+    unsafe {
+      (*out_stats) = (*tape).stats();
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Starts writing every order book and trade message of this subscription thread to `dir`,
+// closing the journal that was running before
+#[no_mangle]
+pub  extern "cdecl" fn start_journal(
+  sub_handle: *mut SubthreadHandle,
+  dir: *mut c_char,
+  config: FFIJournalConfig
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    if dir.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("dir is null")));
+    }
+    let dir = c_str_to_string(dir).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse dir string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let writer = JournalWriter::open(std::path::PathBuf::from(dir), config).map_err(OpenLimitsError::IoError)?;
+    unsafe {
+      let previous = (*sub_handle).journal.lock().expect("Journal lock poisoned").replace(writer);
+      drop(previous);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Closes the open segment and stops journaling
+#[no_mangle]
+pub  extern "cdecl" fn stop_journal(sub_handle: *mut SubthreadHandle) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      let writer = (*sub_handle).journal.lock().expect("Journal lock poisoned").take();
+      if let Some(mut writer) = writer {
+        writer.close_segment().map_err(OpenLimitsError::IoError)?;
+      }
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Journaled records are handed out as stream events of a detached order book or trade subscription
+pub struct JournalReaderHandle {
+  records: JournalReader,
+  book: Arc<EventSource>,
+  trades: Arc<EventSource>,
+}
+
+fn journal_source(kind: FFISubscriptionKind, market: &str) -> Arc<EventSource> {
+  let delivery = FFIDeliveryPolicy {
+    mode: FFIDeliveryMode::All,
+    conflate_ms: 0,
+    max_pending: 0
+  };
+  Arc::new(EventSource::new(
+    0,
+    kind,
+    CString::new(market).expect("Failed to create CString!"),
+    UserData(std::ptr::null_mut()),
+    delivery
+  ))
+}
+
+// Reads the records of `market` received between `from_ms` and `to_ms` (unix ms, inclusive)
+#[no_mangle]
+pub  extern "cdecl" fn open_journal_reader(
+  dir: *mut c_char,
+  market: *mut c_char,
+  from_ms: u64,
+  to_ms: u64,
+  reader: Out<*mut JournalReaderHandle>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if dir.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("dir is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let dir = c_str_to_string(dir).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse dir string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let records = JournalReader::open(std::path::PathBuf::from(dir), market_pair.clone(), from_ms, to_ms).map_err(OpenLimitsError::IoError)?;
+    unsafe {
+      *reader = Box::into_raw(Box::new(JournalReaderHandle {
+        records,
+        book: journal_source(FFISubscriptionKind::OrderBook, &market_pair),
+        trades: journal_source(FFISubscriptionKind::Trades, &market_pair),
+      }));
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Fills the buffer with the next records, `actual_len` below `events_buff_len` means the reader is done.
+// The events are released with free_events.
+#[no_mangle]
+pub  extern "cdecl" fn read_journal(
+  reader: *mut JournalReaderHandle,
+  events_buff: *mut FFIEvent, events_buff_len: usize,
+  actual_len: Out<usize>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if reader.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("reader is null")));
+    }
+    unsafe {
+      let reader = &mut *reader;
+      let out = std::slice::from_raw_parts_mut::<FFIEvent>(events_buff, events_buff_len);
+      let mut len = 0;
+      while len < events_buff_len {
+        let record = match reader.records.next() {
+          Some(record) => record,
+          None => break
+        };
+        let source = match record.stream {
+          JournalStream::OrderBook => reader.book.clone(),
+          JournalStream::Trades => reader.trades.clone(),
+        };
+        let event = Event {
+          kind: record.kind,
+          source,
+          payload: record.payload,
+          seq: 0,
+          conflated: 0,
+          received: ReceiveTime {
+            monotonic_ns: 0,
+            wall_ms: record.received_ms
+          },
+        };
+        out[len] = event.to_ffi(0);
+        len += 1;
+      }
+      (*actual_len) = len;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn free_journal_reader(reader: *mut JournalReaderHandle) {
+  if reader.is_null() {
+    return;
+  }
+  unsafe {
+    drop(Box::from_raw(reader));
+  }
+}
+
+// Replays the journaled order book of `market`, starting before its first update
+#[no_mangle]
+pub  extern "cdecl" fn open_book_replay(
+  dir: *mut c_char,
+  market: *mut c_char,
+  replay: Out<*mut BookReplay>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if dir.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("dir is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let dir = c_str_to_string(dir).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse dir string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let opened = BookReplay::open(std::path::PathBuf::from(dir), market_pair).map_err(OpenLimitsError::IoError)?;
+    unsafe {
+      *replay = Box::into_raw(Box::new(opened));
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Applies the next order book update, `stepped` is false once the journal is exhausted
+#[no_mangle]
+pub  extern "cdecl" fn book_replay_step(replay: *mut BookReplay, stepped: Out<bool>) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if replay.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("replay is null")));
+    }
+    unsafe {
+      (*stepped) = (*replay).step();
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Moves the replay to the book as it was at `time` (unix ms)
+#[no_mangle]
+pub  extern "cdecl" fn book_replay_seek(replay: *mut BookReplay, time: u64) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if replay.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("replay is null")));
+    }
+    unsafe {
+      (*replay).seek(time).map_err(OpenLimitsError::IoError)?;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// The best levels of the replayed book, best first. The caller frees the level strings.
+#[no_mangle]
+pub  extern "cdecl" fn book_replay_book(
+  replay: *mut BookReplay,
+  bids_buff: *mut FFIAskBid, bids_buff_len: u64, actual_bids_buff_len: Out<u64>,
+  asks_buff: *mut FFIAskBid, asks_buff_len: u64, actual_asks_buff_len: Out<u64>,
+  state: Out<FFIBookReplayState>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if replay.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("replay is null")));
+    }
+    unsafe {
+      let book = (*replay).book();
+
+      let bids = std::slice::from_raw_parts_mut::<FFIAskBid>(bids_buff, bids_buff_len as usize);
+      let ffi_bids: Vec<FFIAskBid> = book.bids.iter().rev().take(bids_buff_len as usize)
+        .map(|(price, qty)| to_ffi_ask_bid(&AskBid { price: *price, qty: *qty }))
+        .collect();
+      bids[0..ffi_bids.len()].copy_from_slice(&ffi_bids);
+      (*actual_bids_buff_len) = ffi_bids.len() as u64;
+
+      let asks = std::slice::from_raw_parts_mut::<FFIAskBid>(asks_buff, asks_buff_len as usize);
+      let ffi_asks: Vec<FFIAskBid> = book.asks.iter().take(asks_buff_len as usize)
+        .map(|(price, qty)| to_ffi_ask_bid(&AskBid { price: *price, qty: *qty }))
+        .collect();
+      asks[0..ffi_asks.len()].copy_from_slice(&ffi_asks);
+      (*actual_asks_buff_len) = ffi_asks.len() as u64;
+
+      (*state) = (*replay).state();
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn free_book_replay(replay: *mut BookReplay) {
+  if replay.is_null() {
+    return;
+  }
+  unsafe {
+    drop(Box::from_raw(replay));
+  }
+}
+
+// Starts an order emulator placing and polling orders for the client's account every `poll_interval_ms`.
+// `on_update` is called from the emulator and subscription threads on every status change.
+#[no_mangle]
+pub  extern "cdecl" fn create_order_emulator(
+  client: *mut ExchangeClient,
+  poll_interval_ms: u64,
+  on_update: EmulatedOrderCallback,
+  user_data: *mut c_void,
+  emulator: Out<*mut SharedEmulator>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    unsafe {
+      let started = OrderEmulator::start((*client).init_params.clone(), (*client).rate_limiter.clone(), (*client).risk.clone(), (*client).dry_run.clone(), poll_interval_ms, on_update, UserData(user_data))?;
+      *emulator = Box::into_raw(Box::new(started));
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Stops the emulator. Exchange orders it placed stay as they are.
+#[no_mangle]
+pub  extern "cdecl" fn free_order_emulator(emulator: *mut SharedEmulator) {
+  if emulator.is_null() {
+    return;
+  }
+  unsafe {
+    let emulator = Box::from_raw(emulator);
+    emulator.stop();
+  }
+}
+
+// Subscribes to the trades of `market` so emulated stops on it can fire
+#[no_mangle]
+pub  extern "cdecl" fn order_emulator_watch(
+  emulator: *mut SharedEmulator,
+  client: *mut ExchangeClient,
+  sub_handle: *mut SubthreadHandle,
+  market: *mut c_char,
+  subscription_id: Out<u64>,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if emulator.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("emulator is null")));
+    }
+    if client.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("client is null")));
+    }
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+
+    unsafe {
+      let sink = EmulatorSink {
+        emulator: (*emulator).clone(),
+        market: market_pair.clone()
+      };
+      let delivery = FFIDeliveryPolicy {
+        mode: FFIDeliveryMode::All,
+        conflate_ms: 0,
+        max_pending: 0
+      };
+      send_subscription(client, sub_handle, market_pair.clone(), StreamKind::Trades, None, std::ptr::null_mut(), delivery, Some(Sink::Emulator(sink)), subscription_id)?;
+      (*emulator).watch(market_pair);
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Sells at market once the price fell `trail` below its high since placement, or buys once it rose `trail` above its low
+#[no_mangle]
+pub  extern "cdecl" fn emulate_trailing_stop(
+  emulator: *mut SharedEmulator,
+  market: *mut c_char,
+  side: FFISide,
+  qty: *mut c_char,
+  trail: *mut c_char,
+  id: Out<u64>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if emulator.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("emulator is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let size = decimal_from_c_str(qty, "size")?;
+    let trail = decimal_from_c_str(trail, "trail")?;
+    unsafe {
+      (*id) = (*emulator).trailing_stop(market_pair, side_from_ffi(side), size, trail).map_err(OpenlimitsSharpError::InvalidArgument)?;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// A take profit limit order at `limit_price` and a stop at `stop_price`, whichever fills first cancels the other
+#[no_mangle]
+pub  extern "cdecl" fn emulate_oco(
+  emulator: *mut SharedEmulator,
+  market: *mut c_char,
+  side: FFISide,
+  qty: *mut c_char,
+  limit_price: *mut c_char,
+  stop_price: *mut c_char,
+  id: Out<u64>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if emulator.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("emulator is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let size = decimal_from_c_str(qty, "size")?;
+    let limit_price = decimal_from_c_str(limit_price, "limit_price")?;
+    let stop_price = decimal_from_c_str(stop_price, "stop_price")?;
+    unsafe {
+      (*id) = (*emulator).oco(market_pair, side_from_ffi(side), size, limit_price, stop_price).map_err(OpenlimitsSharpError::InvalidArgument)?;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Works `qty` at `price` with at most `clip_qty` visible on the book at a time
+#[no_mangle]
+pub  extern "cdecl" fn emulate_iceberg(
+  emulator: *mut SharedEmulator,
+  market: *mut c_char,
+  side: FFISide,
+  qty: *mut c_char,
+  clip_qty: *mut c_char,
+  price: *mut c_char,
+  id: Out<u64>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if emulator.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("emulator is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let size = decimal_from_c_str(qty, "size")?;
+    let clip = decimal_from_c_str(clip_qty, "clip_qty")?;
+    let price = decimal_from_c_str(price, "price")?;
+    unsafe {
+      (*id) = (*emulator).iceberg(market_pair, side_from_ffi(side), size, clip, price).map_err(OpenlimitsSharpError::InvalidArgument)?;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// An entry limit order at `entry_price`. Once it filled, the filled size is exited on the opposite side
+// with a take profit at `take_profit_price` and a stop at `stop_price` that cancel each other.
+#[no_mangle]
+pub  extern "cdecl" fn emulate_bracket(
+  emulator: *mut SharedEmulator,
+  market: *mut c_char,
+  side: FFISide,
+  qty: *mut c_char,
+  entry_price: *mut c_char,
+  take_profit_price: *mut c_char,
+  stop_price: *mut c_char,
+  id: Out<u64>
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if emulator.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("emulator is null")));
+    }
+    if market.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("market is null")));
+    }
+    let market_pair = c_str_to_string(market).map_err(|e|
+      OpenlimitsSharpError::InvalidArgument(format!("Failed to parse market string. Invalid character on pos {}", e.valid_up_to()))
+    )?;
+    let size = decimal_from_c_str(qty, "size")?;
+    let entry_price = decimal_from_c_str(entry_price, "entry_price")?;
+    let take_profit_price = decimal_from_c_str(take_profit_price, "take_profit_price")?;
+    let stop_price = decimal_from_c_str(stop_price, "stop_price")?;
+    unsafe {
+      (*id) = (*emulator).bracket(market_pair, side_from_ffi(side), size, entry_price, take_profit_price, stop_price)
+        .map_err(OpenlimitsSharpError::InvalidArgument)?;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+// Disarms the emulated order and cancels the exchange order working for it
+#[no_mangle]
+pub  extern "cdecl" fn cancel_emulated_order(
+  emulator: *mut SharedEmulator,
+  id: u64
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if emulator.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("emulator is null")));
+    }
+    unsafe {
+      (*emulator).cancel(id).map_err(OpenlimitsSharpError::InvalidArgument)?;
+    }
+    Ok(())
+  };
+  result_to_ffi(call())
+}
+
+#[no_mangle]
+pub  extern "cdecl" fn disconnect(
+  sub_handle: *mut SubthreadHandle,
+) -> OpenLimitsResult {
+  let call = move|| -> Result<(), OpenlimitsSharpError> {
+    if sub_handle.is_null() {
+      return Err(OpenlimitsSharpError::InvalidArgument(String::from("sub_handle is null")));
+    }
+    unsafe {
+      (*sub_handle).channel.send(
+        SubthreadCmd::Disconnect
+      ).map_err(|_| OpenlimitsSharpError::SubscribeException(String::from("subscription thread already stopped")))
+    }
+  };
+  result_to_ffi(call())
+}
+
+// Stops the subscription thread if it still runs and releases the handle. Must not be called while
+// another call is using the handle, wait_events included.
+#[no_mangle]
+pub  extern "cdecl" fn free_subscriptions(sub_handle: *mut SubthreadHandle) {
+  if sub_handle.is_null() {
+    return;
+  }
+  unsafe {
+    let handle = Box::from_raw(sub_handle);
+    handle.channel.send(SubthreadCmd::Disconnect).ok();
+    handle.events.close();
+  }
+}
@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::os::raw::c_char;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use rust_decimal::Decimal;
+use openlimits::any_exchange::AnyExchange;
+use openlimits::errors::{MissingImplementation, OpenLimitsError};
+use openlimits::exchange::ExchangeAccount;
+use openlimits::model::{CancelOrderRequest, GetOrderRequest, OpenLimitOrderRequest, OpenMarketOrderRequest, Order, OrderStatus, Side, TimeInForce};
+use crate::{FFIExchangeKind, FFISide, FFITIF, OpenlimitsSharpError};
+use crate::ratelimit::{FFIEndpoint, RateLimiter};
+
+// One order of a place_orders batch, with the arguments place_order takes
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIOrderRequest {
+  pub market: *mut c_char,
+  pub qty: *mut c_char,
+  pub limit: bool,
+  // Ignored for market orders
+  pub price: *mut c_char,
+  pub side: FFISide,
+  pub tif: FFITIF,
+  pub tif_duration: u64,
+  pub post_only: bool,
+  // Generated when null
+  pub client_order_id: *mut c_char,
+}
+
+// One order of a cancel_orders batch
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFICancelRequest {
+  pub order_id: *mut c_char,
+  // Nullable
+  pub market: *mut c_char,
+}
+
+pub enum NewOrder {
+  Market(FFISide, OpenMarketOrderRequest),
+  Limit(FFISide, OpenLimitOrderRequest),
+}
+
+impl NewOrder {
+  // Market, side, size and price (None for market orders)
+  pub fn terms(&self) -> (&str, FFISide, Decimal, Option<Decimal>) {
+    match self {
+      NewOrder::Market(side, req) => (&req.market_pair, *side, req.size, None),
+      NewOrder::Limit(side, req) => (&req.market_pair, *side, req.size, Some(req.price)),
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      NewOrder::Market(side, req) => format!("market {:?} of {} {}", side, req.size, req.market_pair),
+      NewOrder::Limit(side, req) => format!("limit {:?} of {} {} at {} ({:?})", side, req.size, req.market_pair, req.price, req.time_in_force),
+    }
+  }
+
+  pub async fn send(&self, exchange: &AnyExchange) -> Result<Order, OpenLimitsError> {
+    match self {
+      NewOrder::Market(FFISide::Buy, req) => exchange.market_buy(req).await,
+      NewOrder::Market(FFISide::Sell, req) => exchange.market_sell(req).await,
+      NewOrder::Limit(FFISide::Buy, req) => exchange.limit_buy(req).await,
+      NewOrder::Limit(FFISide::Sell, req) => exchange.limit_sell(req).await,
+    }
+  }
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFITriggerDirection {
+  // Triggers once the last price is at or above the trigger price
+  RisesTo,
+  // Triggers once the last price is at or below the trigger price
+  FallsTo,
+}
+
+pub struct StopOrderRequest {
+  pub market_pair: String,
+  pub side: FFISide,
+  pub size: Decimal,
+  pub trigger_price: Decimal,
+  pub direction: FFITriggerDirection,
+  // Stop market order when None
+  pub limit_price: Option<Decimal>,
+  pub time_in_force: TimeInForce,
+}
+
+impl StopOrderRequest {
+  pub fn describe(&self) -> String {
+    let trigger = match self.direction {
+      FFITriggerDirection::RisesTo => "rises to",
+      FFITriggerDirection::FallsTo => "falls to",
+    };
+    let order = match self.limit_price {
+      Some(price) => format!("limit {:?} at {} ({:?})", self.side, price, self.time_in_force),
+      None => format!("market {:?}", self.side)
+    };
+    format!("{} of {} {} once the price {} {}", order, self.size, self.market_pair, trigger, self.trigger_price)
+  }
+}
+
+fn missing_stop_order(exchange: FFIExchangeKind, req: &StopOrderRequest) -> OpenLimitsError {
+  OpenLimitsError::MissingImplementation(MissingImplementation {
+    message: format!("Stop orders are not supported on {:?}, {} was not sent", exchange, req.describe())
+  })
+}
+
+// Fails with MissingImplementation unless the exchange has its own stop order endpoint. openlimits
+// exposes none yet, so every exchange reports the order type as missing.
+pub fn check_native_stop_order(exchange: FFIExchangeKind, req: &StopOrderRequest) -> Result<(), OpenLimitsError> {
+  Err(missing_stop_order(exchange, req))
+}
+
+// Sends the stop order to the exchange's own stop order endpoint. Callers check for one with
+// check_native_stop_order before reserving anything for the order.
+pub async fn place_native_stop_order(exchange: FFIExchangeKind, req: &StopOrderRequest) -> Result<Order, OpenLimitsError> {
+  Err(missing_stop_order(exchange, req))
+}
+
+pub fn is_terminal(status: &OrderStatus) -> bool {
+  match status {
+    OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::Rejected | OrderStatus::Expired => true,
+    _ => false
+  }
+}
+
+pub fn ffi_side(side: &Side) -> FFISide {
+  match side {
+    Side::Buy => FFISide::Buy,
+    Side::Sell => FFISide::Sell,
+  }
+}
+
+pub fn side_from_ffi(side: FFISide) -> Side {
+  match side {
+    FFISide::Buy => Side::Buy,
+    FFISide::Sell => Side::Sell,
+  }
+}
+
+// New price and size for an open limit order, None keeps the current value. The size is the quantity
+// left to work after the amend.
+pub struct AmendRequest {
+  pub id: String,
+  pub market_pair: String,
+  pub price: Option<Decimal>,
+  pub size: Option<Decimal>,
+}
+
+impl AmendRequest {
+  fn describe(&self) -> String {
+    let price = self.price.map(|price| price.to_string()).unwrap_or_else(|| String::from("unchanged"));
+    let size = self.size.map(|size| size.to_string()).unwrap_or_else(|| String::from("unchanged"));
+    format!("amend of {} on {} to price {} and size {}", self.id, self.market_pair, price, size)
+  }
+}
+
+// Amends the order with the exchange's own amend or replace endpoint. openlimits exposes none yet,
+// so every exchange reports it as missing and amends fall back to cancel_replace.
+pub async fn amend_native_order(exchange: FFIExchangeKind, req: &AmendRequest) -> Result<Order, OpenLimitsError> {
+  Err(OpenLimitsError::MissingImplementation(MissingImplementation {
+    message: format!("Order amends are not supported on {:?}, the {} was not sent", exchange, req.describe())
+  }))
+}
+
+pub struct CancelReplace {
+  pub cancel: Result<(), OpenLimitsError>,
+  // None when nothing was sent because the cancel failed or the order filled before it was canceled
+  pub replacement: Option<Result<Order, OpenlimitsSharpError>>,
+  // Sent only when the replacement failed in a way that rules out it was accepted
+  pub restore: Option<Result<Order, OpenlimitsSharpError>>,
+}
+
+fn limit_order(side: &Side, market_pair: &str, price: Decimal, size: Decimal) -> NewOrder {
+  NewOrder::Limit(ffi_side(side), OpenLimitOrderRequest {
+    market_pair: String::from(market_pair),
+    price,
+    size,
+    time_in_force: TimeInForce::GoodTillCancelled,
+    post_only: false
+  })
+}
+
+// Cancels `original` and places the amended order in its place as a good till cancelled limit order.
+// Fills that land between reading `original` and the cancel are taken off the replacement. If the
+// replacement is rejected, what was left of the original is placed again at its old price.
+// `client_order_id` must be claimed with ClientOrderIds::begin, it is given to the replacement.
+// The rate limit budget of the cancel must already be taken, the calls after it wait for theirs as
+// long as the client allows.
+pub async fn cancel_replace(
+  exchange: &AnyExchange,
+  client_orders: &ClientOrderIds,
+  limiter: &RateLimiter,
+  original: &Order,
+  req: &AmendRequest,
+  client_order_id: &str
+) -> CancelReplace {
+  let mut outcome = CancelReplace {
+    cancel: Ok(()),
+    replacement: None,
+    restore: None,
+  };
+  let before = original.remaining.unwrap_or(original.size);
+  let cancel = exchange.cancel_order(&CancelOrderRequest {
+    id: original.id.clone(),
+    market_pair: Some(original.market_pair.clone())
+  }).await;
+  if let Err(e) = cancel {
+    client_orders.release(client_order_id);
+    outcome.cancel = Err(e);
+    return outcome;
+  }
+
+  // Without a fresh read no fills are assumed, the cancel normally lands before the next fill does
+  let after = match limiter.acquire_async(FFIEndpoint::GetOrder, limiter.deadline()).await {
+    Ok(()) => exchange.get_order(&GetOrderRequest {
+      id: original.id.clone(),
+      market_pair: Some(original.market_pair.clone())
+    }).await.ok().and_then(|order| order.remaining),
+    Err(_) => None
+  }.unwrap_or(before);
+  let filled = std::cmp::max(before - after, Decimal::new(0, 0));
+  let size = req.size.unwrap_or(before) - filled;
+  let price = match req.price.or(original.price) {
+    Some(price) if size > Decimal::new(0, 0) => price,
+    _ => {
+      client_orders.release(client_order_id);
+      return outcome;
+    }
+  };
+
+  let placed = match limiter.acquire_async(FFIEndpoint::PlaceOrder, limiter.deadline()).await {
+    Ok(()) => {
+      let placed = limit_order(&original.side, &original.market_pair, price, size).send(exchange).await;
+      client_orders.finish(client_order_id, placed.as_ref().map(|order| (order.id.as_str(), order.market_pair.as_str())));
+      placed.map_err(OpenlimitsSharpError::from)
+    },
+    Err(e) => {
+      client_orders.release(client_order_id);
+      Err(OpenlimitsSharpError::RateLimited(e))
+    }
+  };
+  let not_accepted = match &placed {
+    Ok(_) => false,
+    Err(OpenlimitsSharpError::OpenLimitsError(e)) => !is_unresolved(e),
+    Err(_) => true
+  };
+  let restore = original.price.filter(|_| not_accepted && after > Decimal::new(0, 0));
+  outcome.replacement = Some(placed.map(|order| Order {
+    client_order_id: Some(String::from(client_order_id)),
+    ..order
+  }));
+  if let Some(price) = restore {
+    outcome.restore = Some(match limiter.acquire_async(FFIEndpoint::PlaceOrder, limiter.deadline()).await {
+      Ok(()) => limit_order(&original.side, &original.market_pair, price, after).send(exchange).await.map_err(OpenlimitsSharpError::from),
+      Err(e) => Err(OpenlimitsSharpError::RateLimited(e))
+    });
+  }
+  outcome
+}
+
+enum Placement {
+  // place_order is running for it
+  Pending,
+  Placed {
+    id: String,
+    market_pair: String,
+  },
+  // The placement failed in a way that leaves open whether the exchange accepted the order
+  Unresolved,
+}
+
+#[derive(Default)]
+struct Registry {
+  placements: HashMap<String, Placement>,
+  // Exchange order id to client order id
+  client_order_ids: HashMap<String, String>,
+}
+
+// Client order ids handed to place_order and the exchange orders they resulted in. openlimits does
+// not forward client order ids to the exchanges, so they only live here and are lost with the client.
+#[derive(Default)]
+pub struct ClientOrderIds {
+  next: AtomicU64,
+  registry: Mutex<Registry>,
+}
+
+// Failures after which the order may still have reached the exchange
+fn is_unresolved(e: &OpenLimitsError) -> bool {
+  match e {
+    OpenLimitsError::ReqError(_) => true,
+    OpenLimitsError::IoError(_) => true,
+    OpenLimitsError::InternalServerError() => true,
+    OpenLimitsError::ServiceUnavailable() => true,
+    OpenLimitsError::UnkownResponse(_) => true,
+    OpenLimitsError::NotParsableResponse(_) => true,
+    _ => false
+  }
+}
+
+impl ClientOrderIds {
+  pub fn generate(&self) -> String {
+    let seq = self.next.fetch_add(1, Ordering::SeqCst);
+    format!("ols-{:x}-{}", chrono::Utc::now().timestamp_millis(), seq)
+  }
+
+  // Claims the client order id for a placement. Returns the exchange order id and market when an
+  // order was already placed with it, in which case nothing must be sent.
+  pub fn begin(&self, client_order_id: &str) -> Result<Option<(String, String)>, OpenlimitsSharpError> {
+    let mut registry = self.registry.lock().expect("Client order id lock poisoned");
+    match registry.placements.get(client_order_id) {
+      None => {
+        registry.placements.insert(String::from(client_order_id), Placement::Pending);
+        Ok(None)
+      },
+      Some(Placement::Placed { id, market_pair }) => Ok(Some((id.clone(), market_pair.clone()))),
+      Some(Placement::Pending) => Err(OpenlimitsSharpError::InvalidArgument(
+        format!("An order with client order id {} is already being placed", client_order_id)
+      )),
+      Some(Placement::Unresolved) => Err(OpenlimitsSharpError::InvalidArgument(
+        format!("The last placement with client order id {} failed without telling if the order was accepted, check open orders and forget the id before retrying", client_order_id)
+      )),
+    }
+  }
+
+  pub fn finish(&self, client_order_id: &str, placed: Result<(&str, &str), &OpenLimitsError>) {
+    let mut registry = self.registry.lock().expect("Client order id lock poisoned");
+    match placed {
+      Ok((id, market_pair)) => {
+        registry.placements.insert(String::from(client_order_id), Placement::Placed {
+          id: String::from(id),
+          market_pair: String::from(market_pair),
+        });
+        registry.client_order_ids.insert(String::from(id), String::from(client_order_id));
+      },
+      Err(e) if is_unresolved(e) => {
+        registry.placements.insert(String::from(client_order_id), Placement::Unresolved);
+      },
+      Err(_) => {
+        registry.placements.remove(client_order_id);
+      }
+    }
+  }
+
+  // Exchange order id and market of the order placed with the client order id
+  pub fn resolve(&self, client_order_id: &str) -> Result<(String, String), OpenlimitsSharpError> {
+    let registry = self.registry.lock().expect("Client order id lock poisoned");
+    match registry.placements.get(client_order_id) {
+      Some(Placement::Placed { id, market_pair }) => Ok((id.clone(), market_pair.clone())),
+      Some(Placement::Pending) => Err(OpenlimitsSharpError::InvalidArgument(
+        format!("The order with client order id {} is still being placed", client_order_id)
+      )),
+      Some(Placement::Unresolved) => Err(OpenlimitsSharpError::InvalidArgument(
+        format!("It is unknown if the order with client order id {} was accepted", client_order_id)
+      )),
+      None => Err(OpenlimitsSharpError::InvalidArgument(
+        format!("Unknown client order id {}", client_order_id)
+      )),
+    }
+  }
+
+  pub fn client_order_id(&self, order_id: &str) -> Option<String> {
+    self.registry.lock().expect("Client order id lock poisoned").client_order_ids.get(order_id).cloned()
+  }
+
+  // Drops the claim on a client order id whose order was never sent
+  pub fn release(&self, client_order_id: &str) {
+    let mut registry = self.registry.lock().expect("Client order id lock poisoned");
+    if let Some(Placement::Pending) = registry.placements.get(client_order_id) {
+      registry.placements.remove(client_order_id);
+    }
+  }
+
+  // Releases a client order id so it can be placed again. Pending placements can't be forgotten.
+  pub fn forget(&self, client_order_id: &str) -> Result<(), OpenlimitsSharpError> {
+    let mut registry = self.registry.lock().expect("Client order id lock poisoned");
+    match registry.placements.get(client_order_id) {
+      Some(Placement::Pending) => return Err(OpenlimitsSharpError::InvalidArgument(
+        format!("The order with client order id {} is still being placed", client_order_id)
+      )),
+      Some(Placement::Placed { id, .. }) => {
+        let id = id.clone();
+        registry.client_order_ids.remove(&id);
+      },
+      _ => {}
+    };
+    registry.placements.remove(client_order_id);
+    Ok(())
+  }
+}
@@ -0,0 +1,368 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::FFIExchangeKind;
+
+// REST endpoints the client calls, each costing a weight of the exchange's request budget
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+pub enum FFIEndpoint {
+  OrderBook,
+  PriceTicker,
+  HistoricRates,
+  HistoricTrades,
+  PlaceOrder,
+  CancelOrder,
+  CancelAllOrders,
+  GetOrder,
+  OrderHistory,
+  OpenOrders,
+  TradeHistory,
+  AccountBalances,
+  MarketPairs,
+}
+
+const ENDPOINTS: [FFIEndpoint; 13] = [
+  FFIEndpoint::OrderBook,
+  FFIEndpoint::PriceTicker,
+  FFIEndpoint::HistoricRates,
+  FFIEndpoint::HistoricTrades,
+  FFIEndpoint::PlaceOrder,
+  FFIEndpoint::CancelOrder,
+  FFIEndpoint::CancelAllOrders,
+  FFIEndpoint::GetOrder,
+  FFIEndpoint::OrderHistory,
+  FFIEndpoint::OpenOrders,
+  FFIEndpoint::TradeHistory,
+  FFIEndpoint::AccountBalances,
+  FFIEndpoint::MarketPairs,
+];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIRateLimits {
+  // Request weight the exchange allows per window, 0 turns the request budget off
+  request_weight: u32,
+  request_window_ms: u64,
+  // Orders the exchange allows per window on top of their request weight, 0 turns the order budget off
+  order_count: u32,
+  order_window_ms: u64,
+  // Longest a call waits for budget before failing with RateLimited, 0 fails right away
+  max_wait_ms: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFIRateLimitUsage {
+  // Used weight can go over the budget while calls are waiting for it
+  request_weight_used: f64,
+  request_weight: u32,
+  orders_used: f64,
+  order_count: u32,
+  // Calls failed with RateLimited since the client was initialized
+  rejected: u64,
+}
+
+// Budget refilled continuously, `capacity` per `window`. Calls reserve their cost up front and
+// wait for the budget to come back, so waiting calls are served in order.
+struct Bucket {
+  capacity: f64,
+  window: Duration,
+  available: f64,
+  refilled_at: Instant,
+}
+
+impl Bucket {
+  fn new(capacity: u32, window_ms: u64) -> Self {
+    Bucket {
+      capacity: capacity as f64,
+      window: Duration::from_millis(std::cmp::max(window_ms, 1)),
+      available: capacity as f64,
+      refilled_at: Instant::now(),
+    }
+  }
+
+  fn refill(&mut self, now: Instant) {
+    let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+    self.available = (self.available + elapsed / self.window.as_secs_f64() * self.capacity).min(self.capacity);
+    self.refilled_at = now;
+  }
+
+  // How long a call costing `cost` would wait for its budget
+  fn wait(&self, cost: f64) -> Duration {
+    if self.capacity == 0.0 || self.available >= cost {
+      return Duration::from_secs(0);
+    }
+    self.window.mul_f64((cost - self.available) / self.capacity)
+  }
+
+  fn take(&mut self, cost: f64) {
+    if self.capacity > 0.0 {
+      self.available -= cost;
+    }
+  }
+
+  fn used(&self) -> f64 {
+    (self.capacity - self.available).max(0.0)
+  }
+}
+
+struct LimiterState {
+  limits: FFIRateLimits,
+  weights: [u32; ENDPOINTS.len()],
+  requests: Bucket,
+  orders: Bucket,
+  rejected: u64,
+}
+
+pub struct RateLimiter {
+  state: Mutex<LimiterState>,
+}
+
+// Published limits of each exchange. Nash publishes none, its budget is a conservative guess.
+fn default_limits(kind: FFIExchangeKind) -> FFIRateLimits {
+  let (request_weight, request_window_ms, order_count, order_window_ms) = match kind {
+    FFIExchangeKind::Binance => (1200, 60_000, 10, 1_000),
+    // 3 public or 5 private requests per second, see default_weight
+    FFIExchangeKind::Coinbase => (15, 1_000, 0, 1_000),
+    FFIExchangeKind::Nash => (10, 1_000, 0, 1_000),
+  };
+  FFIRateLimits {
+    request_weight,
+    request_window_ms,
+    order_count,
+    order_window_ms,
+    max_wait_ms: 5_000,
+  }
+}
+
+fn default_weight(kind: FFIExchangeKind, endpoint: FFIEndpoint) -> u32 {
+  match kind {
+    FFIExchangeKind::Binance => match endpoint {
+      FFIEndpoint::OrderBook => 10,
+      FFIEndpoint::GetOrder => 2,
+      FFIEndpoint::OrderHistory | FFIEndpoint::TradeHistory | FFIEndpoint::AccountBalances | FFIEndpoint::MarketPairs => 10,
+      // Open orders of all markets
+      FFIEndpoint::OpenOrders => 40,
+      _ => 1
+    },
+    FFIExchangeKind::Coinbase => match endpoint {
+      FFIEndpoint::OrderBook | FFIEndpoint::PriceTicker | FFIEndpoint::HistoricRates | FFIEndpoint::HistoricTrades | FFIEndpoint::MarketPairs => 5,
+      _ => 3
+    },
+    FFIExchangeKind::Nash => 1,
+  }
+}
+
+impl RateLimiter {
+  pub fn new(kind: FFIExchangeKind) -> Self {
+    let limits = default_limits(kind);
+    let mut weights = [0; ENDPOINTS.len()];
+    for endpoint in ENDPOINTS.iter() {
+      weights[*endpoint as usize] = default_weight(kind, *endpoint);
+    }
+    RateLimiter {
+      state: Mutex::new(LimiterState {
+        limits,
+        weights,
+        requests: Bucket::new(limits.request_weight, limits.request_window_ms),
+        orders: Bucket::new(limits.order_count, limits.order_window_ms),
+        rejected: 0,
+      })
+    }
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+    self.state.lock().expect("Rate limiter lock poisoned")
+  }
+
+  pub fn limits(&self) -> FFIRateLimits {
+    self.lock().limits
+  }
+
+  // Replaces the budgets, the budget used so far carries over
+  pub fn set_limits(&self, limits: FFIRateLimits) {
+    let mut state = self.lock();
+    let now = Instant::now();
+    let requests_used = { state.requests.refill(now); state.requests.used() };
+    let orders_used = { state.orders.refill(now); state.orders.used() };
+    state.requests = Bucket::new(limits.request_weight, limits.request_window_ms);
+    state.requests.take(requests_used);
+    state.orders = Bucket::new(limits.order_count, limits.order_window_ms);
+    state.orders.take(orders_used);
+    state.limits = limits;
+  }
+
+  pub fn set_weight(&self, endpoint: FFIEndpoint, weight: u32) {
+    self.lock().weights[endpoint as usize] = weight;
+  }
+
+  pub fn usage(&self) -> FFIRateLimitUsage {
+    let mut state = self.lock();
+    let now = Instant::now();
+    state.requests.refill(now);
+    state.orders.refill(now);
+    FFIRateLimitUsage {
+      request_weight_used: state.requests.used(),
+      request_weight: state.limits.request_weight,
+      orders_used: state.orders.used(),
+      order_count: state.limits.order_count,
+      rejected: state.rejected,
+    }
+  }
+
+  // Deadline of a call waiting as long as the client allows, starting now
+  pub fn deadline(&self) -> Instant {
+    Instant::now() + Duration::from_millis(self.lock().limits.max_wait_ms)
+  }
+
+  // Reserves the budget of `count` calls to `endpoint` and returns how long to wait before sending them.
+  // Fails without reserving anything when the wait would go past `deadline`.
+  fn reserve(&self, endpoint: FFIEndpoint, count: u32, deadline: Instant) -> Result<Duration, String> {
+    self.lock().reserve(Instant::now(), endpoint, count, deadline)
+  }
+
+  // Waits for the budget of `count` calls on the calling thread
+  pub fn acquire(&self, endpoint: FFIEndpoint, count: u32, deadline: Instant) -> Result<(), String> {
+    let wait = self.reserve(endpoint, count, deadline)?;
+    if wait > Duration::from_secs(0) {
+      std::thread::sleep(wait);
+    }
+    Ok(())
+  }
+
+  pub async fn acquire_async(&self, endpoint: FFIEndpoint, deadline: Instant) -> Result<(), String> {
+    let wait = self.reserve(endpoint, 1, deadline)?;
+    if wait > Duration::from_secs(0) {
+      tokio::time::delay_for(wait).await;
+    }
+    Ok(())
+  }
+}
+
+impl LimiterState {
+  fn reserve(&mut self, now: Instant, endpoint: FFIEndpoint, count: u32, deadline: Instant) -> Result<Duration, String> {
+    self.requests.refill(now);
+    self.orders.refill(now);
+    let weight = self.weights[endpoint as usize] as f64 * count as f64;
+    let orders = match endpoint {
+      FFIEndpoint::PlaceOrder => count as f64,
+      _ => 0.0
+    };
+    let wait = std::cmp::max(self.requests.wait(weight), self.orders.wait(orders));
+    let max_wait = deadline.saturating_duration_since(now);
+    if wait > max_wait {
+      self.rejected += 1;
+      return Err(format!(
+        "{:?} would wait {} ms for the rate limit, the deadline is in {} ms. {:.0} of {} request weight and {:.0} of {} orders are in use",
+        endpoint, wait.as_millis(), max_wait.as_millis(),
+        self.requests.used(), self.limits.request_weight, self.orders.used(), self.limits.order_count
+      ));
+    }
+    self.requests.take(weight);
+    self.orders.take(orders);
+    Ok(wait)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn state(request_weight: u32, order_count: u32, now: Instant) -> LimiterState {
+    let limits = FFIRateLimits {
+      request_weight,
+      request_window_ms: 1_000,
+      order_count,
+      order_window_ms: 1_000,
+      max_wait_ms: 0,
+    };
+    let mut requests = Bucket::new(limits.request_weight, limits.request_window_ms);
+    requests.refilled_at = now;
+    let mut orders = Bucket::new(limits.order_count, limits.order_window_ms);
+    orders.refilled_at = now;
+    LimiterState {
+      limits,
+      weights: [1; ENDPOINTS.len()],
+      requests,
+      orders,
+      rejected: 0,
+    }
+  }
+
+  #[test]
+  fn buckets_refill_in_proportion_to_the_time_passed() {
+    let start = Instant::now();
+    let mut bucket = Bucket::new(100, 1_000);
+    bucket.refilled_at = start;
+    bucket.take(100.0);
+    bucket.refill(start + Duration::from_millis(250));
+    assert_eq!(bucket.available, 25.0);
+    assert_eq!(bucket.used(), 75.0);
+    // Never above capacity
+    bucket.refill(start + Duration::from_secs(10));
+    assert_eq!(bucket.available, 100.0);
+  }
+
+  #[test]
+  fn buckets_wait_for_the_missing_budget() {
+    let mut bucket = Bucket::new(10, 1_000);
+    assert_eq!(bucket.wait(10.0), Duration::from_secs(0));
+    bucket.take(15.0);
+    // 5 over budget plus the 2 asked for, at 10 per second
+    assert_eq!(bucket.wait(2.0), Duration::from_millis(700));
+    assert_eq!(bucket.used(), 15.0);
+  }
+
+  #[test]
+  fn empty_buckets_are_off() {
+    let mut bucket = Bucket::new(0, 1_000);
+    bucket.take(100.0);
+    assert_eq!(bucket.available, 0.0);
+    assert_eq!(bucket.wait(100.0), Duration::from_secs(0));
+  }
+
+  #[test]
+  fn reserve_fails_when_the_wait_goes_past_the_deadline() {
+    let now = Instant::now();
+    let mut state = state(10, 0, now);
+    assert_eq!(state.reserve(now, FFIEndpoint::OrderBook, 10, now), Ok(Duration::from_secs(0)));
+    assert_eq!(state.reserve(now, FFIEndpoint::OrderBook, 5, now + Duration::from_millis(500)), Ok(Duration::from_millis(500)));
+    // Nothing is reserved by a failed call
+    let err = state.reserve(now, FFIEndpoint::OrderBook, 1, now + Duration::from_millis(599)).unwrap_err();
+    assert!(err.starts_with("OrderBook would wait 600 ms for the rate limit, the deadline is in 599 ms"));
+    assert_eq!(state.rejected, 1);
+    assert_eq!(state.reserve(now, FFIEndpoint::OrderBook, 1, now + Duration::from_millis(600)), Ok(Duration::from_millis(600)));
+    // A deadline already passed only lets calls with budget through
+    assert!(state.reserve(now + Duration::from_secs(2), FFIEndpoint::OrderBook, 1, now).is_ok());
+    assert!(state.reserve(now + Duration::from_secs(2), FFIEndpoint::OrderBook, 10, now).is_err());
+  }
+
+  #[test]
+  fn reserve_weighs_calls_by_endpoint_and_counts_orders() {
+    let now = Instant::now();
+    let mut state = state(100, 2, now);
+    state.weights[FFIEndpoint::OpenOrders as usize] = 40;
+    assert!(state.reserve(now, FFIEndpoint::OpenOrders, 2, now).is_ok());
+    assert_eq!(state.requests.used(), 80.0);
+    assert!(state.reserve(now, FFIEndpoint::PlaceOrder, 2, now).is_ok());
+    assert_eq!(state.orders.used(), 2.0);
+    // Request weight is left, the order budget is not
+    let err = state.reserve(now, FFIEndpoint::PlaceOrder, 1, now).unwrap_err();
+    assert!(err.starts_with("PlaceOrder would wait 500 ms"));
+    assert!(state.reserve(now, FFIEndpoint::CancelOrder, 1, now).is_ok());
+  }
+
+  #[test]
+  fn default_weights_follow_the_published_limits() {
+    assert_eq!(default_weight(FFIExchangeKind::Binance, FFIEndpoint::OpenOrders), 40);
+    assert_eq!(default_weight(FFIExchangeKind::Binance, FFIEndpoint::OrderBook), 10);
+    assert_eq!(default_weight(FFIExchangeKind::Binance, FFIEndpoint::GetOrder), 2);
+    assert_eq!(default_weight(FFIExchangeKind::Binance, FFIEndpoint::PlaceOrder), 1);
+    // 15 per second covers 3 public or 5 private calls
+    assert_eq!(default_weight(FFIExchangeKind::Coinbase, FFIEndpoint::OrderBook), 5);
+    assert_eq!(default_weight(FFIExchangeKind::Coinbase, FFIEndpoint::PlaceOrder), 3);
+    assert_eq!(default_weight(FFIExchangeKind::Nash, FFIEndpoint::OpenOrders), 1);
+    let limiter = RateLimiter::new(FFIExchangeKind::Binance);
+    assert_eq!(limiter.lock().weights[FFIEndpoint::OpenOrders as usize], 40);
+  }
+}
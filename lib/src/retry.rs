@@ -0,0 +1,133 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use crate::{OpenLimitsResultTag, OpenlimitsSharpError, error_tag};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FFIRetryPolicy {
+  // Attempts per call including the first one, 1 turns retries off
+  max_attempts: u32,
+  // Doubled after every failed attempt up to max_backoff_ms
+  initial_backoff_ms: u64,
+  max_backoff_ms: u64,
+  // Share of each backoff, 0 to 1, taken off at random so clients don't retry in lockstep
+  jitter: f64,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct RetryPolicy {
+  policy: FFIRetryPolicy,
+  // Bit `1 << tag` set for every OpenLimitsResultTag retried
+  retryable: u64,
+}
+
+fn tag_bit(tag: OpenLimitsResultTag) -> u64 {
+  1 << tag as u32
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    RetryPolicy::new(FFIRetryPolicy {
+      max_attempts: 3,
+      initial_backoff_ms: 200,
+      max_backoff_ms: 2_000,
+      jitter: 0.5,
+    }, tag_bit(OpenLimitsResultTag::ReqError) | tag_bit(OpenLimitsResultTag::ServiceUnavailable) | tag_bit(OpenLimitsResultTag::InternalServerError))
+  }
+}
+
+impl RetryPolicy {
+  pub fn new(policy: FFIRetryPolicy, retryable: u64) -> Self {
+    RetryPolicy {
+      policy,
+      retryable,
+    }
+  }
+
+  pub fn attempts(&self) -> u32 {
+    std::cmp::max(self.policy.max_attempts, 1)
+  }
+
+  pub fn retries(&self, tag: OpenLimitsResultTag) -> bool {
+    self.retryable & tag_bit(tag) != 0
+  }
+
+  // Backoff after the `attempt`th failed attempt, counting from 1
+  pub fn backoff(&self, attempt: u32) -> Duration {
+    let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    self.backoff_with(attempt, random)
+  }
+
+  // Backoff with `random`, 0 to 1, as the share of the jitter taken off
+  fn backoff_with(&self, attempt: u32, random: f64) -> Duration {
+    let doubled = self.policy.initial_backoff_ms.saturating_mul(1 << std::cmp::min(attempt - 1, 32));
+    let backoff = std::cmp::min(doubled, self.policy.max_backoff_ms) as f64;
+    let jitter = self.policy.jitter.max(0.0).min(1.0);
+    Duration::from_millis((backoff * (1.0 - jitter * random)) as u64)
+  }
+}
+
+// Runs `call` until it succeeds, fails with an error the policy doesn't retry or runs out of attempts
+pub fn with_retry<T, F>(policy: RetryPolicy, mut call: F) -> Result<T, OpenlimitsSharpError>
+  where F: FnMut() -> Result<T, OpenlimitsSharpError> {
+  let mut attempt = 1;
+  loop {
+    match call() {
+      Err(e) if attempt < policy.attempts() && policy.retries(error_tag(&e)) => {
+        std::thread::sleep(policy.backoff(attempt));
+        attempt += 1;
+      },
+      result => return result
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn policy(jitter: f64) -> RetryPolicy {
+    RetryPolicy::new(FFIRetryPolicy {
+      max_attempts: 5,
+      initial_backoff_ms: 100,
+      max_backoff_ms: 1_000,
+      jitter,
+    }, tag_bit(OpenLimitsResultTag::ReqError))
+  }
+
+  #[test]
+  fn backoff_doubles_up_to_the_cap() {
+    let policy = policy(0.0);
+    let backoffs: Vec<u64> = (1..=6).map(|attempt| policy.backoff(attempt).as_millis() as u64).collect();
+    assert_eq!(backoffs, vec![100, 200, 400, 800, 1_000, 1_000]);
+    // Shifts past 32 doublings saturate instead of overflowing
+    assert_eq!(policy.backoff(100), Duration::from_millis(1_000));
+  }
+
+  #[test]
+  fn jitter_takes_off_at_most_its_share() {
+    let policy = policy(0.5);
+    assert_eq!(policy.backoff_with(2, 0.0), Duration::from_millis(200));
+    assert_eq!(policy.backoff_with(2, 0.5), Duration::from_millis(150));
+    assert_eq!(policy.backoff_with(2, 1.0), Duration::from_millis(100));
+    for _ in 0..100 {
+      let backoff = policy.backoff(2);
+      assert!(backoff >= Duration::from_millis(100) && backoff <= Duration::from_millis(200));
+    }
+  }
+
+  #[test]
+  fn jitter_is_clamped_to_the_backoff() {
+    assert_eq!(policy(2.0).backoff_with(1, 1.0), Duration::from_millis(0));
+    assert_eq!(policy(-1.0).backoff_with(1, 1.0), Duration::from_millis(100));
+  }
+
+  #[test]
+  fn only_retryable_tags_are_retried() {
+    let policy = policy(0.0);
+    assert!(policy.retries(OpenLimitsResultTag::ReqError));
+    assert!(!policy.retries(OpenLimitsResultTag::ServiceUnavailable));
+    assert_eq!(RetryPolicy::new(FFIRetryPolicy { max_attempts: 0, ..policy.policy }, 0).attempts(), 1);
+  }
+}
@@ -0,0 +1,435 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::*;
+use lazy_static::lazy_static;
+use openlimits::any_exchange::InitAnyExchange;
+use openlimits::model::{Balance, Order};
+use crate::{ExchangeClient, FFISide, OpenlimitsSharpError};
+use crate::ratelimit::RateLimiter;
+use crate::dryrun::is_simulated;
+use crate::orders::{ffi_side, is_terminal};
+
+lazy_static! {
+  // Every client initialized so far, in order. Clients are never freed.
+  static ref CLIENTS: Mutex<Vec<RegisteredClient>> = Mutex::new(Vec::new());
+}
+
+static KILL_SWITCH: AtomicBool = AtomicBool::new(false);
+// Bumped every time the kill switch is engaged. Risk engines synced before reload their open orders.
+static KILL_SWITCH_ENGAGEMENTS: AtomicU64 = AtomicU64::new(0);
+
+pub const KILL_SWITCH_ENGAGED: &str = "kill_switch: new orders are blocked until the kill switch is released";
+
+// What the kill switch needs to reach a client's account without touching the client itself, which
+// may be in use on another thread
+#[derive(Clone)]
+pub struct RegisteredClient {
+  pub init_params: InitAnyExchange,
+  pub limiter: Arc<RateLimiter>,
+}
+
+pub fn register_client(client: &ExchangeClient) {
+  CLIENTS.lock().expect("Client registry lock poisoned").push(RegisteredClient {
+    init_params: client.init_params.clone(),
+    limiter: client.rate_limiter.clone(),
+  });
+}
+
+pub fn registered_clients() -> Vec<RegisteredClient> {
+  CLIENTS.lock().expect("Client registry lock poisoned").clone()
+}
+
+pub fn kill_switch_engaged() -> bool {
+  KILL_SWITCH.load(Ordering::SeqCst)
+}
+
+pub fn set_kill_switch(engaged: bool) {
+  if engaged {
+    KILL_SWITCH_ENGAGEMENTS.fetch_add(1, Ordering::SeqCst);
+  }
+  KILL_SWITCH.store(engaged, Ordering::SeqCst);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFIRiskLimits {
+  // Every limit is off at 0. Notional is in the quote asset, market orders are valued at mid.
+  max_order_notional: f64,
+  max_open_orders_per_market: u32,
+  max_orders_per_second: u32,
+  // Limit prices further than this from the current mid are rejected
+  price_band_bps: f64,
+  // Open orders and balances are reloaded from the exchange once older than this
+  sync_interval_ms: u64,
+}
+
+struct OpenOrder {
+  market: String,
+  side: FFISide,
+  remaining: Decimal,
+}
+
+// Exchange reads the checks of an order may need. Clients make them with their own REST client, the
+// order emulator with the one of its worker.
+pub trait RiskInputs {
+  fn open_orders(&mut self) -> Result<Vec<Order>, OpenlimitsSharpError>;
+  fn balances(&mut self) -> Result<Vec<Balance>, OpenlimitsSharpError>;
+  fn mid_price(&mut self, market: &str) -> Result<Option<Decimal>, OpenlimitsSharpError>;
+  fn base_asset(&mut self, market: &str) -> Result<Option<String>, OpenlimitsSharpError>;
+}
+
+// Pre-trade limits of a client, shared with its order emulator. Open orders and balances come from
+// the exchange and are kept up to date in between by the orders placed and canceled through either.
+#[derive(Default)]
+pub struct RiskEngine {
+  state: Mutex<RiskState>,
+}
+
+#[derive(Default)]
+struct RiskState {
+  limits: FFIRiskLimits,
+  // Largest absolute balance allowed per lowercase asset
+  position_limits: HashMap<String, Decimal>,
+  sent: VecDeque<Instant>,
+  open: HashMap<String, OpenOrder>,
+  // Orders that passed the checks and are being sent, by client order id
+  pending: HashMap<String, OpenOrder>,
+  balances: HashMap<String, Decimal>,
+  synced_at: Option<Instant>,
+  // KILL_SWITCH_ENGAGEMENTS at the last sync
+  synced_engagements: u64,
+}
+
+fn to_decimal(value: f64) -> Decimal {
+  Decimal::from_f64(value).unwrap_or_default()
+}
+
+impl RiskEngine {
+  fn lock(&self) -> std::sync::MutexGuard<'_, RiskState> {
+    self.state.lock().expect("Risk engine lock poisoned")
+  }
+
+  pub fn set_limits(&self, limits: FFIRiskLimits) {
+    self.lock().limits = limits;
+  }
+
+  pub fn set_position_limit(&self, asset: &str, max_position: Option<Decimal>) {
+    let mut state = self.lock();
+    match max_position {
+      Some(max_position) => state.position_limits.insert(asset.to_lowercase(), max_position),
+      None => state.position_limits.remove(&asset.to_lowercase())
+    };
+  }
+
+  // Runs the checks on an order about to be sent and reserves it under `key` until `sent` is called
+  // with the key. `replaces` is the id of an order the new one takes the place of.
+  pub fn check(
+    &self,
+    inputs: &mut impl RiskInputs,
+    key: &str,
+    market: &str,
+    side: FFISide,
+    size: Decimal,
+    price: Option<Decimal>,
+    replaces: Option<&str>
+  ) -> Result<(), OpenlimitsSharpError> {
+    if kill_switch_engaged() {
+      return Err(OpenlimitsSharpError::RiskRejected(String::from(KILL_SWITCH_ENGAGED)));
+    }
+    // The exchange is read without holding the lock, calls on other threads only wait for the check itself
+    let (needs_sync, needs_mid, needs_base_asset) = {
+      let state = self.lock();
+      (state.needs_sync(), state.needs_mid(price), state.needs_base_asset())
+    };
+    if needs_sync {
+      let orders = inputs.open_orders()?;
+      let balances = inputs.balances()?;
+      self.lock().sync(orders, balances);
+    }
+    let mid = match needs_mid {
+      true => inputs.mid_price(market)?,
+      false => None
+    };
+    let base_asset = match needs_base_asset {
+      true => inputs.base_asset(market)?,
+      false => None
+    };
+    self.lock().check(Instant::now(), key, market, side, size, price, mid, base_asset.as_deref(), replaces)
+      .map_err(OpenlimitsSharpError::RiskRejected)
+  }
+
+  // Releases the reservation of `key`, tracking the order if it was placed
+  pub fn sent(&self, key: &str, order: Option<&Order>) {
+    self.lock().sent(key, order);
+  }
+
+  // Counts an order placed without a check, such as an order put back after a failed amend. Simulated
+  // orders of a dry run never count.
+  pub fn track(&self, order: &Order) {
+    self.lock().track(order);
+  }
+
+  pub fn canceled(&self, id: &str) {
+    self.lock().canceled(id);
+  }
+}
+
+impl RiskState {
+  fn needs_sync(&self) -> bool {
+    if self.limits.max_open_orders_per_market == 0 && self.position_limits.is_empty() {
+      return false;
+    }
+    if self.synced_engagements != KILL_SWITCH_ENGAGEMENTS.load(Ordering::SeqCst) {
+      return true;
+    }
+    match self.synced_at {
+      None => true,
+      Some(synced_at) => synced_at.elapsed() >= Duration::from_millis(self.limits.sync_interval_ms)
+    }
+  }
+
+  fn sync(&mut self, orders: Vec<Order>, balances: Vec<Balance>) {
+    self.open = orders.into_iter().filter(|order| !is_terminal(&order.status)).map(|order| (order.id, OpenOrder {
+      market: order.market_pair,
+      side: ffi_side(&order.side),
+      remaining: order.remaining.unwrap_or(order.size),
+    })).collect();
+    self.balances = balances.into_iter().map(|balance| (balance.asset.to_lowercase(), balance.total)).collect();
+    self.synced_at = Some(Instant::now());
+    self.synced_engagements = KILL_SWITCH_ENGAGEMENTS.load(Ordering::SeqCst);
+  }
+
+  fn needs_mid(&self, price: Option<Decimal>) -> bool {
+    match price {
+      Some(_) => self.limits.price_band_bps > 0.0,
+      None => self.limits.max_order_notional > 0.0
+    }
+  }
+
+  fn needs_base_asset(&self) -> bool {
+    !self.position_limits.is_empty()
+  }
+
+  fn working(&self, excluding: Option<&str>) -> Vec<&OpenOrder> {
+    self.open.iter()
+      .filter(|(id, _)| Some(id.as_str()) != excluding)
+      .map(|(_, order)| order)
+      .chain(self.pending.values())
+      .collect()
+  }
+
+  // Checks an order about to be sent at `now` and reserves it under `key` until `sent` is called with
+  // the key. `replaces` is the id of an order the new one takes the place of, it is left out of the counts.
+  fn check(
+    &mut self,
+    now: Instant,
+    key: &str,
+    market: &str,
+    side: FFISide,
+    size: Decimal,
+    price: Option<Decimal>,
+    mid: Option<Decimal>,
+    base_asset: Option<&str>,
+    replaces: Option<&str>
+  ) -> Result<(), String> {
+    while self.sent.front().map(|sent| now.duration_since(*sent) >= Duration::from_secs(1)).unwrap_or(false) {
+      self.sent.pop_front();
+    }
+    let max_orders_per_second = self.limits.max_orders_per_second as usize;
+    if max_orders_per_second > 0 && self.sent.len() >= max_orders_per_second {
+      return Err(format!("max_orders_per_second: {} orders were sent in the last second, the limit is {}", self.sent.len(), max_orders_per_second));
+    }
+
+    if self.limits.max_order_notional > 0.0 {
+      let max_notional = to_decimal(self.limits.max_order_notional);
+      let notional = match price.or(mid) {
+        Some(price) => price * size,
+        None => return Err(format!("max_order_notional: there is no mid price on {} to value the market order at", market))
+      };
+      if notional > max_notional {
+        return Err(format!("max_order_notional: notional {} exceeds the limit {}", notional, max_notional));
+      }
+    }
+
+    if let Some(price) = price.filter(|_| self.limits.price_band_bps > 0.0) {
+      let mid = match mid {
+        Some(mid) => mid,
+        None => return Err(format!("price_band: there is no mid price on {} to check {} against", market, price))
+      };
+      let distance_bps = (price - mid).abs() / mid * Decimal::new(10_000, 0);
+      if distance_bps > to_decimal(self.limits.price_band_bps) {
+        return Err(format!("price_band: price {} is {} bps from mid {}, the band is {} bps", price, distance_bps.round_dp(2), mid, self.limits.price_band_bps));
+      }
+    }
+
+    let max_open_orders = self.limits.max_open_orders_per_market as usize;
+    if max_open_orders > 0 {
+      let open = self.working(replaces).into_iter().filter(|order| order.market.eq_ignore_ascii_case(market)).count();
+      if open >= max_open_orders {
+        return Err(format!("max_open_orders_per_market: {} orders are open on {}, the limit is {}", open, market, max_open_orders));
+      }
+    }
+
+    if !self.position_limits.is_empty() {
+      let base_asset = match base_asset {
+        Some(base_asset) => base_asset.to_lowercase(),
+        None => return Err(format!("max_position: the base asset of {} is unknown", market))
+      };
+      if let Some(max_position) = self.position_limits.get(&base_asset) {
+        let balance = self.balances.get(&base_asset).cloned().unwrap_or_default();
+        let working: Decimal = self.working(replaces).into_iter()
+          .filter(|order| order.market.eq_ignore_ascii_case(market) && matches!((&order.side, &side), (FFISide::Buy, FFISide::Buy) | (FFISide::Sell, FFISide::Sell)))
+          .map(|order| order.remaining)
+          .sum();
+        let position = match side {
+          FFISide::Buy => balance + working + size,
+          FFISide::Sell => balance - working - size,
+        };
+        if position.abs() > *max_position {
+          return Err(format!("max_position: the position in {} would reach {} with the working orders, the limit is {}", base_asset, position, max_position));
+        }
+      }
+    }
+
+    self.sent.push_back(now);
+    self.pending.insert(String::from(key), OpenOrder {
+      market: String::from(market),
+      side,
+      remaining: size,
+    });
+    Ok(())
+  }
+
+  fn sent(&mut self, key: &str, order: Option<&Order>) {
+    self.pending.remove(key);
+    if let Some(order) = order {
+      self.track(order);
+    }
+  }
+
+  fn track(&mut self, order: &Order) {
+    if is_terminal(&order.status) || is_simulated(&order.id) {
+      return;
+    }
+    self.open.insert(order.id.clone(), OpenOrder {
+      market: order.market_pair.clone(),
+      side: ffi_side(&order.side),
+      remaining: order.remaining.unwrap_or(order.size),
+    });
+  }
+
+  fn canceled(&mut self, id: &str) {
+    self.open.remove(id);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::FromStr;
+  use super::*;
+
+  fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+  }
+
+  fn state(limits: FFIRiskLimits) -> RiskState {
+    RiskState { limits, ..RiskState::default() }
+  }
+
+  fn open(market: &str, side: FFISide, remaining: &str) -> OpenOrder {
+    OpenOrder { market: String::from(market), side, remaining: dec(remaining) }
+  }
+
+  #[test]
+  fn orders_per_second_counts_the_last_second_only() {
+    let mut state = state(FFIRiskLimits { max_orders_per_second: 2, ..FFIRiskLimits::default() });
+    let start = Instant::now();
+    let check = |state: &mut RiskState, key: &str, at: Instant| state.check(at, key, "btcusd", FFISide::Buy, dec("1"), None, None, None, None);
+    assert!(check(&mut state, "a", start).is_ok());
+    assert!(check(&mut state, "b", start + Duration::from_millis(500)).is_ok());
+    assert!(check(&mut state, "c", start + Duration::from_millis(999)).unwrap_err().starts_with("max_orders_per_second"));
+    // The first order leaves the window after a second
+    assert!(check(&mut state, "d", start + Duration::from_millis(1000)).is_ok());
+    assert!(check(&mut state, "e", start + Duration::from_millis(1400)).unwrap_err().starts_with("max_orders_per_second"));
+  }
+
+  #[test]
+  fn notional_values_market_orders_at_mid() {
+    let mut state = state(FFIRiskLimits { max_order_notional: 100.0, ..FFIRiskLimits::default() });
+    let now = Instant::now();
+    assert!(state.check(now, "a", "btcusd", FFISide::Buy, dec("2"), Some(dec("50")), None, None, None).is_ok());
+    assert!(state.check(now, "b", "btcusd", FFISide::Buy, dec("2.1"), Some(dec("50")), None, None, None).unwrap_err().starts_with("max_order_notional: notional"));
+    assert!(state.check(now, "c", "btcusd", FFISide::Buy, dec("2"), None, Some(dec("50")), None, None).is_ok());
+    assert!(state.check(now, "d", "btcusd", FFISide::Buy, dec("3"), None, Some(dec("50")), None, None).is_err());
+  }
+
+  #[test]
+  fn notional_rejects_market_orders_without_a_mid() {
+    let mut state = state(FFIRiskLimits { max_order_notional: 100.0, ..FFIRiskLimits::default() });
+    let err = state.check(Instant::now(), "a", "btcusd", FFISide::Sell, dec("1"), None, None, None, None).unwrap_err();
+    assert!(err.starts_with("max_order_notional: there is no mid price"));
+    assert!(state.pending.is_empty());
+  }
+
+  #[test]
+  fn price_band_is_measured_from_mid_on_both_sides() {
+    let mut state = state(FFIRiskLimits { price_band_bps: 100.0, ..FFIRiskLimits::default() });
+    let now = Instant::now();
+    let mid = Some(dec("100"));
+    assert!(state.check(now, "a", "btcusd", FFISide::Buy, dec("1"), Some(dec("99")), mid, None, None).is_ok());
+    assert!(state.check(now, "b", "btcusd", FFISide::Sell, dec("1"), Some(dec("101")), mid, None, None).is_ok());
+    assert!(state.check(now, "c", "btcusd", FFISide::Buy, dec("1"), Some(dec("98.9")), mid, None, None).unwrap_err().starts_with("price_band: price"));
+    assert!(state.check(now, "d", "btcusd", FFISide::Sell, dec("1"), Some(dec("101.1")), mid, None, None).is_err());
+    assert!(state.check(now, "e", "btcusd", FFISide::Buy, dec("1"), Some(dec("100")), None, None, None).unwrap_err().starts_with("price_band: there is no mid price"));
+    // Market orders have no price to check
+    assert!(state.check(now, "f", "btcusd", FFISide::Buy, dec("1"), None, None, None, None).is_ok());
+  }
+
+  #[test]
+  fn open_orders_leave_out_the_replaced_order() {
+    let mut state = state(FFIRiskLimits { max_open_orders_per_market: 2, ..FFIRiskLimits::default() });
+    state.open.insert(String::from("1"), open("btcusd", FFISide::Buy, "1"));
+    state.open.insert(String::from("2"), open("BTCUSD", FFISide::Sell, "1"));
+    state.open.insert(String::from("3"), open("ethusd", FFISide::Buy, "1"));
+    let now = Instant::now();
+    assert!(state.check(now, "a", "btcusd", FFISide::Buy, dec("1"), None, None, None, None).unwrap_err().starts_with("max_open_orders_per_market: 2 orders"));
+    assert!(state.check(now, "b", "btcusd", FFISide::Buy, dec("1"), None, None, None, Some("1")).is_ok());
+    // The pending order counts until it is sent
+    assert!(state.check(now, "c", "btcusd", FFISide::Buy, dec("1"), None, None, None, Some("1")).is_err());
+    state.sent("b", None);
+    assert!(state.check(now, "c", "btcusd", FFISide::Buy, dec("1"), None, None, None, Some("1")).is_ok());
+  }
+
+  #[test]
+  fn position_adds_working_buys_and_subtracts_working_sells() {
+    let mut state = state(FFIRiskLimits::default());
+    state.position_limits.insert(String::from("btc"), dec("10"));
+    state.balances.insert(String::from("btc"), dec("5"));
+    state.open.insert(String::from("1"), open("btcusd", FFISide::Buy, "2"));
+    state.open.insert(String::from("2"), open("btcusd", FFISide::Sell, "4"));
+    let now = Instant::now();
+    // Buys: 5 + 2 working + size
+    assert!(state.check(now, "a", "btcusd", FFISide::Buy, dec("3"), None, None, Some("BTC"), None).is_ok());
+    state.sent("a", None);
+    assert!(state.check(now, "b", "btcusd", FFISide::Buy, dec("3.1"), None, None, Some("BTC"), None).unwrap_err().starts_with("max_position: the position in btc would reach 10.1"));
+    // Sells: 5 - 4 working - size
+    assert!(state.check(now, "c", "btcusd", FFISide::Sell, dec("11"), None, None, Some("BTC"), None).is_ok());
+    state.sent("c", None);
+    assert!(state.check(now, "d", "btcusd", FFISide::Sell, dec("11.1"), None, None, Some("BTC"), None).unwrap_err().contains("would reach -10.1"));
+    // Replacing the working sell frees its size
+    assert!(state.check(now, "e", "btcusd", FFISide::Sell, dec("15"), None, None, Some("BTC"), Some("2")).is_ok());
+  }
+
+  #[test]
+  fn position_limits_need_the_base_asset() {
+    let mut state = state(FFIRiskLimits::default());
+    state.position_limits.insert(String::from("btc"), dec("10"));
+    let now = Instant::now();
+    assert!(state.check(now, "a", "btcusd", FFISide::Buy, dec("1"), None, None, None, None).unwrap_err().starts_with("max_position: the base asset"));
+    // Assets without a limit are not checked
+    assert!(state.check(now, "b", "ethusd", FFISide::Buy, dec("100"), None, None, Some("eth"), None).is_ok());
+  }
+}
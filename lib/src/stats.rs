@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::*;
+use openlimits::model::{Side, Trade};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFITradeStats {
+  window_ms: u64,
+  count: u64,
+  volume: f64,
+  buy_volume: f64,
+  sell_volume: f64,
+  notional: f64,
+  // 0 when the window holds no trades
+  vwap: f64,
+}
+
+struct StatsTrade {
+  received_ms: u64,
+  price: Decimal,
+  qty: Decimal,
+  buy: bool,
+}
+
+// Rolling statistics over the trades of a subscription, one set per window length.
+// Trades are windowed by local receive time so windows keep moving while the market is quiet.
+pub struct TradeStats {
+  windows: Vec<u64>,
+  push_interval_ms: u64,
+  last_push_ms: u64,
+  trades: VecDeque<StatsTrade>,
+}
+
+impl TradeStats {
+  pub fn new(windows: Vec<u64>, push_interval_ms: u64, now_ms: u64) -> Self {
+    TradeStats {
+      windows,
+      push_interval_ms,
+      last_push_ms: now_ms,
+      trades: VecDeque::new(),
+    }
+  }
+
+  fn longest_window(&self) -> u64 {
+    self.windows.iter().cloned().max().unwrap_or_default()
+  }
+
+  fn expire(&mut self, now_ms: u64) {
+    let start = now_ms.saturating_sub(self.longest_window());
+    while self.trades.front().map(|trade| trade.received_ms < start).unwrap_or(false) {
+      self.trades.pop_front();
+    }
+  }
+
+  pub fn record(&mut self, received_ms: u64, trades: &[Trade]) {
+    for trade in trades {
+      self.trades.push_back(StatsTrade {
+        received_ms,
+        price: trade.price,
+        qty: trade.qty,
+        buy: match trade.side {
+          Side::Buy => true,
+          Side::Sell => false,
+        },
+      });
+    }
+    self.expire(received_ms);
+  }
+
+  pub fn stats(&mut self, now_ms: u64) -> Vec<FFITradeStats> {
+    self.expire(now_ms);
+    self.windows.iter().map(|window_ms| {
+      let start = now_ms.saturating_sub(*window_ms);
+      let mut count = 0;
+      let mut volume = Decimal::zero();
+      let mut buy_volume = Decimal::zero();
+      let mut notional = Decimal::zero();
+      for trade in self.trades.iter().rev().take_while(|trade| trade.received_ms >= start) {
+        count += 1;
+        volume += trade.qty;
+        notional += trade.price * trade.qty;
+        if trade.buy {
+          buy_volume += trade.qty;
+        }
+      }
+      FFITradeStats {
+        window_ms: *window_ms,
+        count,
+        volume: volume.to_f64().unwrap_or_default(),
+        buy_volume: buy_volume.to_f64().unwrap_or_default(),
+        sell_volume: (volume - buy_volume).to_f64().unwrap_or_default(),
+        notional: notional.to_f64().unwrap_or_default(),
+        vwap: match volume.is_zero() {
+          true => 0.0,
+          false => (notional / volume).to_f64().unwrap_or_default()
+        },
+      }
+    }).collect()
+  }
+
+  // Statistics to push if the push interval passed since the last push
+  pub fn due(&mut self, now_ms: u64) -> Option<Vec<FFITradeStats>> {
+    if self.push_interval_ms == 0 || now_ms.saturating_sub(self.last_push_ms) < self.push_interval_ms {
+      return None;
+    }
+    self.last_push_ms = now_ms;
+    Some(self.stats(now_ms))
+  }
+}
@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use openlimits::model::Trade;
+use crate::FFIExchangeKind;
+
+struct TapeVenue {
+  exchange: FFIExchangeKind,
+  symbol: String,
+}
+
+struct PendingTrade {
+  venue: u32,
+  trade: Trade,
+  release_at: Instant,
+}
+
+pub struct TapeTrade {
+  pub venue: u32,
+  pub exchange: FFIExchangeKind,
+  pub symbol: String,
+  pub trade: Trade,
+  // Exchange time is before a trade already released, the window was too short to reorder it
+  pub late: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FFITapeStats {
+  pending: u64,
+  late: u64,
+  dropped: u64,
+}
+
+#[derive(Default)]
+struct TapeState {
+  venues: Vec<TapeVenue>,
+  // Ordered by exchange time, then arrival
+  pending: BTreeMap<(u64, u64), PendingTrade>,
+  next_seq: u64,
+  last_released: u64,
+  late: u64,
+  dropped: u64,
+}
+
+// Merges the trade subscriptions of several clients into one stream ordered by exchange time.
+// Every trade is held for `window` after it arrived so trades from slower venues can be sorted in before it.
+pub struct TradeTape {
+  window: Duration,
+  capacity: usize,
+  state: Mutex<TapeState>,
+  ready: Condvar,
+}
+
+pub type SharedTape = Arc<TradeTape>;
+
+impl TradeTape {
+  pub fn new(window_ms: u64, capacity: usize) -> Self {
+    TradeTape {
+      window: Duration::from_millis(window_ms),
+      capacity: std::cmp::max(capacity, 1),
+      state: Mutex::new(TapeState::default()),
+      ready: Condvar::new(),
+    }
+  }
+
+  pub fn add_venue(&self, exchange: FFIExchangeKind, symbol: String) -> u32 {
+    let mut state = self.state.lock().expect("Trade tape lock poisoned");
+    state.venues.push(TapeVenue {
+      exchange,
+      symbol
+    });
+    (state.venues.len() - 1) as u32
+  }
+
+  // Drops the oldest pending trades once more than `capacity` are waiting
+  pub fn push(&self, venue: u32, trades: &[Trade]) {
+    let release_at = Instant::now() + self.window;
+    let mut state = self.state.lock().expect("Trade tape lock poisoned");
+    for trade in trades {
+      let seq = state.next_seq;
+      state.next_seq += 1;
+      state.pending.insert((trade.created_at, seq), PendingTrade {
+        venue,
+        trade: trade.clone(),
+        release_at
+      });
+    }
+    while state.pending.len() > self.capacity {
+      let oldest = *state.pending.keys().next().expect("Pending trades are not empty");
+      state.pending.remove(&oldest);
+      state.dropped += 1;
+    }
+    self.ready.notify_all();
+  }
+
+  fn release(&self, state: &mut TapeState, max: usize) -> Vec<TapeTrade> {
+    let now = Instant::now();
+    let mut released = Vec::new();
+    while released.len() < max {
+      let key = match state.pending.iter().next() {
+        Some((key, pending)) if pending.release_at <= now => *key,
+        _ => break
+      };
+      let pending = state.pending.remove(&key).expect("Released trade is pending");
+      let late = pending.trade.created_at < state.last_released;
+      if late {
+        state.late += 1;
+      }
+      state.last_released = std::cmp::max(state.last_released, pending.trade.created_at);
+      let venue = &state.venues[pending.venue as usize];
+      released.push(TapeTrade {
+        venue: pending.venue,
+        exchange: venue.exchange,
+        symbol: venue.symbol.clone(),
+        trade: pending.trade,
+        late
+      });
+    }
+    released
+  }
+
+  // Blocks until trades are due or `timeout` passes, returning at most `max` of them
+  pub fn wait(&self, max: usize, timeout: Duration) -> Vec<TapeTrade> {
+    let deadline = Instant::now() + timeout;
+    let mut state = self.state.lock().expect("Trade tape lock poisoned");
+    loop {
+      let released = self.release(&mut state, max);
+      let now = Instant::now();
+      if !released.is_empty() || now >= deadline {
+        return released;
+      }
+      let next_due = state.pending.values().next().map(|pending| pending.release_at).unwrap_or(deadline);
+      let wake = std::cmp::min(next_due, deadline);
+      state = self.ready.wait_timeout(state, wake.saturating_duration_since(now)).expect("Trade tape lock poisoned").0;
+    }
+  }
+
+  pub fn stats(&self) -> FFITapeStats {
+    let state = self.state.lock().expect("Trade tape lock poisoned");
+    FFITapeStats {
+      pending: state.pending.len() as u64,
+      late: state.late,
+      dropped: state.dropped,
+    }
+  }
+}
+
+// Routes the trades of a subscription into one venue of a trade tape
+#[derive(Clone)]
+pub struct TapeSink {
+  pub tape: SharedTape,
+  pub venue: u32,
+}
+
+impl TapeSink {
+  pub fn push(&self, trades: &[Trade]) {
+    self.tape.push(self.venue, trades);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rust_decimal::Decimal;
+  use openlimits::model::Side;
+  use super::*;
+
+  fn trade(created_at: u64) -> Trade {
+    Trade {
+      id: created_at.to_string(),
+      buyer_order_id: None,
+      seller_order_id: None,
+      market_pair: String::from("btcusd"),
+      price: Decimal::new(100, 0),
+      qty: Decimal::new(1, 0),
+      fees: None,
+      side: Side::Buy,
+      liquidity: None,
+      created_at,
+    }
+  }
+
+  fn trades(created_at: &[u64]) -> Vec<Trade> {
+    created_at.iter().cloned().map(trade).collect()
+  }
+
+  // (venue, exchange time, late) of every released trade
+  fn released(trades: Vec<TapeTrade>) -> Vec<(u32, u64, bool)> {
+    trades.into_iter().map(|released| (released.venue, released.trade.created_at, released.late)).collect()
+  }
+
+  #[test]
+  fn trades_of_both_venues_are_released_in_exchange_time_order() {
+    let tape = TradeTape::new(200, 16);
+    let binance = tape.add_venue(FFIExchangeKind::Binance, String::from("btcusdt"));
+    let coinbase = tape.add_venue(FFIExchangeKind::Coinbase, String::from("BTC-USD"));
+    tape.push(binance, &trades(&[100, 300]));
+    tape.push(coinbase, &trades(&[200, 150]));
+    // Held for the window
+    assert!(tape.wait(16, Duration::from_millis(0)).is_empty());
+    assert_eq!(tape.stats().pending, 4);
+
+    let first = tape.wait(1, Duration::from_secs(5));
+    assert_eq!(released(first), vec![(binance, 100, false)]);
+    let rest = tape.wait(16, Duration::from_secs(5));
+    assert_eq!(rest[0].symbol, "BTC-USD");
+    assert_eq!(released(rest), vec![(coinbase, 150, false), (coinbase, 200, false), (binance, 300, false)]);
+    let stats = tape.stats();
+    assert_eq!((stats.pending, stats.late, stats.dropped), (0, 0, 0));
+  }
+
+  #[test]
+  fn trades_older_than_a_released_one_are_late() {
+    let tape = TradeTape::new(0, 16);
+    let binance = tape.add_venue(FFIExchangeKind::Binance, String::from("btcusdt"));
+    let coinbase = tape.add_venue(FFIExchangeKind::Coinbase, String::from("BTC-USD"));
+    tape.push(binance, &trades(&[200]));
+    assert_eq!(released(tape.wait(16, Duration::from_secs(5))), vec![(binance, 200, false)]);
+    tape.push(coinbase, &trades(&[150, 200]));
+    assert_eq!(released(tape.wait(16, Duration::from_secs(5))), vec![(coinbase, 150, true), (coinbase, 200, false)]);
+    assert_eq!(tape.stats().late, 1);
+  }
+
+  #[test]
+  fn oldest_pending_trades_are_dropped_over_capacity() {
+    let tape = TradeTape::new(60_000, 2);
+    let binance = tape.add_venue(FFIExchangeKind::Binance, String::from("btcusdt"));
+    let coinbase = tape.add_venue(FFIExchangeKind::Coinbase, String::from("BTC-USD"));
+    tape.push(binance, &trades(&[300]));
+    tape.push(coinbase, &trades(&[100, 200]));
+    let stats = tape.stats();
+    assert_eq!((stats.pending, stats.dropped), (2, 1));
+    let state = tape.state.lock().unwrap();
+    let pending: Vec<u64> = state.pending.values().map(|pending| pending.trade.created_at).collect();
+    assert_eq!(pending, vec![200, 300]);
+  }
+}
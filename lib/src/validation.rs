@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use rust_decimal::Decimal;
+use openlimits::exchange_info::MarketPair;
+use crate::FFISide;
+
+// Markets missing from the cached pairs are looked up again at most this often
+const UNKNOWN_MARKET_REFRESH: Duration = Duration::from_secs(60);
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FFIValidationMode {
+  // Orders are sent as they are
+  Off,
+  // Orders off the market's increments or below its minimums fail with InvalidArgument
+  Reject,
+  // Size is rounded down, buy prices down and sell prices up to the increments. Orders still below
+  // the minimums afterwards are rejected.
+  Round,
+}
+
+// Trading rules of the exchange's markets, fetched on first use
+pub struct MarketRules {
+  pub mode: FFIValidationMode,
+  pairs: HashMap<String, MarketPair>,
+  loaded_at: Option<Instant>,
+}
+
+impl MarketRules {
+  pub fn new(mode: FFIValidationMode) -> Self {
+    MarketRules {
+      mode,
+      pairs: HashMap::new(),
+      loaded_at: None,
+    }
+  }
+
+  pub fn needs_refresh(&self, market: &str) -> bool {
+    match self.loaded_at {
+      None => true,
+      Some(loaded_at) => self.pair(market).is_none() && loaded_at.elapsed() >= UNKNOWN_MARKET_REFRESH
+    }
+  }
+
+  pub fn load(&mut self, pairs: Vec<MarketPair>) {
+    self.pairs = pairs.into_iter().map(|pair| (pair.symbol.to_lowercase(), pair)).collect();
+    self.loaded_at = Some(Instant::now());
+  }
+
+  pub fn pair(&self, market: &str) -> Option<&MarketPair> {
+    self.pairs.get(&market.to_lowercase())
+  }
+}
+
+fn is_multiple(value: Decimal, increment: Decimal) -> bool {
+  increment.is_zero() || (value % increment).is_zero()
+}
+
+fn round_down(value: Decimal, increment: Decimal) -> Decimal {
+  match increment.is_zero() {
+    true => value,
+    false => ((value / increment).floor() * increment).normalize()
+  }
+}
+
+fn round_up(value: Decimal, increment: Decimal) -> Decimal {
+  match increment.is_zero() {
+    true => value,
+    false => ((value / increment).ceil() * increment).normalize()
+  }
+}
+
+// Checks or rounds the size and, for limit orders, the price of an order against the market pair.
+// Returns the size and price to send.
+pub fn normalize(
+  pair: &MarketPair,
+  mode: FFIValidationMode,
+  side: FFISide,
+  size: Decimal,
+  price: Option<Decimal>
+) -> Result<(Decimal, Option<Decimal>), String> {
+  if mode == FFIValidationMode::Off {
+    return Ok((size, price));
+  }
+  let zero = Decimal::new(0, 0);
+  if size <= zero {
+    return Err(format!("Size {} must be greater than 0", size));
+  }
+  if let Some(price) = price.filter(|price| *price <= zero) {
+    return Err(format!("Price {} must be greater than 0", price));
+  }
+
+  let (size, price) = match mode {
+    FFIValidationMode::Round => {
+      let price = price.map(|price| match side {
+        FFISide::Buy => round_down(price, pair.quote_increment),
+        FFISide::Sell => round_up(price, pair.quote_increment),
+      });
+      (round_down(size, pair.base_increment), price)
+    },
+    _ => {
+      if !is_multiple(size, pair.base_increment) {
+        return Err(format!("Size {} is not a multiple of the base increment {} of {}", size, pair.base_increment, pair.symbol));
+      }
+      if let Some(price) = price.filter(|price| !is_multiple(*price, pair.quote_increment)) {
+        return Err(format!("Price {} is not a multiple of the quote increment {} of {}", price, pair.quote_increment, pair.symbol));
+      }
+      (size, price)
+    }
+  };
+
+  if size <= zero || price.map(|price| price <= zero).unwrap_or(false) {
+    return Err(format!("Size {} or price {:?} rounds to 0 on {}", size, price, pair.symbol));
+  }
+  if let Some(min_size) = pair.min_base_trade_size.filter(|min_size| size < *min_size) {
+    return Err(format!("Size {} is below the minimum size {} of {}", size, min_size, pair.symbol));
+  }
+  // Market orders have no price to check the notional against, the exchange does it on fill
+  if let (Some(price), Some(min_notional)) = (price, pair.min_quote_trade_size) {
+    if size * price < min_notional {
+      return Err(format!("Notional {} ({} at {}) is below the minimum notional {} of {}", size * price, size, price, min_notional, pair.symbol));
+    }
+  }
+  Ok((size, price))
+}
+
+// Checks or rounds to the nearest increment a price that doesn't decide the fill price, such as a stop trigger
+pub fn normalize_trigger(pair: &MarketPair, mode: FFIValidationMode, price: Decimal) -> Result<Decimal, String> {
+  match mode {
+    FFIValidationMode::Off => Ok(price),
+    FFIValidationMode::Round if pair.quote_increment.is_zero() => Ok(price),
+    FFIValidationMode::Round => Ok(((price / pair.quote_increment).round() * pair.quote_increment).normalize()),
+    FFIValidationMode::Reject if is_multiple(price, pair.quote_increment) => Ok(price),
+    FFIValidationMode::Reject => Err(format!("Trigger price {} is not a multiple of the quote increment {} of {}", price, pair.quote_increment, pair.symbol)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::FromStr;
+  use super::*;
+
+  fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+  }
+
+  fn pair(base_increment: &str, quote_increment: &str, min_base_trade_size: Option<&str>, min_quote_trade_size: Option<&str>) -> MarketPair {
+    MarketPair {
+      base: String::from("BTC"),
+      quote: String::from("USDT"),
+      symbol: String::from("BTCUSDT"),
+      base_increment: dec(base_increment),
+      quote_increment: dec(quote_increment),
+      min_base_trade_size: min_base_trade_size.map(dec),
+      min_quote_trade_size: min_quote_trade_size.map(dec),
+    }
+  }
+
+  #[test]
+  fn round_mode_rounds_buy_prices_down_and_sell_prices_up() {
+    let pair = pair("0.001", "0.01", None, None);
+    let buy = normalize(&pair, FFIValidationMode::Round, FFISide::Buy, dec("1.23456"), Some(dec("100.011")));
+    assert_eq!(buy, Ok((dec("1.234"), Some(dec("100.01")))));
+    let sell = normalize(&pair, FFIValidationMode::Round, FFISide::Sell, dec("1.23456"), Some(dec("100.011")));
+    assert_eq!(sell, Ok((dec("1.234"), Some(dec("100.02")))));
+    let on_increment = normalize(&pair, FFIValidationMode::Round, FFISide::Sell, dec("1.234"), Some(dec("100.01")));
+    assert_eq!(on_increment, Ok((dec("1.234"), Some(dec("100.01")))));
+  }
+
+  #[test]
+  fn reject_mode_rejects_values_off_the_increments() {
+    let pair = pair("0.001", "0.01", None, None);
+    assert!(normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("1.2345"), Some(dec("100.01"))).is_err());
+    assert!(normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("1.234"), Some(dec("100.015"))).is_err());
+    assert_eq!(
+      normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("1.234"), Some(dec("100.01"))),
+      Ok((dec("1.234"), Some(dec("100.01"))))
+    );
+    assert_eq!(
+      normalize(&pair, FFIValidationMode::Off, FFISide::Buy, dec("1.2345"), Some(dec("100.015"))),
+      Ok((dec("1.2345"), Some(dec("100.015"))))
+    );
+  }
+
+  #[test]
+  fn values_rounding_to_zero_are_rejected() {
+    let pair = pair("0.001", "0.01", None, None);
+    assert!(normalize(&pair, FFIValidationMode::Round, FFISide::Buy, dec("0.0005"), None).is_err());
+    assert!(normalize(&pair, FFIValidationMode::Round, FFISide::Buy, dec("1"), Some(dec("0.004"))).is_err());
+    assert_eq!(
+      normalize(&pair, FFIValidationMode::Round, FFISide::Sell, dec("1"), Some(dec("0.004"))),
+      Ok((dec("1"), Some(dec("0.01"))))
+    );
+    assert!(normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("0"), None).is_err());
+    assert!(normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("1"), Some(dec("-0.01"))).is_err());
+  }
+
+  #[test]
+  fn orders_below_the_minimums_are_rejected() {
+    let pair = pair("0.001", "0.01", Some("0.01"), Some("10"));
+    assert!(normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("0.005"), Some(dec("5000"))).is_err());
+    assert!(normalize(&pair, FFIValidationMode::Round, FFISide::Buy, dec("0.0109"), Some(dec("5000"))).is_ok());
+    // Rounding down can take a size below the minimum
+    assert!(normalize(&pair, FFIValidationMode::Round, FFISide::Buy, dec("0.0099"), Some(dec("5000"))).is_err());
+    assert!(normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("0.05"), Some(dec("100"))).is_err());
+    assert!(normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("0.1"), Some(dec("100"))).is_ok());
+    // Market orders have no notional to check
+    assert!(normalize(&pair, FFIValidationMode::Reject, FFISide::Buy, dec("0.05"), None).is_ok());
+  }
+
+  #[test]
+  fn zero_increments_leave_values_unchanged() {
+    let pair = pair("0", "0", None, None);
+    assert_eq!(
+      normalize(&pair, FFIValidationMode::Round, FFISide::Buy, dec("1.23456"), Some(dec("100.011"))),
+      Ok((dec("1.23456"), Some(dec("100.011"))))
+    );
+    assert_eq!(
+      normalize(&pair, FFIValidationMode::Reject, FFISide::Sell, dec("1.23456"), Some(dec("100.011"))),
+      Ok((dec("1.23456"), Some(dec("100.011"))))
+    );
+    assert_eq!(normalize_trigger(&pair, FFIValidationMode::Round, dec("100.011")), Ok(dec("100.011")));
+    assert_eq!(normalize_trigger(&pair, FFIValidationMode::Reject, dec("100.011")), Ok(dec("100.011")));
+  }
+
+  #[test]
+  fn triggers_round_to_the_nearest_increment() {
+    let pair = pair("0.001", "0.01", None, None);
+    assert_eq!(normalize_trigger(&pair, FFIValidationMode::Round, dec("100.014")), Ok(dec("100.01")));
+    assert_eq!(normalize_trigger(&pair, FFIValidationMode::Round, dec("100.016")), Ok(dec("100.02")));
+    assert!(normalize_trigger(&pair, FFIValidationMode::Reject, dec("100.015")).is_err());
+    assert_eq!(normalize_trigger(&pair, FFIValidationMode::Reject, dec("100.01")), Ok(dec("100.01")));
+    assert_eq!(normalize_trigger(&pair, FFIValidationMode::Off, dec("100.015")), Ok(dec("100.015")));
+  }
+}